@@ -16,6 +16,9 @@ pub struct CharacterStats {
 pub struct ItemStats {
     pub damage: f32,
     pub range: i32,
+    pub noise: f32,
+    // explosives hit everything in this radius where they land
+    pub blast: i32,
 }
 
 impl CharacterStats {
@@ -94,6 +97,12 @@ impl InTurn {
     pub fn action_done(&mut self) {
         self.state = InTurnState::Idle;
     }
+
+    // gives up the remaining action points
+    pub fn end(&mut self) {
+        self.state = InTurnState::Idle;
+        self.action_points = 0;
+    }
 }
 
 impl Component for InTurn {
@@ -0,0 +1,67 @@
+use specs::{ Component, HashMapStorage };
+use components::progress::{ AreaInstance };
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FactionInstance {
+    Kayaba,
+    Yasuda,
+    Nichirei,
+    PrimeSec,
+}
+
+impl FactionInstance {
+    pub fn values() -> [Self; 4] {
+        use self::FactionInstance::*;
+        [Kayaba, Yasuda, Nichirei, PrimeSec]
+    }
+
+    pub fn from_name(name: &str) -> Option<FactionInstance> {
+        FactionInstance::values().iter()
+            .cloned()
+            .find(|f| format!("{:?}", f) == name)
+    }
+
+    pub fn areas(&self) -> &'static [AreaInstance] {
+        use self::FactionInstance::*;
+        use components::progress::AreaInstance::*;
+        match *self {
+            Kayaba => &[KayabaIndustries, KayabaRoboticsLabs],
+            Yasuda => &[YasudaLife],
+            Nichirei => &[NichireiRestaurant, NichireiLabs],
+            PrimeSec => &[PrimeSecHQ, MainFrame],
+        }
+    }
+
+    pub fn owns(&self, area: AreaInstance) -> bool {
+        self.areas().contains(&area)
+    }
+
+    pub fn owner(area: AreaInstance) -> Option<FactionInstance> {
+        FactionInstance::values().iter()
+            .cloned()
+            .find(|f| f.owns(area))
+    }
+
+    // areas nobody without access is supposed to be in
+    pub fn is_restricted(&self, area: AreaInstance) -> bool {
+        use components::progress::AreaInstance::*;
+        self.owns(area) && match area {
+            KayabaRoboticsLabs | NichireiLabs | PrimeSecHQ | MainFrame => true,
+            _ => false,
+        }
+    }
+}
+
+pub struct Faction {
+    pub instance: FactionInstance,
+}
+
+impl Faction {
+    pub fn new(instance: FactionInstance) -> Self {
+        Faction { instance: instance }
+    }
+}
+
+impl Component for Faction {
+    type Storage = HashMapStorage<Faction>;
+}
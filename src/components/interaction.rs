@@ -1,13 +1,13 @@
+use std::sync::{ Arc };
 use components::appearance::{ Renderable };
+use components::common::{ Description };
 use components::item::{ Item, ItemInstance };
+use interactables::{ InteractableKind, InteractableState, StateChange, Requirement };
+use memory::{ LoopMemory };
 use specs::{ Component, HashMapStorage, Entity };
-use tcod::colors::{ self };
 
-
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum InteractableInstance {
-    KeyDoor(i32, bool)
-}
+// how many keycard levels a lockdown adds to every interactable of a floor
+const LOCKDOWN_LEVEL_INCREASE: i32 = 2;
 
 pub struct Interaction {
     pub actor: Entity,
@@ -17,77 +17,137 @@ impl Component for Interaction {
 }
 
 pub struct Interactable {
-    state: InteractableInstance,
-    initial_state: InteractableInstance,
+    kind: Arc<InteractableKind>,
+    state: usize,
+    initial_state: usize,
+    // security level checked by keycard requirements
+    pub level: i32,
+    initial_level: i32,
+    // direction in radians, used by anything that looks around
+    pub heading: f32,
 }
 
 impl Interactable {
-    pub fn new(instance: InteractableInstance) -> Self {
-        Interactable { state: instance, initial_state: instance }
+    pub fn new(kind: Arc<InteractableKind>, level: i32) -> Self {
+        Interactable {
+            kind: kind,
+            state: 0,
+            initial_state: 0,
+            level: level,
+            initial_level: level,
+            heading: 0.0,
+        }
     }
 
-    fn key_door_new_state(min_level: i32, open: bool, tool: Option<&Item>) -> bool {
-        if open {
-            false
+    pub fn facing(mut self, heading: f32) -> Self {
+        self.heading = heading;
+        self
+    }
+
+    fn current(&self) -> &InteractableState {
+        &self.kind.states[self.state]
+    }
+
+    fn is_met(&self, requirement: &Requirement, tool: Option<&Item>, memory: &LoopMemory) -> bool {
+        match *requirement {
+            Requirement::None => true,
+            Requirement::KeyCard => self.level <= 0 || match tool.map(|i| i.instance) {
+                Some(ItemInstance::KeyCard(level)) => level >= self.level,
+                _ => false,
+            },
+            Requirement::KeyCardLevel(min_level) => match tool.map(|i| i.instance) {
+                Some(ItemInstance::KeyCard(level)) => level >= min_level,
+                _ => false,
+            },
+            Requirement::Item(instance) => tool.map_or(false, |i| i.instance == instance),
+            Requirement::Knows(ref fact) => memory.knows(fact),
+        }
+    }
+
+    fn apply(&mut self, changes: &[StateChange], tools: &[Option<&Item>], memory: &LoopMemory) -> bool {
+        let next = changes.iter()
+            .filter(|c| c.from.map_or(true, |from| from == self.state))
+            .filter(|c| tools.iter().any(|tool| self.is_met(&c.requirement, *tool, memory)))
+            .map(|c| c.to)
+            .next();
+        if let Some(to) = next {
+            self.state = to;
+            true
         } else {
-            if let Some(item) = tool {
-                match item.instance {
-                    ItemInstance::KeyCard(level) => level >= min_level,
-                    _ => min_level == 0
-                }
-            } else {
-               min_level == 0
-            }
+            false
         }
     }
 
-    pub fn interact_with(&mut self, active: Option<&Item>, passive: Option<&Item>, clothing: Option<&Item>) {
-        use self::InteractableInstance::*;
-        self.state = match self.state {
-            KeyDoor(min_level, open) => {
-                let mut new_open = Interactable::key_door_new_state(min_level, open, active);
-                if open == new_open {
-                    new_open = Interactable::key_door_new_state(min_level, open, passive);
-                }
-                KeyDoor(min_level, new_open)
-            }
+    pub fn interact_with(&mut self, active: Option<&Item>, passive: Option<&Item>, clothing: Option<&Item>,
+                         memory: &LoopMemory) -> bool {
+        let kind = self.kind.clone();
+        self.apply(&kind.interactions, &[active, passive, clothing], memory)
+    }
+
+    pub fn trigger(&mut self, event: &str) -> bool {
+        let kind = self.kind.clone();
+        match kind.triggers.get(event) {
+            Some(changes) => self.apply(changes, &[None], &LoopMemory::default()),
+            None => false,
         }
     }
 
-    pub fn get_renderable(&self) -> Renderable {
-        use self::InteractableInstance::*;
-        match self.state {
-            KeyDoor(_level, open) => {
-                let mut c = '_';
-                if !open {
-                    c = 'D';
-                }
-                let color = colors::GOLD;
-                Renderable::new(c, color)
-            }
+    pub fn lockdown(&mut self) {
+        self.level += LOCKDOWN_LEVEL_INCREASE;
+        self.trigger("lockdown");
+    }
+
+    pub fn kind(&self) -> &str {
+        &self.kind.id
+    }
+
+    // the security level it was placed with, before any lockdown
+    pub fn initial_level(&self) -> i32 {
+        self.initial_level
+    }
+
+    pub fn state(&self) -> usize {
+        self.state
+    }
+
+    pub fn set_state(&mut self, state: usize) {
+        if state < self.kind.states.len() {
+            self.state = state;
         }
     }
 
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.current().tags.iter().any(|t| t == tag)
+    }
+
+    // values of all tags of the form <prefix><value>
+    pub fn tagged(&self, prefix: &str) -> Vec<String> {
+        self.current().tags.iter()
+            .filter(|t| t.starts_with(prefix))
+            .map(|t| t[prefix.len()..].to_string())
+            .collect()
+    }
+
+    pub fn get_renderable(&self) -> Renderable {
+        let state = self.current();
+        Renderable::new(state.character, state.color)
+    }
+
+    pub fn get_description(&self) -> Description {
+        Description::new(&self.kind.name, &self.kind.description)
+    }
+
     pub fn is_blocking(&self) -> bool {
-        use self::InteractableInstance::*;
-        match self.state {
-            KeyDoor(_level, open) => {
-                !open
-            }
-        }
+        self.current().blocking
     }
 
     pub fn is_sight_blocking(&self) -> bool {
-        use self::InteractableInstance::*;
-        match self.state {
-            KeyDoor(_level, open) => {
-                !open
-            }
-        }
+        self.current().sight_blocking
     }
 
     pub fn reset(&mut self) {
         self.state = self.initial_state;
+        self.level = self.initial_level;
     }
 }
 
@@ -95,5 +155,3 @@ impl Interactable {
 impl Component for Interactable {
     type Storage = HashMapStorage<Interactable>;
 }
-
-
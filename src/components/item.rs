@@ -1,6 +1,7 @@
 use specs::{ Component, VecStorage };
 use components::appearance::{ Renderable };
 use components::common::{ Description, ItemStats };
+use components::faction::{ FactionInstance };
 use tcod::colors::{ self };
 
 #[derive(PartialEq, Debug, Copy, Clone)]
@@ -33,6 +34,11 @@ pub enum ItemInstance {
     Shuriken,
     Simstim,
     Watch,
+    Uniform(FactionInstance),
+    LabCoat,
+    Flashlight,
+    Radio,
+    Grenade,
 }
 
 pub struct Item {
@@ -43,7 +49,58 @@ impl Component for Item {
     type Storage = VecStorage<Item>;
 }
 
+impl ItemInstance {
+    pub fn from_name(name: &str) -> Option<ItemInstance> {
+        use self::ItemInstance::*;
+        match name {
+            "DartGun" => Some(DartGun),
+            "FlickKnife" => Some(FlickKnife),
+            "HitachiRam" => Some(HitachiRam),
+            "Lighter" => Some(Lighter),
+            "Manriki" => Some(Manriki),
+            "PocketVtr" => Some(PocketVtr),
+            "GasMask" => Some(GasMask),
+            "Shuriken" => Some(Shuriken),
+            "Simstim" => Some(Simstim),
+            "Watch" => Some(Watch),
+            "LabCoat" => Some(LabCoat),
+            "Flashlight" => Some(Flashlight),
+            "Radio" => Some(Radio),
+            "Grenade" => Some(Grenade),
+            // parameterized items are written like they are printed, e.g. KeyCard(3)
+            _ if name.starts_with("KeyCard(") && name.ends_with(')') =>
+                name[8..name.len() - 1].parse().ok().map(KeyCard),
+            _ if name.starts_with("Uniform(") && name.ends_with(')') =>
+                FactionInstance::from_name(&name[8..name.len() - 1]).map(Uniform),
+            _ => None,
+        }
+    }
+}
+
 impl Item {
+    pub fn is_clothing(&self) -> bool {
+        use self::ItemInstance::*;
+        match self.instance {
+            Uniform(_) | LabCoat => true,
+            _ => false,
+        }
+    }
+
+    // only bullets leave holes in walls
+    pub fn is_firearm(&self) -> bool {
+        self.instance == ItemInstance::DartGun
+    }
+
+    // how far the item lights up its surroundings while held
+    pub fn light_radius(&self) -> Option<i32> {
+        use self::ItemInstance::*;
+        match self.instance {
+            Lighter => Some(3),
+            Flashlight => Some(7),
+            _ => None,
+        }
+    }
+
     pub fn get_renderable(&self) -> Renderable {
         use self::Type::*;
         use self::Rarity::*;
@@ -67,10 +124,11 @@ impl Item {
     pub fn get_stats(&self) -> Option<ItemStats> {
         use self::ItemInstance::*;
         match self.instance {
-            FlickKnife => Some (ItemStats { damage: 20.0, range: 1 }),
-            Shuriken => Some (ItemStats { damage: 20.0, range: 5 }),
-            Manriki => Some (ItemStats { damage: 40.0, range: 2 }),
-            DartGun => Some (ItemStats { damage: 80.0, range: 10 }),
+            FlickKnife => Some (ItemStats { damage: 20.0, range: 1, noise: 2.0, blast: 0 }),
+            Shuriken => Some (ItemStats { damage: 20.0, range: 5, noise: 3.0, blast: 0 }),
+            Manriki => Some (ItemStats { damage: 40.0, range: 2, noise: 4.0, blast: 0 }),
+            DartGun => Some (ItemStats { damage: 80.0, range: 10, noise: 12.0, blast: 0 }),
+            Grenade => Some (ItemStats { damage: 60.0, range: 6, noise: 20.0, blast: 2 }),
             _ => None,
         }
     }
@@ -81,10 +139,13 @@ impl Item {
         use self::Rarity::*;
         match self.instance {
             Lighter | Watch => (Common, Item),
+            Flashlight => (Common, Equipment),
+            Radio => (Uncommon, Item),
             FlickKnife => (Common, Weapon),
 
-            PocketVtr | GasMask => (Uncommon, Equipment),
-            Manriki | Shuriken => (Uncommon, Weapon),
+            PocketVtr | GasMask | LabCoat => (Uncommon, Equipment),
+            Uniform(_) => (Rare, Equipment),
+            Manriki | Shuriken | Grenade => (Uncommon, Weapon),
 
             Simstim => (Rare, Equipment),
             DartGun => (Rare, Weapon),
@@ -114,6 +175,11 @@ impl Item {
             Shuriken => Description::new("Shuriken", "A traditional japanese conceiled weapon"),
             Simstim => Description::new("Simstim deck", "Remotly simulates stimuli captured from another person to the wearer"),
             Watch => Description::new("Watch", "A plastic watch"),
+            Uniform(faction) => Description::new(&format!("{:?} uniform", faction), &format!("Nobody looks twice at {:?} staff", faction)),
+            Radio => Description::new("Radio", "A walkie-talkie. Whoever carries the other one can tell you what they see"),
+            Grenade => Description::new("Grenade", "A military fragmentation grenade. Walls and doors do not stop it for long"),
+            Flashlight => Description::new("Flashlight", "A heavy rubber flashlight, bright enough to be seen from afar"),
+            LabCoat => Description::new("Lab coat", "A white coat with a pocket protector. Blends in at any lab"),
             KeyCard(level) => Description::new(&format!("Level {} keycard", level), &format!("A plastic keycard giving access to security level {}", level)),
         }
     }
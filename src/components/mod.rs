@@ -7,3 +7,6 @@ pub mod space;
 pub mod player;
 pub mod item;
 pub mod npc;
+pub mod stealth;
+pub mod schedule;
+pub mod faction;
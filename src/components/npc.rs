@@ -1,3 +1,4 @@
+use std::f32::consts::{ PI };
 use tcod::colors::{ self };
 use rand::{ Rng };
 use base64::{ encode };
@@ -5,11 +6,14 @@ use specs::{ Component, HashMapStorage };
 use components::common::{ CharacterStats, Description };
 use components::inventory::{ Inventory };
 use components::appearance::{ Renderable };
+use components::space::{ Vector };
+use components::stealth::{ Vision, Hearing };
 
 pub struct Npc {
     pub instance: NpcInstance,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum NpcInstance {
     Guard,
     Grunt,
@@ -17,10 +21,38 @@ pub enum NpcInstance {
     Technician
 }
 
+impl NpcInstance {
+    pub fn values() -> [Self; 4] {
+        use self::NpcInstance::*;
+        [Guard, Grunt, Accountant, Technician]
+    }
+
+    pub fn from_name(name: &str) -> Option<NpcInstance> {
+        NpcInstance::values().iter()
+            .cloned()
+            .find(|n| format!("{:?}", n) == name)
+    }
+}
+
 impl Component for Npc {
     type Storage = HashMapStorage<Npc>;
 }
 
+// the id of the dialogue tree an npc talks with
+pub struct Dialogue {
+    pub id: String,
+}
+
+impl Dialogue {
+    pub fn new(id: &str) -> Self {
+        Dialogue { id: id.to_string() }
+    }
+}
+
+impl Component for Dialogue {
+    type Storage = HashMapStorage<Dialogue>;
+}
+
 impl Npc {
     pub fn get_renderable(&self) -> Renderable {
         use self::NpcInstance::*;
@@ -50,4 +82,28 @@ impl Npc {
     pub fn get_stats(&self) -> CharacterStats {
         CharacterStats { health: 100.0, max_health: 100.0 }
     }
+
+    pub fn get_vision(&self, direction: Vector) -> Vision {
+        use self::NpcInstance::*;
+        match self.instance {
+            Guard | Grunt => Vision::new(direction, PI / 2.0, 8),
+            Accountant | Technician => Vision::new(direction, PI / 3.0, 5),
+        }
+    }
+
+    pub fn get_hearing(&self) -> Hearing {
+        use self::NpcInstance::*;
+        match self.instance {
+            Guard | Grunt => Hearing { threshold: 1.0 },
+            Accountant | Technician => Hearing { threshold: 3.0 },
+        }
+    }
+
+    pub fn is_guard(&self) -> bool {
+        use self::NpcInstance::*;
+        match self.instance {
+            Guard | Grunt => true,
+            Accountant | Technician => false,
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::{ HashMap };
+use std::collections::{ HashMap, VecDeque };
 use specs::{ Component, HashMapStorage, Entity };
 use components::space::{ Level };
 
@@ -8,6 +8,13 @@ impl Component for Player {
     type Storage = HashMapStorage<Player>;
 }
 
+// a player controlled from the other end of a co-op game, never from this keyboard
+pub struct Remote;
+
+impl Component for Remote {
+    type Storage = HashMapStorage<Remote>;
+}
+
 pub struct Fov {
     pub fov_map: HashMap<Level, usize>,
 }
@@ -34,4 +41,48 @@ impl Equipment {
             clothing: None,
         }
     }
+
+    // empties every slot the item is in, once it changed hands
+    pub fn unequip(&mut self, item: Entity) {
+        if self.active_item == Some(item) {
+            self.active_item = None;
+        }
+        if self.passive_item == Some(item) {
+            self.passive_item = None;
+        }
+        if self.clothing == Some(item) {
+            self.clothing = None;
+        }
+    }
+}
+
+// what a player does while the other one is controlled
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Order {
+    Hold,
+    Follow(Entity),
+    Guard((i32, i32)),
+}
+
+pub struct Orders {
+    pub order: Order,
+    // waypoints walked one after the other
+    pub queue: VecDeque<(i32, i32)>,
+}
+
+impl Component for Orders {
+    type Storage = HashMapStorage<Orders>;
+}
+
+impl Orders {
+    pub fn new() -> Self {
+        Orders {
+            order: Order::Hold,
+            queue: VecDeque::new(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Orders::new();
+    }
 }
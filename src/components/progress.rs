@@ -89,6 +89,12 @@ impl AreaInstance {
         [FuyoPenthouse, MainFrame, ObservationDeck, Entrance, NichireiRestaurant, NichireiLabs,
         YasudaLife, KayabaIndustries, KayabaRoboticsLabs, PrimeSecHQ]
     }
+
+    pub fn from_name(name: &str) -> Option<AreaInstance> {
+        AreaInstance::values().iter()
+            .cloned()
+            .find(|a| format!("{:?}", a) == name)
+    }
 }
 
 impl Milestone {
@@ -0,0 +1,105 @@
+use specs::{ Component, HashMapStorage };
+use components::space::{ Level };
+
+// a place an npc goes to at a fixed time of the loop
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Errand {
+    // seconds since the start of the loop
+    pub at: f32,
+    pub level: Level,
+    pub target: (i32, i32),
+}
+
+pub struct Schedule {
+    errands: Vec<Errand>,
+    // the errand that was completed or given up
+    finished: Option<usize>,
+}
+
+impl Component for Schedule {
+    type Storage = HashMapStorage<Schedule>;
+}
+
+// parses a loop clock like "02:30"
+pub fn parse_clock(clock: &str) -> Option<f32> {
+    let mut parts = clock.split(':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(minutes), Some(seconds), None) => match (minutes.parse::<u32>(), seconds.parse::<u32>()) {
+            (Ok(m), Ok(s)) if s < 60 => Some((m * 60 + s) as f32),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// the loop clock of a number of seconds, 150 is "02:30"
+pub fn format_clock(at: f32) -> String {
+    let seconds = at as u32;
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Schedule { errands: vec![], finished: None }
+    }
+
+    pub fn at(mut self, clock: &str, level: Level, target: (i32, i32)) -> Self {
+        let at = parse_clock(clock).expect("invalid loop clock");
+        self.errands.push(Errand { at: at, level: level, target: target });
+        self.errands.sort_by(|a, b| a.at.partial_cmp(&b.at).unwrap());
+        self
+    }
+
+    // the latest errand that is due and not yet finished
+    pub fn current(&self, elapsed: f32) -> Option<(usize, Errand)> {
+        let index = match self.errands.iter().rposition(|e| e.at <= elapsed) {
+            Some(index) => index,
+            None => return None,
+        };
+        if self.finished == Some(index) {
+            None
+        } else {
+            Some((index, self.errands[index]))
+        }
+    }
+
+    pub fn errands(&self) -> &[Errand] {
+        &self.errands
+    }
+
+    pub fn finish(&mut self, index: usize) {
+        self.finished = Some(index);
+    }
+
+    pub fn reset(&mut self) {
+        self.finished = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use components::schedule::{ Schedule, parse_clock };
+    use components::space::{ Level };
+
+    #[test]
+    fn parses_clock() {
+        assert!(parse_clock("02:30") == Some(150.0));
+        assert!(parse_clock("7:10") == Some(430.0));
+        assert!(parse_clock("02:60").is_none());
+        assert!(parse_clock("0230").is_none());
+    }
+
+    #[test]
+    fn follows_errands_in_order() {
+        let mut schedule = Schedule::new()
+            .at("05:00", Level::Tower(0), (5, 5))
+            .at("02:30", Level::Tower(0), (1, 1));
+        assert!(schedule.current(100.0).is_none());
+        assert!(schedule.current(200.0).unwrap().1.target == (1, 1));
+        schedule.finish(0);
+        assert!(schedule.current(200.0).is_none());
+        assert!(schedule.current(300.0).unwrap().1.target == (5, 5));
+        schedule.reset();
+        assert!(schedule.current(200.0).unwrap().0 == 0);
+    }
+}
@@ -105,12 +105,23 @@ impl Component for Level {
 
 pub struct Viewport {
     r: Rect,
+    // where the viewport starts on the map console
+    screen: (i32, i32),
 }
 
 impl Viewport {
     pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
         Viewport {
-            r: Rect::new(x, y, w, h)
+            r: Rect::new(x, y, w, h),
+            screen: (0, 0),
+        }
+    }
+
+    // a smaller view drawn at a screen position on top of the main one
+    pub fn inset(screen: (i32, i32), w: i32, h: i32) -> Self {
+        Viewport {
+            r: Rect::new(0, 0, w, h),
+            screen: screen,
         }
     }
 
@@ -118,12 +129,17 @@ impl Viewport {
         self.r.center_at(p.x as i32, p.y as i32);
     }
 
+    pub fn pan(self: &mut Self, dx: i32, dy: i32) {
+        let (x, y) = self.r.center();
+        self.r.center_at(x + dx, y + dy);
+    }
+
     pub fn transform(self: &Self, p: (i32, i32)) -> (i32, i32) {
-        (p.0 - self.r.left(), p.1 - self.r.top())
+        (p.0 - self.r.left() + self.screen.0, p.1 - self.r.top() + self.screen.1)
     }
 
     pub fn inv_transform(self: &Self, p: (i32, i32)) -> (i32, i32) {
-        (p.0 + self.r.left(), p.1 + self.r.top())
+        (p.0 + self.r.left() - self.screen.0, p.1 + self.r.top() - self.screen.1)
     }
 
     pub fn into_iter(self: &Self) -> RectIter {
@@ -134,3 +150,26 @@ impl Viewport {
         self.r.is_enclosed(p)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use components::space::{ Position, Viewport };
+
+    #[test]
+    fn insets_are_drawn_at_their_screen_position() {
+        let mut inset = Viewport::inset((60, 33), 20, 10);
+        inset.center_at(Position { x: 40.0, y: 20.0 });
+        assert!(inset.transform((30, 15)) == (60, 33));
+        assert!(inset.transform((40, 20)) == (70, 38));
+        assert!(inset.inv_transform((70, 38)) == (40, 20));
+        assert!(inset.inv_transform(inset.transform((35, 22))) == (35, 22));
+    }
+
+    #[test]
+    fn the_main_view_starts_at_the_screen_origin() {
+        let mut view = Viewport::new(0, 0, 80, 43);
+        view.center_at(Position { x: 50.0, y: 30.0 });
+        assert!(view.transform((10, 9)) == (0, 0));
+        assert!(view.inv_transform((0, 0)) == (10, 9));
+    }
+}
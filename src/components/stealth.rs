@@ -0,0 +1,158 @@
+use specs::{ Component, HashMapStorage };
+use components::space::{ Vector };
+use geometry::{ Triangle, Shape };
+
+const SUSPICIOUS_THRESHOLD: f32 = 0.3;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AlertState {
+    Unaware,
+    Suspicious,
+    Alerted,
+}
+
+pub struct Vision {
+    pub direction: Vector,
+    pub initial_direction: Vector,
+    pub angle: f32,
+    pub range: i32,
+}
+
+impl Component for Vision {
+    type Storage = HashMapStorage<Vision>;
+}
+
+impl Vision {
+    pub fn new(direction: Vector, angle: f32, range: i32) -> Self {
+        let d = direction.norm();
+        Vision { direction: d, initial_direction: d, angle: angle, range: range }
+    }
+
+    pub fn cone(&self, p: (i32, i32)) -> Triangle {
+        let d = self.direction.norm();
+        let perpendicular = Vector { x: -d.y, y: d.x };
+        let reach = self.range as f32;
+        let spread = reach * (self.angle / 2.0).tan();
+        let tip = (p.0 as f32 + d.x * reach, p.1 as f32 + d.y * reach);
+        let left = ((tip.0 + perpendicular.x * spread).round() as i32,
+                    (tip.1 + perpendicular.y * spread).round() as i32);
+        let right = ((tip.0 - perpendicular.x * spread).round() as i32,
+                     (tip.1 - perpendicular.y * spread).round() as i32);
+        Triangle::new(p, left, right)
+    }
+
+    pub fn is_in_cone(&self, from: (i32, i32), to: (i32, i32)) -> bool {
+        let dx = (to.0 - from.0) as f32;
+        let dy = (to.1 - from.1) as f32;
+        (dx * dx + dy * dy).sqrt() <= self.range as f32 && self.cone(from).is_enclosed(to)
+    }
+
+    pub fn look_at(&mut self, direction: Vector) {
+        if direction.length() > 0.0 {
+            self.direction = direction.norm();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.direction = self.initial_direction;
+    }
+}
+
+pub struct Awareness {
+    pub value: f32,
+    pub state: AlertState,
+}
+
+impl Component for Awareness {
+    type Storage = HashMapStorage<Awareness>;
+}
+
+impl Awareness {
+    pub fn new() -> Self {
+        Awareness { value: 0.0, state: AlertState::Unaware }
+    }
+
+    pub fn notice(&mut self, amount: f32) {
+        self.value = (self.value + amount).min(1.0);
+        self.update_state();
+    }
+
+    pub fn calm(&mut self, amount: f32) {
+        // once alerted, an npc stays alerted for the rest of the loop
+        if self.state != AlertState::Alerted {
+            self.value = (self.value - amount).max(0.0);
+            self.update_state();
+        }
+    }
+
+    pub fn alert(&mut self) {
+        self.value = 1.0;
+        self.state = AlertState::Alerted;
+    }
+
+    pub fn is_alerted(&self) -> bool {
+        self.state == AlertState::Alerted
+    }
+
+    pub fn reset(&mut self) {
+        *self = Awareness::new();
+    }
+
+    fn update_state(&mut self) {
+        self.state = if self.value >= 1.0 {
+            AlertState::Alerted
+        } else if self.value >= SUSPICIOUS_THRESHOLD {
+            AlertState::Suspicious
+        } else {
+            AlertState::Unaware
+        };
+    }
+}
+
+pub struct Hearing {
+    pub threshold: f32,
+}
+
+impl Component for Hearing {
+    type Storage = HashMapStorage<Hearing>;
+}
+
+pub struct Sneaking;
+impl Component for Sneaking {
+    type Storage = HashMapStorage<Sneaking>;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{ PI };
+    use components::space::{ Vector };
+    use components::stealth::{ Vision, Awareness, AlertState };
+
+    #[test]
+    fn sees_inside_cone() {
+        let vision = Vision::new(Vector { x: 1.0, y: 0.0 }, PI / 2.0, 8);
+        assert!(vision.is_in_cone((10, 10), (15, 10)));
+        assert!(vision.is_in_cone((10, 10), (15, 12)));
+    }
+
+    #[test]
+    fn does_not_see_behind_or_beyond() {
+        let vision = Vision::new(Vector { x: 1.0, y: 0.0 }, PI / 2.0, 8);
+        assert!(!vision.is_in_cone((10, 10), (5, 10)));
+        assert!(!vision.is_in_cone((10, 10), (10, 15)));
+        assert!(!vision.is_in_cone((10, 10), (19, 10)));
+    }
+
+    #[test]
+    fn awareness_rises_and_sticks_when_alerted() {
+        let mut awareness = Awareness::new();
+        awareness.notice(0.5);
+        assert!(awareness.state == AlertState::Suspicious);
+        awareness.calm(0.4);
+        assert!(awareness.state == AlertState::Unaware);
+        awareness.notice(2.0);
+        assert!(awareness.state == AlertState::Alerted);
+        awareness.calm(1.0);
+        assert!(awareness.is_alerted());
+    }
+}
@@ -0,0 +1,476 @@
+use std::fs::{ File };
+use std::io::{ Read };
+use std::sync::{ Arc };
+use std::collections::{ HashMap, HashSet };
+use specs::{ World, Entity };
+use tcod::colors::{ self };
+use tcod::input::{ KeyCode };
+
+use engine::state::{ State, Transition };
+use engine::tcod::{ Tcod };
+use engine::input_handler::{ InputHandler };
+use components::common::{ Description, MoveToPosition };
+use components::inventory::{ Inventory };
+use components::player::{ Equipment };
+use components::item::{ Item, ItemInstance };
+use components::npc::{ Dialogue };
+use components::progress::{ Event, AreaInstance };
+use components::space::{ Position, Level };
+use interactables::{ DefinitionError, error };
+use game_state::{ GameState };
+use memory::{ LoopMemory };
+use tower::{ Tower };
+
+// npcs that were sent somewhere by a conversation do not hurry
+const RELOCATION_SPEED: f32 = 2.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    Remembers(String),
+    Forgot(String),
+    HasItem(ItemInstance),
+    // a keycard of at least this level
+    HasKeyCard(i32),
+    HasAccess(AreaInstance),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Learn(String),
+    // hands over an item from the inventory of the npc
+    Give(ItemInstance),
+    // the npc takes an item from the inventory of the player
+    Take(ItemInstance),
+    Relocate(i32, i32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Choice {
+    pub text: String,
+    // None ends the conversation
+    pub target: Option<String>,
+    pub conditions: Vec<Condition>,
+    pub outcomes: Vec<Outcome>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub text: Vec<String>,
+    pub choices: Vec<Choice>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DialogueTree {
+    pub id: String,
+    pub nodes: HashMap<String, Node>,
+}
+
+impl Condition {
+    pub fn holds(&self, memory: &LoopMemory, items: &[ItemInstance], events: &HashSet<Event>) -> bool {
+        match *self {
+            Condition::Remembers(ref fact) => memory.knows(fact),
+            Condition::Forgot(ref fact) => !memory.knows(fact),
+            Condition::HasItem(instance) => items.contains(&instance),
+            Condition::HasKeyCard(min_level) => items.iter().any(|i| match *i {
+                ItemInstance::KeyCard(level) => level >= min_level,
+                _ => false,
+            }),
+            Condition::HasAccess(area) => events.contains(&Event::HasAccess(area)),
+        }
+    }
+}
+
+impl Choice {
+    pub fn is_available(&self, memory: &LoopMemory, items: &[ItemInstance], events: &HashSet<Event>) -> bool {
+        self.conditions.iter().all(|c| c.holds(memory, items, events))
+    }
+}
+
+fn parse_number(word: &str, line: usize) -> Result<i32, DefinitionError> {
+    match word.parse::<i32>() {
+        Ok(n) => Ok(n),
+        Err(_) => error(line, format!("'{}' is not a number", word)),
+    }
+}
+
+fn parse_item(name: &str, line: usize) -> Result<ItemInstance, DefinitionError> {
+    match ItemInstance::from_name(name) {
+        Some(item) => Ok(item),
+        None => error(line, format!("unknown item '{}'", name)),
+    }
+}
+
+fn parse_condition(words: &[&str], line: usize) -> Result<Condition, DefinitionError> {
+    match words {
+        ["remembers", fact] => Ok(Condition::Remembers(fact.to_string())),
+        ["forgot", fact] => Ok(Condition::Forgot(fact.to_string())),
+        ["has_item", name] => Ok(Condition::HasItem(parse_item(name, line)?)),
+        ["has_keycard", level] => Ok(Condition::HasKeyCard(parse_number(level, line)?)),
+        ["has_access", name] => match AreaInstance::from_name(name) {
+            Some(area) => Ok(Condition::HasAccess(area)),
+            None => error(line, format!("unknown area '{}'", name)),
+        },
+        _ => error(line, format!("invalid condition '{}'", words.join(" "))),
+    }
+}
+
+fn parse_outcome(words: &[&str], line: usize) -> Result<Outcome, DefinitionError> {
+    match words {
+        ["learn", fact] => Ok(Outcome::Learn(fact.to_string())),
+        ["give", "item", name] => Ok(Outcome::Give(parse_item(name, line)?)),
+        ["give", "keycard", level] => Ok(Outcome::Give(ItemInstance::KeyCard(parse_number(level, line)?))),
+        ["take", "item", name] => Ok(Outcome::Take(parse_item(name, line)?)),
+        ["relocate", x, y] => Ok(Outcome::Relocate(parse_number(x, line)?, parse_number(y, line)?)),
+        _ => error(line, format!("invalid outcome '{}'", words.join(" "))),
+    }
+}
+
+struct TreeBuilder {
+    tree: DialogueTree,
+    node: Option<String>,
+    // line of every choice target, to report targets that were never defined
+    targets: Vec<(usize, String)>,
+}
+
+impl TreeBuilder {
+    fn current_node(&mut self, line: usize) -> Result<&mut Node, DefinitionError> {
+        match self.node {
+            Some(ref name) => Ok(self.tree.nodes.get_mut(name).unwrap()),
+            None => error(line, "expected a node first".into()),
+        }
+    }
+
+    fn current_choice(&mut self, line: usize) -> Result<&mut Choice, DefinitionError> {
+        match self.current_node(line)?.choices.last_mut() {
+            Some(choice) => Ok(choice),
+            None => error(line, "expected a choice first".into()),
+        }
+    }
+
+    fn finish(self, line: usize) -> Result<DialogueTree, DefinitionError> {
+        if !self.tree.nodes.contains_key("start") {
+            return error(line, format!("dialogue '{}' has no start node", self.tree.id));
+        }
+        for &(target_line, ref target) in self.targets.iter() {
+            if !self.tree.nodes.contains_key(target) {
+                return error(target_line, format!("unknown node '{}'", target));
+            }
+        }
+        Ok(self.tree)
+    }
+}
+
+pub struct Dialogues {
+    trees: HashMap<String, Arc<DialogueTree>>,
+}
+
+impl Dialogues {
+    pub fn load(path: &str) -> Result<Self, DefinitionError> {
+        let mut content = String::new();
+        match File::open(path).and_then(|mut f| f.read_to_string(&mut content)) {
+            Ok(_) => Dialogues::parse(&content),
+            Err(e) => error(0, format!("could not read {}: {}", path, e)),
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Self, DefinitionError> {
+        let mut trees = HashMap::new();
+        let mut current: Option<TreeBuilder> = None;
+
+        for (index, raw) in content.lines().enumerate() {
+            let line = index + 1;
+            let text = raw.split('#').next().unwrap().trim();
+            if text.is_empty() {
+                continue;
+            }
+            let words = text.split_whitespace().collect::<Vec<&str>>();
+            let rest = text[words[0].len()..].trim();
+
+            if words[0] == "dialogue" {
+                if current.is_some() {
+                    return error(line, "missing 'end' of previous dialogue".into());
+                }
+                if words.len() != 2 {
+                    return error(line, "expected a single dialogue id".into());
+                }
+                if trees.contains_key(words[1]) {
+                    return error(line, format!("dialogue '{}' is defined twice", words[1]));
+                }
+                current = Some(TreeBuilder {
+                    tree: DialogueTree { id: words[1].to_string(), nodes: HashMap::new() },
+                    node: None,
+                    targets: vec![],
+                });
+                continue;
+            }
+
+            let builder = match current.as_mut() {
+                Some(builder) => builder,
+                None => return error(line, format!("'{}' outside of a dialogue", words[0])),
+            };
+            match words[0] {
+                "node" => {
+                    if words.len() != 2 {
+                        return error(line, "expected a single node name".into());
+                    }
+                    if builder.tree.nodes.contains_key(words[1]) {
+                        return error(line, format!("node '{}' is defined twice", words[1]));
+                    }
+                    builder.tree.nodes.insert(words[1].to_string(), Node { text: vec![], choices: vec![] });
+                    builder.node = Some(words[1].to_string());
+                },
+                "text" => builder.current_node(line)?.text.push(rest.to_string()),
+                "choice" => {
+                    if words.len() < 3 {
+                        return error(line, "expected a target node and a text".into());
+                    }
+                    let target = match words[1] {
+                        "end" => None,
+                        target => {
+                            builder.targets.push((line, target.to_string()));
+                            Some(target.to_string())
+                        },
+                    };
+                    let text = rest[words[1].len()..].trim().to_string();
+                    builder.current_node(line)?.choices.push(Choice {
+                        text: text,
+                        target: target,
+                        conditions: vec![],
+                        outcomes: vec![],
+                    });
+                },
+                "when" => {
+                    let condition = parse_condition(&words[1..], line)?;
+                    builder.current_choice(line)?.conditions.push(condition);
+                },
+                "then" => {
+                    let outcome = parse_outcome(&words[1..], line)?;
+                    builder.current_choice(line)?.outcomes.push(outcome);
+                },
+                "end" => (),
+                other => return error(line, format!("unknown keyword '{}'", other)),
+            }
+            if words[0] == "end" {
+                let tree = current.take().unwrap().finish(line)?;
+                trees.insert(tree.id.clone(), Arc::new(tree));
+            }
+        }
+
+        if let Some(builder) = current {
+            return error(content.lines().count(), format!("dialogue '{}' is missing 'end'", builder.tree.id));
+        }
+        Ok(Dialogues { trees: trees })
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<DialogueTree>> {
+        self.trees.get(id).cloned()
+    }
+}
+
+pub struct Conversation {
+    npc: Entity,
+    actor: Entity,
+    name: String,
+    tree: Option<Arc<DialogueTree>>,
+    node: String,
+}
+
+impl Conversation {
+    pub fn new(npc: Entity, actor: Entity) -> Self {
+        Conversation {
+            npc: npc,
+            actor: actor,
+            name: "".into(),
+            tree: None,
+            node: "start".into(),
+        }
+    }
+
+    fn current(&self) -> Option<&Node> {
+        self.tree.as_ref().and_then(|t| t.nodes.get(&self.node))
+    }
+
+    fn available_choices(&self, world: &World) -> Vec<Choice> {
+        let memory = world.read_resource::<LoopMemory>();
+        let state = world.read_resource::<GameState>();
+        let inventories = world.read::<Inventory>();
+        let items = world.read::<Item>();
+        let carried = inventories.get(self.actor)
+            .map(|inventory| inventory.items.iter()
+                 .filter_map(|i| items.get(*i))
+                 .map(|i| i.instance)
+                 .collect::<Vec<ItemInstance>>())
+            .unwrap_or(vec![]);
+        match self.current() {
+            Some(node) => node.choices.iter()
+                .filter(|c| c.is_available(&memory, &carried, &state.events))
+                .cloned()
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn hand_over(&self, instance: ItemInstance, from: Entity, to: Entity, world: &mut World) {
+        let mut inventories = world.write::<Inventory>();
+        let mut equipments = world.write::<Equipment>();
+        let items = world.read::<Item>();
+        let item = inventories.get(from)
+            .and_then(|inventory| inventory.items.iter()
+                      .cloned()
+                      .find(|i| items.get(*i).map_or(false, |i| i.instance == instance)));
+        if let Some(item) = item {
+            inventories.get_mut(from).unwrap().items.retain(|i| *i != item);
+            if let Some(equipment) = equipments.get_mut(from) {
+                equipment.unequip(item);
+            }
+            if let Some(inventory) = inventories.get_mut(to) {
+                inventory.push(item);
+            }
+        }
+    }
+
+    fn relocate(&self, target: (i32, i32), world: &mut World) {
+        let positions = world.read::<Position>();
+        let levels = world.read::<Level>();
+        let tower = world.read_resource::<Tower>();
+        let mut move_to_positions = world.write::<MoveToPosition>();
+        if let (Some(p), Some(level)) = (positions.get(self.npc), levels.get(self.npc)) {
+            let maps = tower.get(level).unwrap();
+            let path = maps.find_npc_path(&self.npc, (p.x as i32, p.y as i32), target);
+            if path.len() > 0 {
+                move_to_positions.insert(self.npc, MoveToPosition { path: path, speed: RELOCATION_SPEED });
+            }
+        }
+    }
+
+    fn choose(&mut self, choice: &Choice, world: &mut World) -> Transition {
+        for outcome in choice.outcomes.iter() {
+            match *outcome {
+                Outcome::Learn(ref fact) => world.write_resource::<LoopMemory>().learn(fact),
+                Outcome::Give(instance) => self.hand_over(instance, self.npc, self.actor, world),
+                Outcome::Take(instance) => self.hand_over(instance, self.actor, self.npc, world),
+                Outcome::Relocate(x, y) => self.relocate((x, y), world),
+            }
+        }
+        match choice.target {
+            Some(ref target) => {
+                self.node = target.clone();
+                Transition::None
+            },
+            None => Transition::Pop,
+        }
+    }
+}
+
+impl State for Conversation {
+    fn start(&mut self, _tcod: &mut Tcod, world: &mut World) {
+        self.name = world.read::<Description>().get(self.npc)
+            .map_or("".into(), |d| d.name.clone());
+        self.tree = world.read::<Dialogue>().get(self.npc)
+            .and_then(|d| world.read_resource::<Dialogues>().get(&d.id));
+    }
+
+    fn handle_events(&mut self, _tcod: &mut Tcod, world: &mut World) -> Transition {
+        if self.current().is_none() {
+            return Transition::Pop;
+        }
+        let (escape, digit) = {
+            world.write_resource::<InputHandler>().intercept(|input| {
+                (input.is_key_pressed(KeyCode::Escape), input.pressed_digit)
+            })
+        };
+        if escape {
+            return Transition::Pop;
+        }
+        let choices = self.available_choices(world);
+        match digit.and_then(|d| if d > 0 { choices.get(d as usize - 1) } else { None }) {
+            Some(choice) => self.choose(choice, world),
+            None => Transition::None,
+        }
+    }
+
+    fn render(&mut self, tcod: &mut Tcod, world: &mut World) {
+        tcod.clear(colors::BLACK);
+        tcod.render_overlay_text((2, 2), colors::YELLOW, &self.name);
+        let mut y = 4;
+        if let Some(node) = self.current() {
+            for text in node.text.iter() {
+                tcod.render_overlay_text((4, y), colors::LIGHT_GREY, text);
+                y += 1;
+            }
+        }
+        y += 1;
+        for (index, choice) in self.available_choices(world).iter().enumerate() {
+            tcod.render_overlay_text((4, y), colors::WHITE, &format!("{}) {}", index + 1, choice.text));
+            y += 1;
+        }
+        tcod.render_overlay_text((2, y + 1), colors::LIGHT_GREY, "press a number to answer, escape to leave");
+        tcod.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{ HashSet };
+    use dialogue::{ Dialogues, Condition, Outcome };
+    use components::item::{ ItemInstance };
+    use memory::{ LoopMemory };
+
+    const TECHNICIAN: &str = "
+        dialogue technician
+          node start
+            text Not now, the Z-80 is acting up again.
+            choice ram I brought you a memory module
+              when has_item HitachiRam
+              then take item HitachiRam
+              then give keycard 3
+            choice end Bye  # always available
+          node ram
+            text Wonderful! The panel code is 0451, by the way.
+            choice end Thanks
+              then learn panel_code
+        end
+    ";
+
+    #[test]
+    fn parses_tree() {
+        let dialogues = Dialogues::parse(TECHNICIAN).unwrap();
+        let tree = dialogues.get("technician").unwrap();
+        let start = &tree.nodes["start"];
+        assert!(start.text.len() == 1);
+        assert!(start.choices[0].target == Some("ram".into()));
+        assert!(start.choices[0].conditions == vec![Condition::HasItem(ItemInstance::HitachiRam)]);
+        assert!(start.choices[0].outcomes == vec![Outcome::Take(ItemInstance::HitachiRam),
+                                                   Outcome::Give(ItemInstance::KeyCard(3))]);
+        assert!(start.choices[1].target.is_none());
+        assert!(start.choices[1].text == "Bye");
+        assert!(tree.nodes["ram"].choices[0].outcomes == vec![Outcome::Learn("panel_code".into())]);
+    }
+
+    #[test]
+    fn reports_unknown_target() {
+        let error = Dialogues::parse("dialogue a\n  node start\n    choice nowhere Go\nend").err().unwrap();
+        assert!(error.line == 3, "wrong line {}", error.line);
+        assert!(error.message == "unknown node 'nowhere'");
+    }
+
+    #[test]
+    fn checks_conditions() {
+        let mut memory = LoopMemory::default();
+        let events = HashSet::new();
+        let items = [ItemInstance::KeyCard(2)];
+        assert!(Condition::HasKeyCard(2).holds(&memory, &items, &events));
+        assert!(!Condition::HasKeyCard(3).holds(&memory, &items, &events));
+        assert!(Condition::Forgot("code".into()).holds(&memory, &items, &events));
+        memory.learn("code");
+        assert!(Condition::Remembers("code".into()).holds(&memory, &items, &events));
+    }
+
+    #[test]
+    fn shipped_dialogues_parse() {
+        let dialogues = Dialogues::parse(include_str!("../data/dialogues.txt")).unwrap();
+        for id in ["technician", "guard", "accountant"].iter() {
+            assert!(dialogues.get(id).is_some(), "{} is missing", id);
+        }
+    }
+}
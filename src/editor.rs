@@ -0,0 +1,458 @@
+use std::cmp::{ min, max };
+use specs::{ World };
+use tcod::colors::{ self };
+use tcod::input::{ KeyCode };
+
+use engine::state::{ State, Transition };
+use engine::tcod::{ Tcod, VIEW_WIDTH, VIEW_HEIGHT };
+use engine::input_handler::{ InputHandler };
+use components::appearance::{ Renderable };
+use components::faction::{ FactionInstance };
+use components::interaction::{ Interactable };
+use components::item::{ Item, ItemInstance };
+use components::npc::{ Npc, NpcInstance };
+use components::space::{ Viewport, Level };
+use floor_plan::{ FloorPlan, InteractableSpawn, NpcSpawn, ItemSpawn };
+use geometry::{ Shape, Rect, Ellipse, Triangle, Polygon, Line, Ring, RoundedRect, MAX_CORNERS };
+use interactables::{ InteractableRegistry };
+use maps::{ Maps };
+use tower::{ Tower };
+
+const HEADING_STEP: f32 = 45.0;
+const CORNER_RADIUS: i32 = 2;
+// a floor started from scratch
+const NEW_FLOOR: (i32, i32) = (80, 43);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Tool {
+    Room,
+    EllipseRoom,
+    TriangleRoom,
+    Wall,
+    Corridor,
+    Carve,
+    Erase,
+    Interactable,
+    Npc,
+    Item,
+}
+
+impl Tool {
+    // selected with the digit keys 1 to 0
+    fn values() -> [Tool; 10] {
+        use self::Tool::*;
+        [Room, EllipseRoom, TriangleRoom, Wall, Corridor, Carve, Erase, Interactable, Npc, Item]
+    }
+
+    // how many clicks the tool takes
+    fn points(&self) -> usize {
+        match *self {
+            Tool::TriangleRoom => 3,
+            Tool::Room | Tool::EllipseRoom | Tool::Wall | Tool::Corridor | Tool::Carve => 2,
+            _ => 1,
+        }
+    }
+}
+
+// everything the editor can hand out, parameterized items included
+fn item_catalog() -> Vec<ItemInstance> {
+    use components::item::ItemInstance::*;
+    let mut items = vec![DartGun, KeyCard(1), KeyCard(2), KeyCard(3), FlickKnife, HitachiRam, Lighter,
+                         Manriki, PocketVtr, GasMask, Shuriken, Simstim, Watch, LabCoat, Flashlight, Radio,
+                         Grenade];
+    items.extend(FactionInstance::values().iter().map(|f| Uniform(*f)));
+    items
+}
+
+fn corners(a: (i32, i32), b: (i32, i32)) -> Rect {
+    let (x, y) = (min(a.0, b.0), min(a.1, b.1));
+    Rect::new(x, y, (a.0 - b.0).abs() + 1, (a.1 - b.1).abs() + 1)
+}
+
+fn next<T: PartialEq + Copy>(values: &[T], current: T) -> T {
+    let index = values.iter().position(|v| *v == current).unwrap_or(0);
+    values[(index + 1) % values.len()]
+}
+
+// paints the tiles of a floor plan and places what is spawned on it
+pub struct Editor {
+    path: String,
+    plan: FloorPlan,
+    maps: Maps,
+    camera: Viewport,
+    tool: Tool,
+    points: Vec<(i32, i32)>,
+    kinds: Vec<String>,
+    kind: usize,
+    // rounded rooms and rings instead of rectangles and ellipses
+    rounded: bool,
+    // polygons instead of triangles, closed by clicking the first corner again
+    polygon: bool,
+    // the last rectangular room, a ctrl click joins the next one to it
+    last_room: Option<Rect>,
+    npc: NpcInstance,
+    item: ItemInstance,
+    undo: Vec<FloorPlan>,
+    redo: Vec<FloorPlan>,
+    message: String,
+}
+
+impl Editor {
+    pub fn new(path: &str) -> Self {
+        Editor {
+            path: path.to_string(),
+            plan: FloorPlan { size: NEW_FLOOR, ..FloorPlan::default() },
+            maps: Maps::new(NEW_FLOOR.0, NEW_FLOOR.1),
+            camera: Viewport::new(0, 0, VIEW_WIDTH, VIEW_HEIGHT),
+            tool: Tool::Room,
+            points: vec![],
+            kinds: vec![],
+            kind: 0,
+            rounded: false,
+            polygon: false,
+            last_room: None,
+            npc: NpcInstance::Guard,
+            item: ItemInstance::KeyCard(1),
+            undo: vec![],
+            redo: vec![],
+            message: "".into(),
+        }
+    }
+
+    // the plan with the painted tiles
+    fn snapshot(&self) -> FloorPlan {
+        let mut plan = self.plan.clone();
+        plan.tiles = self.maps.export().tiles;
+        plan.normalize();
+        plan
+    }
+
+    fn restore(&mut self, plan: FloorPlan) {
+        self.maps = Maps::from_plan(&plan);
+        self.plan = plan;
+    }
+
+    // remembers the plan before a change
+    fn change(&mut self) {
+        let snapshot = self.snapshot();
+        self.undo.push(snapshot);
+        self.redo.clear();
+    }
+
+    fn undo(&mut self) {
+        if let Some(plan) = self.undo.pop() {
+            let current = self.snapshot();
+            self.redo.push(current);
+            self.restore(plan);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(plan) = self.redo.pop() {
+            let current = self.snapshot();
+            self.undo.push(current);
+            self.restore(plan);
+        }
+    }
+
+    fn save(&mut self) {
+        self.message = match self.snapshot().save(&self.path) {
+            Ok(_) => format!("saved {}, it is played from the next start", self.path),
+            Err(e) => format!("{}", e),
+        };
+    }
+
+    fn click(&mut self, p: (i32, i32), join: bool) {
+        self.points.push(p);
+        let is_polygon = self.tool == Tool::TriangleRoom && self.polygon;
+        if is_polygon && self.points.len() > 3 && self.points[0] == p {
+            self.points.pop();
+        } else if self.points.len() < if is_polygon { MAX_CORNERS } else { self.tool.points() } {
+            return;
+        }
+        let points = self.points.drain(..).collect::<Vec<(i32, i32)>>();
+        self.change();
+        match self.tool {
+            Tool::Room if self.rounded => {
+                let r = corners(points[0], points[1]);
+                let (w, h) = (r.right() - r.left() + 1, r.bottom() - r.top() + 1);
+                self.maps.paint_room(&RoundedRect::new(r.left(), r.top(), w, h, CORNER_RADIUS));
+            },
+            Tool::Room => {
+                let room = corners(points[0], points[1]);
+                match self.last_room.filter(|_| join) {
+                    // one room in the shape of both, without a wall between them
+                    Some(last) => self.maps.paint_room(&last.union(room)),
+                    None => self.maps.paint_room(&room),
+                }
+                self.last_room = Some(room);
+            },
+            Tool::EllipseRoom => {
+                let radius = (max(1, (points[1].0 - points[0].0).abs()), max(1, (points[1].1 - points[0].1).abs()));
+                if self.rounded {
+                    let outer = max(radius.0, radius.1);
+                    self.maps.paint_room(&Ring::new(points[0], outer, outer / 2));
+                } else {
+                    let ellipse = Ellipse::new(points[0], radius);
+                    match self.last_room.filter(|_| join) {
+                        // only what lies inside the last room, for rooms with a rounded side
+                        Some(last) => self.maps.paint_room(&last.intersection(ellipse)),
+                        None => self.maps.paint_room(&ellipse),
+                    }
+                }
+            },
+            Tool::TriangleRoom if self.polygon => self.maps.paint_room(&Polygon::new(&points)),
+            Tool::TriangleRoom => self.maps.paint_room(&Triangle::new(points[0], points[1], points[2])),
+            Tool::Wall => {
+                if points[0] != points[1] {
+                    self.maps.paint_wall(&Line::new(points[0].0, points[0].1, points[1].0, points[1].1));
+                }
+            },
+            Tool::Corridor => self.maps.paint_corridor(&corners(points[0], points[1])),
+            Tool::Carve => self.maps.carve(&corners(points[0], points[1])),
+            Tool::Erase => self.maps.erase(p),
+            Tool::Interactable => {
+                if let Some(kind) = self.kinds.get(self.kind) {
+                    self.plan.interactables.push(InteractableSpawn {
+                        kind: kind.clone(), position: p, security_level: 0, heading: 0.0,
+                    });
+                }
+            },
+            Tool::Npc => self.plan.npcs.push(NpcSpawn {
+                instance: self.npc,
+                position: p,
+                facing: (0.0, 1.0),
+                faction: FactionInstance::Kayaba,
+                inventory: vec![],
+                dialogue: None,
+                errands: vec![],
+            }),
+            Tool::Item => self.plan.items.push(ItemSpawn { instance: self.item, position: p }),
+        }
+    }
+
+    fn remove_at(&mut self, p: (i32, i32)) {
+        self.change();
+        self.plan.interactables.retain(|i| i.position != p);
+        self.plan.npcs.retain(|n| n.position != p);
+        self.plan.items.retain(|i| i.position != p);
+    }
+
+    fn configure(&mut self, p: (i32, i32), input: &InputHandler) {
+        let has_interactable = self.plan.interactables.iter().any(|i| i.position == p);
+        let has_npc = self.plan.npcs.iter().any(|n| n.position == p);
+        if !has_interactable && !has_npc {
+            return;
+        }
+        let item = self.item;
+        let configures = ['+', '-', '.', 'f', 'i', 'c'].iter().any(|c| input.is_char_pressed(*c));
+        if !configures {
+            return;
+        }
+        self.change();
+        for i in self.plan.interactables.iter_mut().filter(|i| i.position == p) {
+            if input.is_char_pressed('+') {
+                i.security_level += 1;
+            } else if input.is_char_pressed('-') {
+                i.security_level = max(0, i.security_level - 1);
+            } else if input.is_char_pressed('.') {
+                i.heading = (i.heading + HEADING_STEP) % 360.0;
+            }
+        }
+        for npc in self.plan.npcs.iter_mut().filter(|n| n.position == p) {
+            if input.is_char_pressed('.') {
+                // turns clockwise
+                npc.facing = (-npc.facing.1, npc.facing.0);
+            } else if input.is_char_pressed('f') {
+                npc.faction = next(&FactionInstance::values(), npc.faction);
+            } else if input.is_char_pressed('i') {
+                npc.inventory.push(item);
+            } else if input.is_char_pressed('c') {
+                npc.inventory.clear();
+            }
+        }
+    }
+
+    fn cycle(&mut self) {
+        match self.tool {
+            Tool::Interactable => if !self.kinds.is_empty() {
+                self.kind = (self.kind + 1) % self.kinds.len();
+            },
+            Tool::Npc => self.npc = next(&NpcInstance::values(), self.npc),
+            Tool::Room | Tool::EllipseRoom => self.rounded = !self.rounded,
+            Tool::TriangleRoom => {
+                self.polygon = !self.polygon;
+                self.points.clear();
+            },
+            _ => self.item = next(&item_catalog(), self.item),
+        }
+    }
+
+    fn selection(&self) -> String {
+        match self.tool {
+            Tool::Interactable => self.kinds.get(self.kind).cloned().unwrap_or("-".into()),
+            Tool::Npc => format!("{:?}", self.npc),
+            Tool::Room => if self.rounded { "rounded".into() } else { "".into() },
+            Tool::EllipseRoom => if self.rounded { "ring".into() } else { "".into() },
+            Tool::TriangleRoom => if self.polygon { "polygon".into() } else { "".into() },
+            _ => format!("{:?}", self.item),
+        }
+    }
+
+    fn cursor(&self, input: &InputHandler) -> Option<(i32, i32)> {
+        self.maps.within(self.camera.inv_transform(input.mouse_pos))
+    }
+
+    fn render_spawn(&self, tcod: &mut Tcod, p: (i32, i32), renderable: Renderable) {
+        if self.camera.visible(p) {
+            tcod.render_character(self.camera.transform(p), renderable.color, renderable.character);
+        }
+    }
+}
+
+impl State for Editor {
+    fn start(&mut self, _tcod: &mut Tcod, world: &mut World) {
+        self.kinds = world.read_resource::<InteractableRegistry>().ids();
+        let loaded = FloorPlan::load(&self.path, &world.read_resource::<InteractableRegistry>());
+        match loaded {
+            Ok(plan) => self.restore(plan),
+            Err(e) => {
+                // the floor as it was spawned is the next best thing to the file
+                let running = world.read_resource::<Tower>().export(&Level::Tower(0), world);
+                match running {
+                    Some(plan) => {
+                        self.restore(plan);
+                        self.message = format!("starting from the running floor, {}", e);
+                    },
+                    None => self.message = format!("starting empty, {}", e),
+                }
+            },
+        }
+    }
+
+    fn handle_events(&mut self, _tcod: &mut Tcod, world: &mut World) -> Transition {
+        world.write_resource::<InputHandler>().intercept(|input| {
+            let cursor = self.cursor(input);
+
+            if input.is_key_pressed(KeyCode::Escape) || input.is_key_pressed(KeyCode::F3) {
+                return Transition::Pop;
+            } else if input.is_key_pressed(KeyCode::Left) {
+                self.camera.pan(-1, 0);
+            } else if input.is_key_pressed(KeyCode::Right) {
+                self.camera.pan(1, 0);
+            } else if input.is_key_pressed(KeyCode::Up) {
+                self.camera.pan(0, -1);
+            } else if input.is_key_pressed(KeyCode::Down) {
+                self.camera.pan(0, 1);
+            } else if input.is_key_pressed(KeyCode::Tab) {
+                self.cycle();
+            } else if input.is_char_pressed('z') {
+                self.undo();
+            } else if input.is_char_pressed('y') {
+                self.redo();
+            } else if input.is_char_pressed('s') {
+                self.save();
+            } else if let Some(digit) = input.pressed_digit {
+                self.tool = Tool::values()[((digit + 9) % 10) as usize];
+                self.points.clear();
+            } else if let Some(p) = cursor {
+                if input.is_mouse_pressed() {
+                    let join = input.ctrl;
+                    self.click(p, join);
+                } else if input.mouse.rbutton_pressed {
+                    self.remove_at(p);
+                } else {
+                    self.configure(p, input);
+                }
+            }
+            Transition::None
+        })
+    }
+
+    fn render(&mut self, tcod: &mut Tcod, world: &mut World) {
+        let registry = world.read_resource::<InteractableRegistry>();
+        let input = world.read_resource::<InputHandler>();
+
+        tcod.clear(colors::BLACK);
+        self.maps.draw(tcod, &self.camera, true);
+
+        for i in self.plan.interactables.iter() {
+            if let Some(kind) = registry.get(&i.kind) {
+                self.render_spawn(tcod, i.position, Interactable::new(kind, i.security_level).get_renderable());
+            }
+        }
+        for npc in self.plan.npcs.iter() {
+            self.render_spawn(tcod, npc.position, Npc { instance: npc.instance }.get_renderable());
+        }
+        for item in self.plan.items.iter() {
+            self.render_spawn(tcod, item.position, Item { instance: item.instance }.get_renderable());
+        }
+        for player in self.plan.players.iter() {
+            self.render_spawn(tcod, player.position, Renderable::new('@', colors::WHITE));
+        }
+
+        for p in self.points.iter().cloned().chain(self.cursor(&input)) {
+            if self.camera.visible(p) {
+                tcod.highlight(self.camera.transform(p), colors::DARK_GREEN);
+            }
+        }
+
+        tcod.render_text((1, 1), colors::BLACK, colors::WHITE,
+                         &format!("EDITOR {} - {:?} {} - {}", self.path, self.tool, self.selection(), self.message));
+        tcod.render_text((1, 3), colors::BLACK, colors::LIGHT_GREY,
+                         &"1-0 tool, tab variant, click place, ctrl joins or clips rooms, right click remove, z undo, y redo, s save".to_string());
+        tcod.render_text((1, 4), colors::BLACK, colors::LIGHT_GREY,
+                         &"on a spawn: +/- security, . turn, f faction, i give item, c clear inventory".to_string());
+        tcod.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn painting_can_be_undone_and_redone() {
+        let mut editor = Editor::new("unused.map");
+        let empty = editor.snapshot();
+        editor.click((2, 2), false);
+        editor.click((6, 5), false);
+        let painted = editor.snapshot();
+        assert!(painted.tile((4, 3)).is_some());
+
+        editor.undo();
+        assert_eq!(editor.snapshot(), empty);
+        editor.redo();
+        assert_eq!(editor.snapshot(), painted);
+    }
+
+    #[test]
+    fn ellipses_are_clipped_to_the_last_room() {
+        let mut editor = Editor::new("unused.map");
+        editor.click((2, 2), false);
+        editor.click((10, 10), false);
+        editor.tool = Tool::EllipseRoom;
+        editor.click((10, 6), true);
+        editor.click((14, 9), true);
+        let painted = editor.snapshot();
+        assert!(painted.tile((10, 6)).is_some());
+        assert!(painted.tile((12, 6)).is_none());
+    }
+
+    #[test]
+    fn polygons_close_on_their_first_corner() {
+        let mut editor = Editor::new("unused.map");
+        editor.tool = Tool::TriangleRoom;
+        editor.cycle();
+        for p in [(2, 2), (12, 2), (12, 6), (7, 10), (2, 6)].iter() {
+            editor.click(*p, false);
+        }
+        assert!(editor.snapshot().tile((7, 7)).is_none());
+        editor.click((2, 2), false);
+        assert!(editor.points.is_empty());
+        let painted = editor.snapshot();
+        assert!(painted.tile((7, 7)).is_some());
+        assert!(painted.tile((7, 9)).map(|t| t.wall) == Some(true));
+        assert!(painted.tile((3, 9)).is_none());
+    }
+}
@@ -0,0 +1,77 @@
+use tcod::map::{ Map as FovMap, FovAlgorithm };
+
+use maps::{ Maps };
+
+const FOV_LIGHT_WALLS: bool = true;
+const FOV_ALGO: FovAlgorithm = FovAlgorithm::Basic;
+
+// the fov of every player on every floor, they need no window
+pub struct Fovs {
+    fov: Vec<FovMap>,
+    // the fovs that count as visible
+    shown: Vec<usize>,
+}
+
+impl Fovs {
+    pub fn new() -> Self {
+        Fovs {
+            fov: vec![],
+            shown: vec![],
+        }
+    }
+
+    pub fn create_fov(&mut self, width: i32, height: i32) -> usize {
+        self.fov.push(FovMap::new(width, height));
+        self.shown.push(self.fov.len() - 1);
+        self.fov.len() - 1
+    }
+
+    pub fn show_fovs(&mut self, fovs: &[usize]) {
+        self.shown = fovs.to_vec();
+    }
+
+    // the fov takes the size of the floor it is on
+    pub fn update_fov(&mut self, index: usize, maps: &Maps) {
+        if self.fov[index].size() != (maps.width(), maps.height()) {
+            self.fov[index] = FovMap::new(maps.width(), maps.height());
+        }
+        for y in 0..maps.height() {
+            for x in 0..maps.width() {
+                let see_through = !maps.is_sight_blocking((x, y));
+                let walk_through = !maps.is_blocking((x, y));
+                self.fov[index].set(x, y, see_through, walk_through);
+            }
+        }
+    }
+
+    pub fn compute_fov(&mut self, index: usize, p: (i32, i32), radius: i32) {
+        self.fov[index].compute_fov(p.0, p.1, radius, FOV_LIGHT_WALLS, FOV_ALGO);
+    }
+
+    pub fn is_in_fov(&self, p: (i32, i32)) -> bool {
+        self.shown.iter()
+            .any(|i| self.is_in_fov_map(*i, p))
+    }
+
+    pub fn shown_fovs(&self) -> Vec<usize> {
+        self.shown.clone()
+    }
+
+    // the cells a fov computed around center sees
+    pub fn visible_cells(&self, index: usize, center: (i32, i32), radius: i32) -> Vec<(i32, i32)> {
+        let mut cells = vec![];
+        for y in (center.1 - radius)..(center.1 + radius + 1) {
+            for x in (center.0 - radius)..(center.0 + radius + 1) {
+                if self.is_in_fov_map(index, (x, y)) {
+                    cells.push((x, y));
+                }
+            }
+        }
+        cells
+    }
+
+    pub fn is_in_fov_map(&self, index: usize, p: (i32, i32)) -> bool {
+        let (width, height) = self.fov[index].size();
+        p.0 >= 0 && p.0 < width && p.1 >= 0 && p.1 < height && self.fov[index].is_in_fov(p.0, p.1)
+    }
+}
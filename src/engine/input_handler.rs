@@ -1,9 +1,11 @@
 use std::collections::HashSet;
 use tcod::input::{ self, Event, Mouse, Key, KeyCode };
+use engine::tcod::{ VIEW_Y };
 
 #[derive(Default)]
 pub struct InputHandler {
     pub mouse: Mouse,
+    // relative to where the maps are drawn
     pub mouse_pos: (i32, i32),
     pub key: Key,
     pub pressed_keys: HashSet<char>,
@@ -43,7 +45,7 @@ impl InputHandler {
 
     fn register_mouse(&mut self, mouse: Mouse) {
         self.mouse = mouse;
-        self.mouse_pos = (self.mouse.cx as i32, self.mouse.cy as i32);
+        self.mouse_pos = (self.mouse.cx as i32, self.mouse.cy as i32 - VIEW_Y);
     }
 
     pub fn is_mouse_pressed(&self) -> bool {
@@ -62,6 +64,22 @@ impl InputHandler {
         self.key.code == key && self.key.pressed
     }
 
+    pub fn consume(&mut self) {
+        self.key = Default::default();
+        self.mouse = Default::default();
+        self.pressed_keys.clear();
+        self.pressed_digit = None;
+    }
+
+    // handles the next event for a state on top of the game, the game keeps running
+    // underneath and must not see the keys handled here
+    pub fn intercept<T, F>(&mut self, handle: F) -> T where F: FnOnce(&InputHandler) -> T {
+        self.update();
+        let result = handle(self);
+        self.consume();
+        result
+    }
+
     pub fn update(&mut self) {
         match input::check_for_event(input::MOUSE | input::KEY_PRESS | input::KEY_RELEASE) {
             Some((_, Event::Mouse(m))) => self.register_mouse(m),
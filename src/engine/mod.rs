@@ -1,5 +1,6 @@
 pub mod application;
 pub mod state;
 pub mod tcod;
+pub mod fov;
 pub mod input_handler;
 pub mod time;
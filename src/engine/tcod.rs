@@ -1,18 +1,18 @@
 use tcod::system::*;
 use tcod::console::*;
 use tcod::chars::{ self };
-use tcod::map::{Map as FovMap, FovAlgorithm};
 use tcod::colors::{ Color };
 
-use maps::{ Maps };
+use engine::fov::{ Fovs };
 use geometry::{ Rect };
 
 const SCREEN_WIDTH: i32 = 80;
 const SCREEN_HEIGHT: i32 = 50;
 
-const MAP_WIDTH: i32 = 80;
-const MAP_HEIGHT: i32 = 43;
-const MAP_Y: i32 = SCREEN_HEIGHT - MAP_HEIGHT;
+// the part of the screen the maps are drawn to, a floor may be larger and is scrolled
+pub const VIEW_WIDTH: i32 = 80;
+pub const VIEW_HEIGHT: i32 = 43;
+pub const VIEW_Y: i32 = SCREEN_HEIGHT - VIEW_HEIGHT;
 
 const PANEL_WIDTH: i32 = SCREEN_WIDTH;
 const PANEL_HEIGHT: i32 = 7;
@@ -20,14 +20,11 @@ const PANEL_Y: i32 = 0;
 
 const MAX_FPS: i32 = 60;
 
-const FOV_LIGHT_WALLS: bool = true;
-const FOV_ALGO: FovAlgorithm = FovAlgorithm::Basic;
-
 pub struct Tcod {
     root: Root,
     console: Offscreen,
     panel: Offscreen,
-    fov: Vec<FovMap>,
+    pub fovs: Fovs,
 }
 impl Tcod {
     pub fn new() -> Tcod {
@@ -42,31 +39,12 @@ impl Tcod {
 
         Tcod {
             root: root,
-            console: Offscreen::new(MAP_WIDTH, MAP_HEIGHT),
+            console: Offscreen::new(VIEW_WIDTH, VIEW_HEIGHT),
             panel: Offscreen::new(PANEL_WIDTH, PANEL_HEIGHT),
-            fov: vec![],
-        }
-    }
-
-    pub fn create_fov(&mut self) -> usize {
-        self.fov.push(FovMap::new(MAP_WIDTH, MAP_HEIGHT));
-        self.fov.len() - 1
-    }
-
-    pub fn update_fov(&mut self, index: usize, maps: &Maps) {
-        for y in 0..MAP_HEIGHT {
-            for x in 0..MAP_WIDTH {
-                let see_through = !maps.is_sight_blocking((x, y));
-                let walk_through = !maps.is_blocking((x, y));
-                self.fov[index].set(x, y, see_through, walk_through);
-            }
+            fovs: Fovs::new(),
         }
     }
 
-    pub fn compute_fov(&mut self, index: usize, p: (i32, i32), radius: i32) {
-        self.fov[index].compute_fov(p.0, p.1, radius, FOV_LIGHT_WALLS, FOV_ALGO);
-    }
-
     pub fn clear(&mut self, color: Color) {
         self.console.set_default_background(color);
         self.console.clear();
@@ -82,18 +60,13 @@ impl Tcod {
 
     pub fn flush(&mut self) {
         self.root.clear();
-        blit(&mut self.console, (0, 0), (MAP_WIDTH, MAP_HEIGHT),
-             &mut self.root,(0, MAP_Y), 1.0, 1.0);
+        blit(&mut self.console, (0, 0), (VIEW_WIDTH, VIEW_HEIGHT),
+             &mut self.root,(0, VIEW_Y), 1.0, 1.0);
         blit(&mut self.panel, (0, 0), (PANEL_WIDTH, PANEL_HEIGHT),
              &mut self.root,(0, PANEL_Y), 1.0, 1.0);
         self.root.flush();
     }
 
-    pub fn is_in_fov(&self, p: (i32, i32)) -> bool {
-        self.fov.iter()
-            .any(|f| f.is_in_fov(p.0, p.1))
-    }
-
     pub fn render(&mut self, p: (i32, i32), bgcolor: Color, fgcolor: Color, character: char) {
         self.console.set_default_foreground(fgcolor);
         self.console.set_char_background(p.0, p.1, bgcolor, BackgroundFlag::Set);
@@ -115,6 +88,34 @@ impl Tcod {
         self.panel.print_ex(p.0, p.1, BackgroundFlag::Set, TextAlignment::Left, text);
     }
 
+    pub fn render_overlay_text(&mut self, p: (i32, i32), fgcolor: Color, text: &str) {
+        self.console.set_default_foreground(fgcolor);
+        self.console.print_ex(p.0, p.1, BackgroundFlag::None, TextAlignment::Left, text);
+    }
+
+    // clears a part of the map console and frames it
+    pub fn render_frame(&mut self, rect: &Rect, bgcolor: Color, fgcolor: Color) {
+        self.console.set_default_foreground(fgcolor);
+        for y in rect.top() .. rect.bottom() + 1 {
+            for x in rect.left() .. rect.right() + 1 {
+                self.console.set_char_background(x, y, bgcolor, BackgroundFlag::Set);
+                self.console.put_char(x, y, ' ', BackgroundFlag::None);
+            }
+        }
+        self.console.put_char(rect.left(), rect.top(), chars::NW, BackgroundFlag::None);
+        self.console.put_char(rect.right(), rect.top(), chars::NE, BackgroundFlag::None);
+        self.console.put_char(rect.left(), rect.bottom(), chars::SW, BackgroundFlag::None);
+        self.console.put_char(rect.right(), rect.bottom(), chars::SE, BackgroundFlag::None);
+        for i in rect.left() + 1 .. rect.right() {
+            self.console.put_char(i, rect.top(), chars::HLINE, BackgroundFlag::None);
+            self.console.put_char(i, rect.bottom(), chars::HLINE, BackgroundFlag::None);
+        }
+        for i in rect.top() + 1 .. rect.bottom() {
+            self.console.put_char(rect.left(), i, chars::VLINE, BackgroundFlag::None);
+            self.console.put_char(rect.right(), i, chars::VLINE, BackgroundFlag::None);
+        }
+    }
+
     pub fn render_box(&mut self, rect: &Rect, bgcolor: Color, fgcolor: Color) {
         self.panel.set_default_foreground(fgcolor);
         self.panel.set_default_background(bgcolor);
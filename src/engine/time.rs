@@ -12,10 +12,6 @@ pub enum Stopwatch {
     Ended(Duration),
 }
 
-pub struct Timer {
-    deadline: Instant,
-}
-
 impl Default for Stopwatch {
     fn default() -> Self {
         Stopwatch::Waiting
@@ -51,17 +47,3 @@ impl Stopwatch {
         }
     }
 }
-
-impl Timer {
-    pub fn new(duration: Duration) -> Self {
-        Timer { deadline: Instant::now() + duration }
-    }
-
-    pub fn elapsed(&self) -> bool {
-        Instant::now() > self.deadline
-    }
-
-    pub fn time_left(&self) -> Duration {
-        self.deadline - Instant::now()
-    }
-}
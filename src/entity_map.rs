@@ -1,73 +1,267 @@
-use std::slice::Iter;
+use std::collections::{ HashMap };
 use specs::{ Entity };
 
-const MAP_WIDTH: i32 = 80;
-const MAP_HEIGHT: i32 = 43;
+use geometry::{ Shape };
 
 #[derive(Copy, Clone, Debug, PartialEq)]
-pub struct Entry(pub Entity, pub bool, pub bool);
+pub struct Entry {
+    pub entity: Entity,
+    pub blocking: bool,
+    pub sight_blocking: bool,
+}
+
+impl Entry {
+    pub fn new(entity: Entity) -> Self {
+        Entry { entity: entity, blocking: true, sight_blocking: false }
+    }
+}
 
+// a spatial index of the entities on a floor, every entity is in one cell
 pub struct EntityMap {
-    map: Vec<Vec<Vec<Entry>>>,
+    // one bucket per cell, row by row
+    cells: Vec<Vec<Entry>>,
+    // the cell of each entity, so nobody has to remember where they put it
+    locations: HashMap<Entity, usize>,
     width: i32,
     height: i32,
 }
 
 impl EntityMap {
-    pub fn new() -> Self {
-        let map = vec![vec![vec![]; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    pub fn new(width: i32, height: i32) -> Self {
         EntityMap {
-            map: map,
-            width: MAP_WIDTH,
-            height: MAP_HEIGHT,
+            cells: vec![vec![]; (width.max(0) * height.max(0)) as usize],
+            locations: HashMap::new(),
+            width: width,
+            height: height,
         }
     }
 
+    // keeps the buckets allocated
     pub fn clear(&mut self) {
-        self.map = vec![vec![vec![]; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+        self.locations.clear();
     }
 
-    pub fn remove(&mut self, entity: &Entity, p: (i32, i32)) -> Option<Entry> {
-        self.map[p.0 as usize][p.1 as usize].iter()
-            .position(|e| e.0 == *entity)
-            .map(|index| self.map[p.0 as usize][p.1 as usize].swap_remove(index))
+    fn index(&self, p: (i32, i32)) -> Option<usize> {
+        if p.0 < 0 || p.0 >= self.width || p.1 < 0 || p.1 >= self.height {
+            None
+        } else {
+            Some((p.1 * self.width + p.0) as usize)
+        }
     }
 
-    pub fn set_blocking(&mut self, entity: &Entity, p: (i32, i32), blocking: bool) {
-        let entry = self.map[p.0 as usize][p.1 as usize].iter_mut()
-            .filter(|e| e.0 == *entity)
-            .next();
-        if let Some(e) = entry {
-            e.1 = blocking;
+    pub fn position(&self, entity: &Entity) -> Option<(i32, i32)> {
+        self.locations.get(entity).map(|index| (*index as i32 % self.width, *index as i32 / self.width))
+    }
+
+    pub fn remove(&mut self, entity: &Entity) -> Option<Entry> {
+        let index = match self.locations.remove(entity) {
+            Some(index) => index,
+            None => return None,
+        };
+        let cell = &mut self.cells[index];
+        cell.iter()
+            .position(|e| e.entity == *entity)
+            .map(|i| cell.swap_remove(i))
+    }
+
+    // positions off the map take nothing, the entity stays where it was
+    pub fn move_to(&mut self, entity: &Entity, to: (i32, i32)) {
+        if self.index(to).is_some() {
+            if let Some(entry) = self.remove(entity) {
+                self.push_entry(entry, to);
+            }
         }
     }
 
-    pub fn set_sight_blocking(&mut self, entity: &Entity, p: (i32, i32), blocking: bool) {
-        let entry = self.map[p.0 as usize][p.1 as usize].iter_mut()
-            .filter(|e| e.0 == *entity)
-            .next();
-        if let Some(e) = entry {
-            e.2 = blocking;
+    fn entry_mut(&mut self, entity: &Entity) -> Option<&mut Entry> {
+        match self.locations.get(entity) {
+            Some(index) => self.cells[*index].iter_mut().find(|e| e.entity == *entity),
+            None => None,
+        }
+    }
+
+    pub fn set_blocking(&mut self, entity: &Entity, blocking: bool) {
+        if let Some(e) = self.entry_mut(entity) {
+            e.blocking = blocking;
+        }
+    }
+
+    pub fn set_sight_blocking(&mut self, entity: &Entity, blocking: bool) {
+        if let Some(e) = self.entry_mut(entity) {
+            e.sight_blocking = blocking;
         }
     }
 
     pub fn is_empty(&self, p: (i32, i32)) -> bool {
-        self.map[p.0 as usize][p.1 as usize].is_empty()
+        self.get(p).is_empty()
     }
 
     pub fn push(&mut self, entity: &Entity, p: (i32, i32)) {
-        self.map[p.0 as usize][p.1 as usize].push(Entry(*entity, true, false));
+        self.push_entry(Entry::new(*entity), p);
     }
 
-    pub fn push_entry(&mut self, entity: Entry, p: (i32, i32)) {
-        self.map[p.0 as usize][p.1 as usize].push(entity);
+    // an entity already on the map is taken from its old cell
+    pub fn push_entry(&mut self, entry: Entry, p: (i32, i32)) {
+        if let Some(index) = self.index(p) {
+            self.remove(&entry.entity);
+            self.cells[index].push(entry);
+            self.locations.insert(entry.entity, index);
+        }
     }
 
     pub fn pop(&mut self, p: (i32, i32)) -> Option<Entry> {
-        self.map[p.0 as usize][p.1 as usize].pop()
+        let entry = self.index(p).and_then(|index| self.cells[index].pop());
+        if let Some(e) = entry {
+            self.locations.remove(&e.entity);
+        }
+        entry
     }
 
-    pub fn get(&self, p: (i32, i32)) -> &Vec<Entry> {
-        &self.map[p.0 as usize][p.1 as usize]
+    pub fn get(&self, p: (i32, i32)) -> &[Entry] {
+        self.index(p).map_or(&[], |index| &self.cells[index][..])
+    }
+
+    // everything in the cells at most radius away from the center cell
+    pub fn within_radius(&self, center: (i32, i32), radius: f32) -> Vec<Entity> {
+        let r = radius as i32;
+        let mut result = vec![];
+        for y in (center.1 - r)..(center.1 + r + 1) {
+            for x in (center.0 - r)..(center.0 + r + 1) {
+                let (dx, dy) = ((x - center.0) as f32, (y - center.1) as f32);
+                if dx * dx + dy * dy <= radius * radius {
+                    result.extend(self.get((x, y)).iter().map(|e| e.entity));
+                }
+            }
+        }
+        result
+    }
+
+    pub fn in_shape<T: Shape>(&self, shape: T) -> Vec<Entity> {
+        shape.into_iter()
+            .flat_map(|p| self.get(p).iter().map(|e| e.entity))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{ Instant };
+    use specs::{ World, Entity };
+    use entity_map::{ EntityMap, Entry };
+    use geometry::{ Rect };
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let mut world = World::new();
+        (0..count).map(|_| world.create_entity().build()).collect()
+    }
+
+    #[test]
+    fn entities_are_found_without_their_position() {
+        let e = entities(2);
+        let mut map = EntityMap::new(10, 10);
+        map.push(&e[0], (2, 3));
+        map.push(&e[1], (2, 3));
+        map.set_sight_blocking(&e[0], true);
+
+        map.move_to(&e[0], (5, 5));
+        assert_eq!(map.position(&e[0]), Some((5, 5)));
+        assert_eq!(map.get((5, 5)), &[Entry { entity: e[0], blocking: true, sight_blocking: true }]);
+        assert_eq!(map.get((2, 3)).len(), 1);
+
+        // moving off the map keeps it in place
+        map.move_to(&e[0], (10, 5));
+        assert_eq!(map.position(&e[0]), Some((5, 5)));
+
+        assert_eq!(map.remove(&e[1]).map(|e| e.entity), Some(e[1]));
+        assert!(map.is_empty((2, 3)));
+        assert_eq!(map.position(&e[1]), None);
+    }
+
+    #[test]
+    fn radius_and_shape_queries() {
+        let e = entities(3);
+        let mut map = EntityMap::new(20, 20);
+        map.push(&e[0], (5, 5));
+        map.push(&e[1], (8, 5));
+        map.push(&e[2], (8, 7));
+
+        let mut near = map.within_radius((5, 5), 3.0);
+        near.sort();
+        assert_eq!(near, vec![e[0], e[1]]);
+        assert_eq!(map.in_shape(Rect::new(6, 6, 3, 3)), vec![e[2]]);
+    }
+
+    // the nested vectors the index replaced, they are told where the entity was
+    struct NestedMap {
+        map: Vec<Vec<Vec<Entry>>>,
+        width: i32,
+        height: i32,
+    }
+
+    impl NestedMap {
+        fn clear(&mut self) {
+            self.map = vec![vec![vec![]; self.height as usize]; self.width as usize];
+        }
+
+        fn push(&mut self, entity: &Entity, p: (i32, i32)) {
+            self.map[p.0 as usize][p.1 as usize].push(Entry::new(*entity));
+        }
+
+        fn move_entity(&mut self, entity: &Entity, from: (i32, i32), to: (i32, i32)) {
+            let entry = self.map[from.0 as usize][from.1 as usize].iter()
+                .position(|e| e.entity == *entity)
+                .map(|index| self.map[from.0 as usize][from.1 as usize].swap_remove(index));
+            if let Some(entry) = entry {
+                self.map[to.0 as usize][to.1 as usize].push(entry);
+            }
+        }
+    }
+
+    // the index must agree with the nested vectors it replaced and prints how long both take,
+    // it is slow without optimizations:
+    // cargo test --release -- --ignored --nocapture compare_with_nested_vectors
+    #[test]
+    #[ignore]
+    fn compare_with_nested_vectors() {
+        let e = entities(200);
+        let frames = 1000;
+        let step = |i: usize, frame: usize| (((i * 7 + frame) % 80) as i32, ((i * 3 + frame / 80) % 43) as i32);
+
+        let start = Instant::now();
+        let mut nested = NestedMap { map: vec![], width: 80, height: 43 };
+        for frame in 0..frames {
+            nested.clear();
+            for (i, entity) in e.iter().enumerate() {
+                nested.push(entity, step(i, frame));
+                nested.move_entity(entity, step(i, frame), step(i, frame + 1));
+            }
+        }
+        let nested_time = start.elapsed();
+
+        let start = Instant::now();
+        let mut index = EntityMap::new(80, 43);
+        for frame in 0..frames {
+            index.clear();
+            for (i, entity) in e.iter().enumerate() {
+                index.push(entity, step(i, frame));
+                index.move_to(entity, step(i, frame + 1));
+            }
+        }
+        let index_time = start.elapsed();
+        println!("push, move and clear of {} entities over {} frames: nested {:?}, index {:?}",
+                 e.len(), frames, nested_time, index_time);
+
+        for x in 0..80 {
+            for y in 0..43 {
+                let mut expected: Vec<Entity> = nested.map[x as usize][y as usize].iter().map(|e| e.entity).collect();
+                let mut found: Vec<Entity> = index.get((x, y)).iter().map(|e| e.entity).collect();
+                expected.sort();
+                found.sort();
+                assert!(found == expected);
+            }
+        }
     }
 }
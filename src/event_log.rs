@@ -1,10 +1,13 @@
 use std::collections::VecDeque;
 use specs::{ Entity };
+use components::space::{ Level };
 
 pub enum LogEvent {
     FinishedTurn(Entity),
     Died(Entity),
     DidDamage(Entity, Entity, f32),
+    Spotted(Entity),
+    Lockdown(Level),
 }
 
 
@@ -0,0 +1,125 @@
+use std::collections::{ HashMap, HashSet };
+use specs::{ Entity };
+use components::faction::{ FactionInstance };
+use components::item::{ ItemInstance };
+use components::progress::{ AreaInstance, Event };
+
+// how suspicious a disguised player acting out of character is, compared to an intruder
+const OUT_OF_CHARACTER_SUSPICION: f32 = 0.5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Attitude {
+    Neutral,
+    Hostile,
+}
+
+// how every faction thinks of every player, for the current loop
+pub struct FactionRelations {
+    attitudes: HashMap<(FactionInstance, Entity), Attitude>,
+}
+
+impl Default for FactionRelations {
+    fn default() -> Self {
+        FactionRelations { attitudes: HashMap::new() }
+    }
+}
+
+impl FactionRelations {
+    pub fn attitude(&self, faction: FactionInstance, player: Entity) -> Attitude {
+        *self.attitudes.get(&(faction, player)).unwrap_or(&Attitude::Neutral)
+    }
+
+    // attacking one member turns the whole faction against the attacker
+    pub fn provoke(&mut self, faction: FactionInstance, player: Entity) {
+        self.attitudes.insert((faction, player), Attitude::Hostile);
+    }
+
+    pub fn reset(&mut self) {
+        self.attitudes.clear();
+    }
+}
+
+pub fn is_trespassing(faction: FactionInstance, area: Option<AreaInstance>, events: &HashSet<Event>) -> bool {
+    area.map_or(false, |a| faction.is_restricted(a) && !events.contains(&Event::HasAccess(a)))
+}
+
+// whether an npc of a faction cares about a player standing in an area. Guards
+// only respond inside the areas of their own corporation
+pub fn reacts_to(faction: FactionInstance, is_guard: bool, attitude: Attitude,
+                 area: Option<AreaInstance>, events: &HashSet<Event>) -> bool {
+    if is_guard && !area.map_or(false, |a| faction.owns(a)) {
+        return false;
+    }
+    attitude == Attitude::Hostile || is_trespassing(faction, area, events)
+}
+
+pub fn is_disguised(faction: FactionInstance, clothing: Option<ItemInstance>, area: Option<AreaInstance>) -> bool {
+    match clothing {
+        Some(ItemInstance::Uniform(uniform)) => uniform == faction,
+        Some(ItemInstance::LabCoat) => match area {
+            Some(AreaInstance::KayabaRoboticsLabs) | Some(AreaInstance::NichireiLabs) => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+// how much an npc minds a player, a disguise only fools those who are not
+// already hostile and only as long as the player behaves
+pub fn suspicion(faction: FactionInstance, is_guard: bool, attitude: Attitude, area: Option<AreaInstance>,
+                 events: &HashSet<Event>, clothing: Option<ItemInstance>, out_of_character: bool) -> f32 {
+    if !reacts_to(faction, is_guard, attitude, area, events) {
+        0.0
+    } else if attitude != Attitude::Hostile && is_disguised(faction, clothing, area) {
+        if out_of_character { OUT_OF_CHARACTER_SUSPICION } else { 0.0 }
+    } else {
+        1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{ HashSet };
+    use factions::{ Attitude, reacts_to, suspicion };
+    use components::faction::{ FactionInstance };
+    use components::item::{ ItemInstance };
+    use components::progress::{ AreaInstance, Event };
+
+    #[test]
+    fn trespassers_need_access() {
+        let mut events = HashSet::new();
+        let lab = Some(AreaInstance::KayabaRoboticsLabs);
+        assert!(reacts_to(FactionInstance::Kayaba, false, Attitude::Neutral, lab, &events));
+        events.insert(Event::HasAccess(AreaInstance::KayabaRoboticsLabs));
+        assert!(!reacts_to(FactionInstance::Kayaba, false, Attitude::Neutral, lab, &events));
+        assert!(reacts_to(FactionInstance::Kayaba, false, Attitude::Hostile, lab, &events));
+    }
+
+    #[test]
+    fn guards_stay_in_their_areas() {
+        let events = HashSet::new();
+        let restaurant = Some(AreaInstance::NichireiRestaurant);
+        assert!(!reacts_to(FactionInstance::Kayaba, true, Attitude::Hostile, restaurant, &events));
+        assert!(reacts_to(FactionInstance::Kayaba, false, Attitude::Hostile, restaurant, &events));
+        assert!(reacts_to(FactionInstance::Nichirei, true, Attitude::Hostile, restaurant, &events));
+        assert!(!reacts_to(FactionInstance::Nichirei, true, Attitude::Hostile, None, &events));
+    }
+
+    #[test]
+    fn disguises_fool_the_right_faction() {
+        let events = HashSet::new();
+        let lab = Some(AreaInstance::KayabaRoboticsLabs);
+        let uniform = Some(ItemInstance::Uniform(FactionInstance::Kayaba));
+        let coat = Some(ItemInstance::LabCoat);
+        assert!(suspicion(FactionInstance::Kayaba, true, Attitude::Neutral, lab, &events, None, false) == 1.0);
+        assert!(suspicion(FactionInstance::Kayaba, true, Attitude::Neutral, lab, &events, uniform, false) == 0.0);
+        assert!(suspicion(FactionInstance::Kayaba, true, Attitude::Neutral, lab, &events, coat, false) == 0.0);
+        assert!(suspicion(FactionInstance::Kayaba, true, Attitude::Neutral, lab, &events, uniform, true) > 0.0);
+        assert!(suspicion(FactionInstance::Kayaba, true, Attitude::Hostile, lab, &events, uniform, false) == 1.0);
+
+        let mainframe = Some(AreaInstance::MainFrame);
+        let other = Some(ItemInstance::Uniform(FactionInstance::Kayaba));
+        assert!(suspicion(FactionInstance::PrimeSec, true, Attitude::Neutral, mainframe, &events, other, false) == 1.0);
+        assert!(suspicion(FactionInstance::PrimeSec, true, Attitude::Neutral, mainframe, &events, coat, false) == 1.0);
+    }
+}
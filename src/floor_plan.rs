@@ -0,0 +1,616 @@
+use std::fmt;
+use std::fs::{ File };
+use std::io::{ Read, Write };
+
+use geometry::{ Rect };
+use tile_map::{ TileSpec, DoorState };
+use components::progress::{ AreaInstance };
+use components::npc::{ NpcInstance };
+use components::faction::{ FactionInstance };
+use components::item::{ ItemInstance };
+use components::schedule::{ parse_clock };
+use interactables::{ InteractableRegistry };
+
+#[derive(Debug, PartialEq)]
+pub struct FloorPlanError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FloorPlanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+fn error<T>(line: usize, column: usize, message: String) -> Result<T, FloorPlanError> {
+    Err(FloorPlanError { line: line, column: column, message: message })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerSpawn {
+    pub name: String,
+    pub position: (i32, i32),
+    pub active: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InteractableSpawn {
+    pub kind: String,
+    pub position: (i32, i32),
+    pub security_level: i32,
+    // in degrees
+    pub heading: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NpcSpawn {
+    pub instance: NpcInstance,
+    pub position: (i32, i32),
+    pub facing: (f32, f32),
+    pub faction: FactionInstance,
+    pub inventory: Vec<ItemInstance>,
+    pub dialogue: Option<String>,
+    // loop clock and target of each errand
+    pub errands: Vec<(String, (i32, i32))>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ItemSpawn {
+    pub instance: ItemInstance,
+    pub position: (i32, i32),
+}
+
+// a hand authored floor: an ascii grid of tiles, the legend to read it and
+// everything that is spawned on it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FloorPlan {
+    // width and height of the floor, the tiles may leave bedrock at its far sides
+    pub size: (i32, i32),
+    // rows of tiles, none is bedrock
+    pub tiles: Vec<Vec<Option<TileSpec>>>,
+    pub areas: Vec<(Rect, AreaInstance)>,
+    pub players: Vec<PlayerSpawn>,
+    pub interactables: Vec<InteractableSpawn>,
+    pub npcs: Vec<NpcSpawn>,
+    pub items: Vec<ItemSpawn>,
+}
+
+// whitespace separated words with their 1-based column
+fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut result = vec![];
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            if let Some(s) = start.take() {
+                result.push((s + 1, &text[s..i]));
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        result.push((s + 1, &text[s..]));
+    }
+    result
+}
+
+fn expect_words(words: &[(usize, &str)], count: usize, what: &str, line: usize) -> Result<(), FloorPlanError> {
+    if words.len() < count + 1 {
+        let column = words.last().map_or(1, |&(c, w)| c + w.len());
+        return error(line, column, format!("expected {}", what));
+    }
+    Ok(())
+}
+
+fn parse_number<T: ::std::str::FromStr>(word: (usize, &str), line: usize) -> Result<T, FloorPlanError> {
+    match word.1.parse() {
+        Ok(n) => Ok(n),
+        Err(_) => error(line, word.0, format!("'{}' is not a number", word.1)),
+    }
+}
+
+fn parse_position(words: &[(usize, &str)], line: usize) -> Result<(i32, i32), FloorPlanError> {
+    Ok((parse_number(words[0], line)?, parse_number(words[1], line)?))
+}
+
+fn parse_item(word: (usize, &str), line: usize) -> Result<ItemInstance, FloorPlanError> {
+    match ItemInstance::from_name(word.1) {
+        Some(item) => Ok(item),
+        None => error(line, word.0, format!("unknown item '{}'", word.1)),
+    }
+}
+
+// doors name their state after the room
+fn parse_legend_entry(words: &[(usize, &str)], line: usize) -> Result<(char, TileSpec), FloorPlanError> {
+    expect_words(words, 2, "a character, wall, floor or door and a room", line)?;
+    let mut chars = words[0].1.chars();
+    let character = match (chars.next(), chars.next()) {
+        (Some(c), None) => c,
+        _ => return error(line, words[0].0, format!("'{}' is not a single character", words[0].1)),
+    };
+    let (wall, is_door) = match words[1].1 {
+        "wall" => (true, false),
+        "floor" => (false, false),
+        "door" => (false, true),
+        other => return error(line, words[1].0, format!("expected wall, floor or door, not '{}'", other)),
+    };
+    let room = parse_number(words[2], line)?;
+    let mut rest = words[3..].iter();
+    let door = if is_door {
+        expect_words(words, 3, "a door state", line)?;
+        let word = rest.next().unwrap();
+        match DoorState::from_name(word.1) {
+            Some(state) => Some(state),
+            None => return error(line, word.0, format!("unknown door state '{}'", word.1)),
+        }
+    } else {
+        None
+    };
+    let light = match rest.next() {
+        Some(word) => parse_number(*word, line)?,
+        None => 1.0,
+    };
+    if let Some(word) = rest.next() {
+        return error(line, word.0, format!("unexpected '{}'", word.1));
+    }
+    Ok((character, TileSpec { wall: wall, room: room, light: light, door: door }))
+}
+
+fn parse_interactable(words: &[(usize, &str)], line: usize,
+                      registry: Option<&InteractableRegistry>) -> Result<InteractableSpawn, FloorPlanError> {
+    expect_words(words, 3, "a kind and a position", line)?;
+    if let Some(registry) = registry {
+        if registry.get(words[1].1).is_none() {
+            return error(line, words[1].0, format!("unknown interactable kind '{}'", words[1].1));
+        }
+    }
+    let mut spawn = InteractableSpawn {
+        kind: words[1].1.to_string(),
+        position: parse_position(&words[2..], line)?,
+        security_level: 0,
+        heading: 0.0,
+    };
+    let mut options = words[4..].iter();
+    while let Some(option) = options.next() {
+        let value = match options.next() {
+            Some(value) => *value,
+            None => return error(line, option.0, format!("'{}' needs a value", option.1)),
+        };
+        match option.1 {
+            "security" => spawn.security_level = parse_number(value, line)?,
+            "heading" => spawn.heading = parse_number(value, line)?,
+            other => return error(line, option.0, format!("unknown option '{}'", other)),
+        }
+    }
+    Ok(spawn)
+}
+
+enum Section {
+    Top,
+    Tiles,
+    Legend,
+    // the npc and the line it started on, the faction is required
+    Npc(NpcSpawn, Option<FactionInstance>, usize),
+}
+
+impl FloorPlan {
+    pub fn load(path: &str, registry: &InteractableRegistry) -> Result<Self, FloorPlanError> {
+        let mut content = String::new();
+        match File::open(path).and_then(|mut f| f.read_to_string(&mut content)) {
+            Ok(_) => FloorPlan::parse_known(&content, registry),
+            Err(e) => error(0, 0, format!("could not read {}: {}", path, e)),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), FloorPlanError> {
+        match File::create(path).and_then(|mut f| f.write_all(self.to_text().as_bytes())) {
+            Ok(_) => Ok(()),
+            Err(e) => error(0, 0, format!("could not write {}: {}", path, e)),
+        }
+    }
+
+    pub fn tile(&self, p: (i32, i32)) -> Option<TileSpec> {
+        if p.0 < 0 || p.1 < 0 {
+            return None;
+        }
+        self.tiles.get(p.1 as usize)
+            .and_then(|row| row.get(p.0 as usize))
+            .and_then(|tile| *tile)
+    }
+
+    // the plans of the tests get by without interactable kinds
+    #[cfg(test)]
+    pub fn parse(content: &str) -> Result<Self, FloorPlanError> {
+        FloorPlan::read(content, None)
+    }
+
+    // like parse, but every interactable has to be of a kind the registry knows
+    pub fn parse_known(content: &str, registry: &InteractableRegistry) -> Result<Self, FloorPlanError> {
+        FloorPlan::read(content, Some(registry))
+    }
+
+    fn read(content: &str, registry: Option<&InteractableRegistry>) -> Result<Self, FloorPlanError> {
+        let mut plan = FloorPlan::default();
+        let mut legend: Vec<(char, TileSpec)> = vec![];
+        let mut rows: Vec<(usize, &str)> = vec![];
+        let mut has_tiles = false;
+        let mut section = Section::Top;
+
+        for (index, raw) in content.lines().enumerate() {
+            let line = index + 1;
+            if let Section::Tiles = section {
+                // tile rows are taken verbatim, a '#' may be a tile
+                if raw.trim() == "end" {
+                    section = Section::Top;
+                } else {
+                    rows.push((line, raw));
+                }
+                continue;
+            }
+
+            let text = raw.split('#').next().unwrap();
+            let words = words(text);
+            if words.is_empty() {
+                continue;
+            }
+            let (column, keyword) = words[0];
+
+            section = match section {
+                Section::Tiles => unreachable!(),
+                Section::Legend => {
+                    if keyword == "end" {
+                        Section::Top
+                    } else {
+                        let (character, spec) = parse_legend_entry(&words, line)?;
+                        if character == ' ' || legend.iter().any(|&(c, _)| c == character) {
+                            return error(line, column, format!("'{}' is already taken", character));
+                        }
+                        legend.push((character, spec));
+                        Section::Legend
+                    }
+                },
+                Section::Npc(mut npc, faction, start) => {
+                    let mut faction = faction;
+                    match keyword {
+                        "facing" => {
+                            expect_words(&words, 2, "a direction", line)?;
+                            npc.facing = (parse_number(words[1], line)?, parse_number(words[2], line)?);
+                        },
+                        "faction" => {
+                            expect_words(&words, 1, "a faction", line)?;
+                            faction = match FactionInstance::from_name(words[1].1) {
+                                Some(f) => Some(f),
+                                None => return error(line, words[1].0, format!("unknown faction '{}'", words[1].1)),
+                            };
+                        },
+                        "inventory" => {
+                            for word in &words[1..] {
+                                npc.inventory.push(parse_item(*word, line)?);
+                            }
+                        },
+                        "dialogue" => {
+                            expect_words(&words, 1, "a dialogue id", line)?;
+                            npc.dialogue = Some(words[1].1.to_string());
+                        },
+                        "errand" => {
+                            expect_words(&words, 3, "a loop clock and a position", line)?;
+                            if parse_clock(words[1].1).is_none() {
+                                return error(line, words[1].0, format!("invalid loop clock '{}'", words[1].1));
+                            }
+                            npc.errands.push((words[1].1.to_string(), parse_position(&words[2..], line)?));
+                        },
+                        "end" => {
+                            match faction {
+                                Some(f) => npc.faction = f,
+                                None => return error(start, 1, "npc has no faction".into()),
+                            }
+                            plan.npcs.push(npc);
+                            section = Section::Top;
+                            continue;
+                        },
+                        other => return error(line, column, format!("unknown npc keyword '{}'", other)),
+                    }
+                    Section::Npc(npc, faction, start)
+                },
+                Section::Top => match keyword {
+                    "tiles" => {
+                        if has_tiles {
+                            return error(line, column, "tiles are defined twice".into());
+                        }
+                        has_tiles = true;
+                        Section::Tiles
+                    },
+                    "legend" => Section::Legend,
+                    "size" => {
+                        expect_words(&words, 2, "a width and a height", line)?;
+                        plan.size = parse_position(&words[1..], line)?;
+                        if plan.size.0 <= 0 || plan.size.1 <= 0 {
+                            return error(line, words[1].0, "the size must be positive".into());
+                        }
+                        Section::Top
+                    },
+                    "area" => {
+                        expect_words(&words, 5, "a rectangle and an area", line)?;
+                        let (x, y) = parse_position(&words[1..], line)?;
+                        let (w, h) = parse_position(&words[3..], line)?;
+                        let area = match AreaInstance::from_name(words[5].1) {
+                            Some(area) => area,
+                            None => return error(line, words[5].0, format!("unknown area '{}'", words[5].1)),
+                        };
+                        plan.areas.push((Rect::new(x, y, w, h), area));
+                        Section::Top
+                    },
+                    "player" => {
+                        expect_words(&words, 3, "a name and a position", line)?;
+                        let active = match words.get(4) {
+                            None => false,
+                            Some(&(_, "active")) => true,
+                            Some(&(c, other)) => return error(line, c, format!("unexpected '{}'", other)),
+                        };
+                        plan.players.push(PlayerSpawn {
+                            name: words[1].1.to_string(),
+                            position: parse_position(&words[2..], line)?,
+                            active: active,
+                        });
+                        Section::Top
+                    },
+                    "interactable" => {
+                        plan.interactables.push(parse_interactable(&words, line, registry)?);
+                        Section::Top
+                    },
+                    "npc" => {
+                        expect_words(&words, 3, "an npc and a position", line)?;
+                        let instance = match NpcInstance::from_name(words[1].1) {
+                            Some(n) => n,
+                            None => return error(line, words[1].0, format!("unknown npc '{}'", words[1].1)),
+                        };
+                        Section::Npc(NpcSpawn {
+                            instance: instance,
+                            position: parse_position(&words[2..], line)?,
+                            facing: (0.0, 1.0),
+                            faction: FactionInstance::Kayaba,
+                            inventory: vec![],
+                            dialogue: None,
+                            errands: vec![],
+                        }, None, line)
+                    },
+                    "item" => {
+                        expect_words(&words, 3, "an item and a position", line)?;
+                        plan.items.push(ItemSpawn {
+                            instance: parse_item(words[1], line)?,
+                            position: parse_position(&words[2..], line)?,
+                        });
+                        Section::Top
+                    },
+                    other => return error(line, column, format!("unknown keyword '{}'", other)),
+                },
+            };
+        }
+
+        let last = content.lines().count();
+        match section {
+            Section::Top => (),
+            Section::Tiles => return error(last, 1, "tiles are missing 'end'".into()),
+            Section::Legend => return error(last, 1, "legend is missing 'end'".into()),
+            Section::Npc(_, _, start) => return error(start, 1, "npc is missing 'end'".into()),
+        }
+
+        for (line, row) in rows {
+            let mut tiles = vec![];
+            for (index, character) in row.chars().enumerate() {
+                if character == ' ' {
+                    tiles.push(None);
+                    continue;
+                }
+                match legend.iter().find(|&&(c, _)| c == character) {
+                    Some(&(_, spec)) => tiles.push(Some(spec)),
+                    None => return error(line, index + 1, format!("'{}' is not in the legend", character)),
+                }
+            }
+            plan.tiles.push(tiles);
+        }
+        plan.normalize();
+        Ok(plan)
+    }
+
+    // drops trailing bedrock, pads the rows to the same width and grows the
+    // size to fit the tiles
+    pub fn normalize(&mut self) {
+        for row in self.tiles.iter_mut() {
+            while row.last() == Some(&None) {
+                row.pop();
+            }
+        }
+        while self.tiles.last().map_or(false, |row| row.is_empty()) {
+            self.tiles.pop();
+        }
+        let width = self.tiles.iter().map(|row| row.len()).max().unwrap_or(0);
+        for row in self.tiles.iter_mut() {
+            row.resize(width, None);
+        }
+        self.size = (self.size.0.max(width as i32), self.size.1.max(self.tiles.len() as i32));
+    }
+
+    // walls are the upper case letter of their room's floor, doors are digits
+    fn legend(&self) -> Vec<(char, TileSpec)> {
+        // the floor and wall sharing a letter
+        let mut letters: Vec<(i32, Option<TileSpec>, Option<TileSpec>)> = vec![];
+        let mut doors = 0;
+        let mut legend = vec![];
+        for spec in self.tiles.iter().flat_map(|row| row.iter()).filter_map(|t| *t) {
+            if legend.iter().any(|&(_, s)| s == spec) {
+                continue;
+            }
+            if spec.door.is_some() {
+                // past 9 they continue in latin extended-b
+                let character = if doors < 10 {
+                    (b'0' + doors as u8) as char
+                } else {
+                    ::std::char::from_u32(0x180 + doors - 10).unwrap()
+                };
+                doors += 1;
+                legend.push((character, spec));
+                continue;
+            }
+            let free = letters.iter().position(|&(room, floor, wall)| room == spec.room &&
+                                                if spec.wall { wall.is_none() } else { floor.is_none() });
+            let index = match free {
+                Some(index) => index,
+                None => {
+                    letters.push((spec.room, None, None));
+                    letters.len() - 1
+                }
+            };
+            if spec.wall {
+                letters[index].2 = Some(spec);
+            } else {
+                letters[index].1 = Some(spec);
+            }
+            // past z the letters continue in latin extended-a, which pairs upper and lower case
+            let (floor, wall) = if index < 26 {
+                ((b'a' + index as u8) as char, (b'A' + index as u8) as char)
+            } else {
+                let base = 0x100 + 2 * (index as u32 - 26);
+                (::std::char::from_u32(base + 1).unwrap(), ::std::char::from_u32(base).unwrap())
+            };
+            legend.push((if spec.wall { wall } else { floor }, spec));
+        }
+        legend.sort_by_key(|&(c, _)| c);
+        legend
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![];
+        let legend = self.legend();
+
+        lines.push(format!("size {} {}", self.size.0, self.size.1));
+        lines.push("legend".to_string());
+        for &(character, spec) in legend.iter() {
+            let kind = match spec.door {
+                Some(state) => format!("door {} {}", spec.room, state.name()),
+                None if spec.wall => format!("wall {}", spec.room),
+                None => format!("floor {}", spec.room),
+            };
+            if spec.light == 1.0 {
+                lines.push(format!("{} {}", character, kind));
+            } else {
+                lines.push(format!("{} {} {}", character, kind, spec.light));
+            }
+        }
+        lines.push("end".to_string());
+
+        lines.push("tiles".to_string());
+        let mut rows: Vec<String> = self.tiles.iter()
+            .map(|row| row.iter()
+                 .map(|tile| tile.and_then(|spec| legend.iter().find(|&&(_, s)| s == spec)).map_or(' ', |&(c, _)| c))
+                 .collect::<String>()
+                 .trim_end()
+                 .to_string())
+            .collect();
+        while rows.last().map_or(false, |row| row.is_empty()) {
+            rows.pop();
+        }
+        lines.extend(rows);
+        lines.push("end".to_string());
+        lines.push("".to_string());
+
+        for &(ref rect, area) in self.areas.iter() {
+            lines.push(format!("area {} {} {} {} {:?}", rect.left(), rect.top(),
+                               rect.right() - rect.left() + 1, rect.bottom() - rect.top() + 1, area));
+        }
+        for player in self.players.iter() {
+            let active = if player.active { " active" } else { "" };
+            lines.push(format!("player {} {} {}{}", player.name, player.position.0, player.position.1, active));
+        }
+        for i in self.interactables.iter() {
+            lines.push(format!("interactable {} {} {} security {} heading {}", i.kind,
+                               i.position.0, i.position.1, i.security_level, i.heading));
+        }
+        for npc in self.npcs.iter() {
+            lines.push(format!("npc {:?} {} {}", npc.instance, npc.position.0, npc.position.1));
+            lines.push(format!("  facing {} {}", npc.facing.0, npc.facing.1));
+            lines.push(format!("  faction {:?}", npc.faction));
+            if !npc.inventory.is_empty() {
+                let items = npc.inventory.iter().map(|i| format!("{:?}", i)).collect::<Vec<String>>();
+                lines.push(format!("  inventory {}", items.join(" ")));
+            }
+            if let Some(ref dialogue) = npc.dialogue {
+                lines.push(format!("  dialogue {}", dialogue));
+            }
+            for &(ref clock, target) in npc.errands.iter() {
+                lines.push(format!("  errand {} {} {}", clock, target.0, target.1));
+            }
+            lines.push("end".to_string());
+        }
+        for item in self.items.iter() {
+            lines.push(format!("item {:?} {} {}", item.instance, item.position.0, item.position.1));
+        }
+        lines.push("".to_string());
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use floor_plan::{ FloorPlan, FloorPlanError };
+    use tile_map::{ DoorState };
+    use components::item::{ ItemInstance };
+    use components::npc::{ NpcInstance };
+    use interactables::{ InteractableRegistry };
+
+    #[test]
+    fn shipped_tower_parses_and_round_trips() {
+        let plan = FloorPlan::parse(include_str!("../data/tower0.map")).unwrap();
+        assert_eq!(plan.size, (80, 43));
+        assert_eq!(plan.players.len(), 2);
+        assert_eq!(plan.npcs[0].instance, NpcInstance::Guard);
+        assert!(plan.npcs[0].inventory.contains(&ItemInstance::KeyCard(3)));
+        assert!(plan.tile((22, 22)).map_or(false, |t| !t.wall));
+        assert!(plan.tile((20, 22)).map_or(false, |t| t.wall));
+        assert_eq!(plan.tile((14, 28)).and_then(|t| t.door), Some(DoorState::Closed));
+        assert_eq!(FloorPlan::parse(&plan.to_text()), Ok(plan));
+    }
+
+    #[test]
+    fn errors_point_at_line_and_column() {
+        let unknown_tile = "legend\na floor 0\nend\ntiles\naaa\naxa\nend\n";
+        assert_eq!(FloorPlan::parse(unknown_tile).unwrap_err(),
+                   FloorPlanError { line: 6, column: 2, message: "'x' is not in the legend".into() });
+
+        let bad_number = "item Lighter 3 four\n";
+        let error = FloorPlan::parse(bad_number).unwrap_err();
+        assert_eq!((error.line, error.column), (1, 16));
+
+        let no_faction = "\nnpc Guard 1 1\nend\n";
+        let error = FloorPlan::parse(no_faction).unwrap_err();
+        assert_eq!((error.line, error.column), (2, 1));
+
+        let bad_door = "legend\n0 door 1 ajar\nend\n";
+        assert_eq!(FloorPlan::parse(bad_door).unwrap_err(),
+                   FloorPlanError { line: 2, column: 10, message: "unknown door state 'ajar'".into() });
+
+        assert!(FloorPlan::parse("tiles\n#\n").is_err());
+    }
+
+    #[test]
+    fn size_grows_to_fit_the_tiles() {
+        let plan = FloorPlan::parse("size 2 1\nlegend\na floor 0\nend\ntiles\naaa\n\na\nend\n").unwrap();
+        assert_eq!(plan.size, (3, 3));
+        let plan = FloorPlan::parse("size 120 60\n").unwrap();
+        assert_eq!(plan.size, (120, 60));
+        assert_eq!(FloorPlan::parse("size 0 5\n").unwrap_err().column, 6);
+    }
+
+    #[test]
+    fn interactables_must_be_known() {
+        let registry = InteractableRegistry::parse(include_str!("../data/interactables.txt")).unwrap();
+        assert!(FloorPlan::parse_known(include_str!("../data/tower0.map"), &registry).is_ok());
+
+        let unknown = "size 4 4\n\ninteractable  vending_machine 1 2\n";
+        assert!(FloorPlan::parse(unknown).is_ok());
+        assert_eq!(FloorPlan::parse_known(unknown, &registry).unwrap_err(),
+                   FloorPlanError { line: 3, column: 15, message: "unknown interactable kind 'vending_machine'".into() });
+    }
+}
@@ -1,13 +1,32 @@
+use std::collections::{ HashMap, HashSet };
+use specs::{ Entity };
+use components::space::{ Level };
+use components::progress::{ Event };
+use hacking::{ CodeBreaker };
+
 pub struct GameState {
     pub is_turn_based: bool,
     pub fov_needs_update: bool,
+    pub lockdowns: HashSet<Level>,
+    pub events: HashSet<Event>,
+    // terminal and actor of a requested hack
+    pub hacking: Option<(Entity, Entity)>,
+    // npc and actor of a requested conversation
+    pub conversation: Option<(Entity, Entity)>,
+    // codes of the terminals hacks were aborted at, kept until the loop resets
+    pub breakers: HashMap<Entity, CodeBreaker>,
 }
 
 impl Default for GameState {
     fn default() -> Self {
         GameState {
             is_turn_based: false,
-            fov_needs_update: true
+            fov_needs_update: true,
+            lockdowns: HashSet::new(),
+            events: HashSet::new(),
+            hacking: None,
+            conversation: None,
+            breakers: HashMap::new(),
         }
     }
 }
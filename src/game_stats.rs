@@ -1,25 +1,57 @@
-use std::time::{ Duration };
-use engine::time::{ Timer };
-
 pub struct GameStats {
-    timer: Timer,
-    time_loop_length: Duration,
+    // seconds a loop lasts
+    loop_length: f32,
+    // seconds of the loop that were played, which stands still between turns
+    clock: f32,
 }
 
 impl Default for GameStats {
     fn default() -> Self {
-        let time_loop_length = Duration::from_secs(10 * 60);
-        let timer = Timer::new(time_loop_length);
-        GameStats { timer: timer, time_loop_length: time_loop_length }
+        GameStats { loop_length: 10.0 * 60.0, clock: 0.0 }
     }
 }
 
 impl GameStats {
-    pub fn time_left(&self) -> i32 {
-        return self.timer.time_left().as_secs() as i32;
+    // the countdown on screen, it runs on the same clock as the npc schedules
+    pub fn time_left(&self) -> f32 {
+        self.loop_length - self.clock
+    }
+
+    pub fn is_loop_over(&self) -> bool {
+        self.time_left() < 0.0
+    }
+
+    pub fn elapsed(&self) -> f32 {
+        self.clock
+    }
+
+    pub fn advance(&mut self, seconds: f32) {
+        self.clock += seconds;
+    }
+
+    // a client follows the clock of the host
+    pub fn set_elapsed(&mut self, seconds: f32) {
+        self.clock = seconds;
     }
 
     pub fn reset(&mut self) {
-        self.timer = Timer::new(self.time_loop_length);
+        self.clock = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game_stats::{ GameStats };
+
+    #[test]
+    fn the_loop_ends_on_the_loop_clock() {
+        let mut stats = GameStats::default();
+        stats.advance(430.0);
+        assert!(stats.time_left() == 170.0);
+        assert!(!stats.is_loop_over());
+        stats.advance(171.0);
+        assert!(stats.is_loop_over());
+        stats.reset();
+        assert!(stats.elapsed() == 0.0 && !stats.is_loop_over());
     }
 }
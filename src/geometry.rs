@@ -1,23 +1,63 @@
-use components::space::{ Vector, Position };
 use std::cmp::{ min, max };
 
+const NEIGHBOURS: [(i32, i32); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+pub const MAX_CORNERS: usize = 12;
+
 pub trait Shape: Copy + IntoIterator<Item=(i32, i32)> {
     fn center(&self) -> (i32, i32);
+    // encloses every enclosed position, the right and bottom side are exclusive
     fn bounding_box(&self) -> Rect;
 
     fn is_enclosed(&self, pos: (i32, i32)) -> bool;
-    fn is_boundary(&self, pos: (i32, i32)) -> bool;
-    fn is_interior(&self, pos: (i32, i32)) -> bool;
+
+    // enclosed positions touching the outside, diagonals included so walls have no gaps
+    fn is_boundary(&self, pos: (i32, i32)) -> bool {
+        self.is_enclosed(pos) && NEIGHBOURS.iter().any(|d| !self.is_enclosed((pos.0 + d.0, pos.1 + d.1)))
+    }
+
+    fn is_interior(&self, pos: (i32, i32)) -> bool {
+        self.is_enclosed(pos) && !self.is_boundary(pos)
+    }
+
+    fn union<T: Shape>(self, other: T) -> Union<Self, T> {
+        Union { a: self, b: other }
+    }
+
+    fn difference<T: Shape>(self, other: T) -> Difference<Self, T> {
+        Difference { a: self, b: other }
+    }
+
+    fn intersection<T: Shape>(self, other: T) -> Intersection<Self, T> {
+        Intersection { a: self, b: other }
+    }
+
+    fn translate(self, offset: (i32, i32)) -> Translate<Self> {
+        Translate { shape: self, offset: offset }
+    }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub struct Line {
-    p1: (i32, i32),
-    p2: (i32, i32),
+// which side of the line from a to b p lies on
+fn side(a: (i32, i32), b: (i32, i32), p: (i32, i32)) -> i32 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+fn on_segment(a: (i32, i32), b: (i32, i32), p: (i32, i32)) -> bool {
+    side(a, b, p) == 0
+        && p.0 >= min(a.0, b.0) && p.0 <= max(a.0, b.0)
+        && p.1 >= min(a.1, b.1) && p.1 <= max(a.1, b.1)
+}
+
+fn bounds(points: &[(i32, i32)]) -> Rect {
+    Rect {
+        x1: points.iter().map(|p| p.0).min().unwrap(),
+        y1: points.iter().map(|p| p.1).min().unwrap(),
+        x2: points.iter().map(|p| p.0).max().unwrap() + 1,
+        y2: points.iter().map(|p| p.1).max().unwrap() + 1,
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
-pub struct Ray {
+pub struct Line {
     p1: (i32, i32),
     p2: (i32, i32),
 }
@@ -42,6 +82,50 @@ pub struct Ellipse {
     radius: (i32, i32),
 }
 
+// a simple polygon, edges may not cross
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Polygon {
+    corners: [(i32, i32); MAX_CORNERS],
+    count: usize,
+}
+
+// a circle with a circular hole
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ring {
+    outer: Ellipse,
+    hole: Ellipse,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RoundedRect {
+    rect: Rect,
+    radius: i32,
+}
+
+// composites, walls only run where the combined shape meets the outside
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Union<A: Shape, B: Shape> {
+    a: A,
+    b: B,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Difference<A: Shape, B: Shape> {
+    a: A,
+    b: B,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Intersection<A: Shape, B: Shape> {
+    a: A,
+    b: B,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Translate<A: Shape> {
+    shape: A,
+    offset: (i32, i32),
+}
 
 impl Line {
     pub fn new(x1: i32, y1: i32, x2: i32, y2: i32) -> Self {
@@ -50,13 +134,6 @@ impl Line {
     }
 }
 
-impl Ray {
-    pub fn new(p1: (i32, i32), p2: (i32, i32)) -> Self {
-        assert!(p1.0 != p2.0 || p1.1 != p2.1);
-        Ray { p1: p1, p2: p2 }
-    }
-}
-
 impl Rect {
     pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
         Rect {
@@ -100,6 +177,27 @@ impl Rect {
     pub fn right(self: &Rect) -> i32 {
         self.x2 - 1
     }
+
+    fn is_empty(self: &Rect) -> bool {
+        self.x1 >= self.x2 || self.y1 >= self.y2
+    }
+
+    fn cover(self: &Rect, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        } else if other.is_empty() {
+            return *self;
+        }
+        Rect { x1: min(self.x1, other.x1), y1: min(self.y1, other.y1),
+               x2: max(self.x2, other.x2), y2: max(self.y2, other.y2) }
+    }
+
+    fn overlap(self: &Rect, other: &Rect) -> Rect {
+        let rect = Rect { x1: max(self.x1, other.x1), y1: max(self.y1, other.y1),
+                          x2: min(self.x2, other.x2), y2: min(self.y2, other.y2) };
+        // an empty rect must not iterate
+        if rect.is_empty() { Rect::new(rect.x1, rect.y1, 0, 0) } else { rect }
+    }
 }
 
 impl Triangle {
@@ -117,6 +215,37 @@ impl Ellipse {
     }
 }
 
+impl Polygon {
+    pub fn new(corners: &[(i32, i32)]) -> Self {
+        assert!(corners.len() >= 3 && corners.len() <= MAX_CORNERS);
+        let mut polygon = Polygon { corners: [(0, 0); MAX_CORNERS], count: corners.len() };
+        polygon.corners[..corners.len()].copy_from_slice(corners);
+        polygon
+    }
+
+    fn corners(&self) -> &[(i32, i32)] {
+        &self.corners[..self.count]
+    }
+
+    fn edges(&self) -> Vec<((i32, i32), (i32, i32))> {
+        let corners = self.corners();
+        corners.iter().cloned().zip(corners.iter().cycle().skip(1).cloned()).collect()
+    }
+}
+
+impl Ring {
+    pub fn new(center: (i32, i32), radius: i32, hole: i32) -> Self {
+        assert!(hole < radius);
+        Ring { outer: Ellipse::circle(center, radius), hole: Ellipse::circle(center, hole) }
+    }
+}
+
+impl RoundedRect {
+    pub fn new(x: i32, y: i32, w: i32, h: i32, radius: i32) -> Self {
+        RoundedRect { rect: Rect::new(x, y, w, h), radius: max(0, min(radius, min(w, h) / 2)) }
+    }
+}
+
 impl Shape for Rect {
     fn center(&self) -> (i32, i32) {
         let center_x = (self.x1 + self.x2) / 2;
@@ -133,7 +262,7 @@ impl Shape for Rect {
     }
 
     fn is_boundary(&self, pos: (i32, i32)) -> bool {
-        pos.0 == self.x1 || pos.0 == self.x2 - 1 || pos.1 == self.y1 || pos.1 == self.y2 - 1
+        self.is_enclosed(pos) && (pos.0 == self.x1 || pos.0 == self.x2 - 1 || pos.1 == self.y1 || pos.1 == self.y2 - 1)
     }
 
     fn is_interior(&self, pos: (i32, i32)) -> bool {
@@ -149,41 +278,15 @@ impl Shape for Triangle {
     }
 
     fn bounding_box(&self) -> Rect {
-        Rect {
-            x1: min(min(self.p1.0, self.p2.0), self.p3.0),
-            y1: min(min(self.p1.1, self.p2.1), self.p3.1),
-            x2: max(max(self.p1.0, self.p2.0), self.p3.0),
-            y2: max(max(self.p1.1, self.p2.1), self.p3.1),
-        }
+        bounds(&[self.p1, self.p2, self.p3])
     }
 
+    // the edges belong to the triangle, whichever way it winds
     fn is_enclosed(&self, pos: (i32, i32)) -> bool {
-        let p1 = Position { x: self.p1.0 as f32, y: self.p1.1 as f32 };
-        let p2 = Position { x: self.p2.0 as f32, y: self.p2.1 as f32 };
-        let p3 = Position { x: self.p3.0 as f32, y: self.p3.1 as f32 };
-        let ps = Position { x: pos.0 as f32, y: pos.1 as f32 };
-        let v1 = p2 - p1;
-        let v2 = p3 - p1;
-        let v3 = ps - p1;
-        let d11 = v1.dot(&v1);
-        let d12 = v1.dot(&v2);
-        let d13 = v1.dot(&v3);
-        let d22 = v2.dot(&v2);
-        let d23 = v2.dot(&v3);
-
-        let inv_denominator = 1.0 / (d11 * d22 - d12 * d12);
-        let u = (d22 * d13 - d12 * d23) * inv_denominator;
-        let v = (d11 * d23 - d12 * d13) * inv_denominator;
-
-        u >= 0.0 && v >= 0.0 && (u + v) < 1.0
-    }
-
-    fn is_boundary(&self, pos: (i32, i32)) -> bool {
-        false
-    }
-
-    fn is_interior(&self, pos: (i32, i32)) -> bool {
-        self.is_enclosed(pos)
+        let sides = [side(self.p1, self.p2, pos), side(self.p2, self.p3, pos), side(self.p3, self.p1, pos)];
+        let inside = !(sides.iter().any(|s| *s < 0) && sides.iter().any(|s| *s > 0));
+        // a flat triangle is only its edges
+        inside && self.bounding_box().is_enclosed(pos)
     }
 }
 
@@ -196,24 +299,79 @@ impl Shape for Ellipse {
         Rect {
             x1: self.center.0 - self.radius.0,
             y1: self.center.1 - self.radius.1,
-            x2: self.center.0 + self.radius.0,
-            y2: self.center.1 + self.radius.1,
+            x2: self.center.0 + self.radius.0 + 1,
+            y2: self.center.1 + self.radius.1 + 1,
         }
     }
 
+    // half a cell more radius keeps the tips from being single cells
     fn is_enclosed(&self, pos: (i32, i32)) -> bool {
-        let p = (self.center.0 - pos.0, self.center.1 - pos.1);
-        let a = (p.0 * p.0) / (self.radius.0 * self.radius.0);
-        let b = (p.1 * p.1) / (self.radius.1 * self.radius.1);
-        a + b <= 1
+        let dx = (pos.0 - self.center.0) as f32 / (self.radius.0 as f32 + 0.5);
+        let dy = (pos.1 - self.center.1) as f32 / (self.radius.1 as f32 + 0.5);
+        dx * dx + dy * dy <= 1.0 && self.bounding_box().is_enclosed(pos)
     }
+}
 
-    fn is_boundary(&self, pos: (i32, i32)) -> bool {
-        false
+impl Shape for Polygon {
+    fn center(&self) -> (i32, i32) {
+        let n = self.count as i32;
+        (self.corners().iter().map(|p| p.0).sum::<i32>() / n, self.corners().iter().map(|p| p.1).sum::<i32>() / n)
     }
 
-    fn is_interior(&self, pos: (i32, i32)) -> bool {
-        self.is_enclosed(pos)
+    fn bounding_box(&self) -> Rect {
+        bounds(self.corners())
+    }
+
+    // even-odd crossings of a ray to the right, edges belong to the polygon
+    fn is_enclosed(&self, pos: (i32, i32)) -> bool {
+        let edges = self.edges();
+        if edges.iter().any(|&(a, b)| on_segment(a, b, pos)) {
+            return true;
+        }
+        let crossings = edges.iter()
+            .filter(|&&(a, b)| (a.1 > pos.1) != (b.1 > pos.1))
+            .filter(|&&(a, b)| {
+                // where the edge crosses the row, compared without dividing
+                let s = side(a, b, pos);
+                if b.1 > a.1 { s > 0 } else { s < 0 }
+            })
+            .count();
+        crossings % 2 == 1
+    }
+}
+
+impl Shape for Ring {
+    fn center(&self) -> (i32, i32) {
+        self.outer.center()
+    }
+
+    fn bounding_box(&self) -> Rect {
+        self.outer.bounding_box()
+    }
+
+    fn is_enclosed(&self, pos: (i32, i32)) -> bool {
+        self.outer.is_enclosed(pos) && !self.hole.is_enclosed(pos)
+    }
+}
+
+impl Shape for RoundedRect {
+    fn center(&self) -> (i32, i32) {
+        self.rect.center()
+    }
+
+    fn bounding_box(&self) -> Rect {
+        self.rect
+    }
+
+    // the corners are quarter circles around points inset by the radius
+    fn is_enclosed(&self, pos: (i32, i32)) -> bool {
+        if !self.rect.is_enclosed(pos) {
+            return false;
+        }
+        let r = self.radius;
+        let x = max(self.rect.left() + r, min(pos.0, self.rect.right() - r));
+        let y = max(self.rect.top() + r, min(pos.1, self.rect.bottom() - r));
+        Ellipse::circle((x, y), r).is_enclosed(pos)
     }
 }
 
@@ -233,19 +391,126 @@ impl IntoIterator for Ellipse {
     }
 }
 
-impl IntoIterator for Line {
+impl<A: Shape, B: Shape> Shape for Union<A, B> {
+    fn center(&self) -> (i32, i32) {
+        self.bounding_box().center()
+    }
+
+    fn bounding_box(&self) -> Rect {
+        self.a.bounding_box().cover(&self.b.bounding_box())
+    }
+
+    fn is_enclosed(&self, pos: (i32, i32)) -> bool {
+        self.a.is_enclosed(pos) || self.b.is_enclosed(pos)
+    }
+}
+
+impl<A: Shape, B: Shape> Shape for Difference<A, B> {
+    fn center(&self) -> (i32, i32) {
+        self.a.center()
+    }
+
+    fn bounding_box(&self) -> Rect {
+        self.a.bounding_box()
+    }
+
+    fn is_enclosed(&self, pos: (i32, i32)) -> bool {
+        self.a.is_enclosed(pos) && !self.b.is_enclosed(pos)
+    }
+}
+
+impl<A: Shape, B: Shape> Shape for Intersection<A, B> {
+    fn center(&self) -> (i32, i32) {
+        self.bounding_box().center()
+    }
+
+    fn bounding_box(&self) -> Rect {
+        self.a.bounding_box().overlap(&self.b.bounding_box())
+    }
+
+    fn is_enclosed(&self, pos: (i32, i32)) -> bool {
+        self.a.is_enclosed(pos) && self.b.is_enclosed(pos)
+    }
+}
+
+impl<A: Shape> Shape for Translate<A> {
+    fn center(&self) -> (i32, i32) {
+        let c = self.shape.center();
+        (c.0 + self.offset.0, c.1 + self.offset.1)
+    }
+
+    fn bounding_box(&self) -> Rect {
+        let r = self.shape.bounding_box();
+        Rect { x1: r.x1 + self.offset.0, y1: r.y1 + self.offset.1,
+               x2: r.x2 + self.offset.0, y2: r.y2 + self.offset.1 }
+    }
+
+    fn is_enclosed(&self, pos: (i32, i32)) -> bool {
+        self.shape.is_enclosed((pos.0 - self.offset.0, pos.1 - self.offset.1))
+    }
+}
+
+impl<A: Shape, B: Shape> IntoIterator for Union<A, B> {
     type Item = (i32, i32);
-    type IntoIter = BresenhamIter;
-    fn into_iter(self) -> BresenhamIter {
-        BresenhamIter::init(self.p1, self.p2, false)
+    type IntoIter = ShapeIter<Union<A, B>>;
+    fn into_iter(self) -> ShapeIter<Union<A, B>> {
+        ShapeIter { shape: Box::new(self), rect: self.bounding_box().into_iter() }
+    }
+}
+
+impl<A: Shape, B: Shape> IntoIterator for Difference<A, B> {
+    type Item = (i32, i32);
+    type IntoIter = ShapeIter<Difference<A, B>>;
+    fn into_iter(self) -> ShapeIter<Difference<A, B>> {
+        ShapeIter { shape: Box::new(self), rect: self.bounding_box().into_iter() }
+    }
+}
+
+impl<A: Shape, B: Shape> IntoIterator for Intersection<A, B> {
+    type Item = (i32, i32);
+    type IntoIter = ShapeIter<Intersection<A, B>>;
+    fn into_iter(self) -> ShapeIter<Intersection<A, B>> {
+        ShapeIter { shape: Box::new(self), rect: self.bounding_box().into_iter() }
+    }
+}
+
+impl<A: Shape> IntoIterator for Translate<A> {
+    type Item = (i32, i32);
+    type IntoIter = ShapeIter<Translate<A>>;
+    fn into_iter(self) -> ShapeIter<Translate<A>> {
+        ShapeIter { shape: Box::new(self), rect: self.bounding_box().into_iter() }
+    }
+}
+
+impl IntoIterator for Polygon {
+    type Item = (i32, i32);
+    type IntoIter = ShapeIter<Polygon>;
+    fn into_iter(self) -> ShapeIter<Polygon> {
+        ShapeIter { shape: Box::new(self), rect: self.bounding_box().into_iter() }
     }
 }
 
-impl IntoIterator for Ray {
+impl IntoIterator for Ring {
+    type Item = (i32, i32);
+    type IntoIter = ShapeIter<Ring>;
+    fn into_iter(self) -> ShapeIter<Ring> {
+        ShapeIter { shape: Box::new(self), rect: self.bounding_box().into_iter() }
+    }
+}
+
+impl IntoIterator for RoundedRect {
+    type Item = (i32, i32);
+    type IntoIter = ShapeIter<RoundedRect>;
+    fn into_iter(self) -> ShapeIter<RoundedRect> {
+        ShapeIter { shape: Box::new(self), rect: self.bounding_box().into_iter() }
+    }
+}
+
+impl IntoIterator for Line {
     type Item = (i32, i32);
     type IntoIter = BresenhamIter;
     fn into_iter(self) -> BresenhamIter {
-        BresenhamIter::init(self.p1, self.p2, true)
+        BresenhamIter::init(self.p1, self.p2)
     }
 }
 
@@ -287,12 +552,11 @@ pub struct BresenhamIter {
     delta: (f32, f32),
     swap: bool,
     d: f32,
-    overshoot: bool,
     done: bool,
 }
 
 impl BresenhamIter {
-    pub fn init(p1: (i32, i32), p2: (i32, i32), overshoot: bool) -> Self {
+    pub fn init(p1: (i32, i32), p2: (i32, i32)) -> Self {
         let mut delta = ((p2.0 as f32 - p1.0 as f32).abs(),
                      (p2.1 as f32 - p1.1 as f32).abs());
         let sign = ((p2.0 - p1.0).signum(), (p2.1 - p1.1).signum());
@@ -312,7 +576,6 @@ impl BresenhamIter {
             d: d,
             swap: swap,
             done: false,
-            overshoot: overshoot,
         }
     }
 
@@ -326,7 +589,7 @@ impl Iterator for BresenhamIter {
         }
 
         let result = Some(self.start);
-        if !self.overshoot && self.index as f32 >= self.delta.0 {
+        if self.index as f32 >= self.delta.0 {
             self.done = true;
         } else {
             while self.d >= 0.0 {
@@ -371,7 +634,7 @@ impl Iterator for RectIter {
 }
 #[cfg(test)]
 mod tests {
-    use geometry::{ Line };
+    use geometry::{ Shape, Line, Rect, Triangle, Ellipse, Polygon, Ring, RoundedRect };
     use std::fmt::{ Display, Debug };
 
     fn assert_equals<T>(a: T, b: T)
@@ -426,4 +689,87 @@ mod tests {
     fn steep_lines() {
         assert_equals(Line::new(0,0,7,10).into_iter().count(), 11);
     }
+
+    // every enclosed position is boundary or interior but not both, and the
+    // iterator walks exactly the enclosed positions
+    fn assert_partitioned<T: Shape + Debug>(shape: T) {
+        let area = shape.bounding_box().grow(2);
+        let enclosed = shape.into_iter().collect::<Vec<(i32, i32)>>();
+        for p in area.into_iter() {
+            assert!(shape.is_boundary(p) != shape.is_interior(p) || !shape.is_enclosed(p),
+                    "{:?} at {:?} is boundary and interior", shape, p);
+            assert!(shape.is_enclosed(p) == (shape.is_boundary(p) || shape.is_interior(p)),
+                    "{:?} at {:?} is neither boundary nor interior", shape, p);
+            assert!(shape.is_enclosed(p) == enclosed.contains(&p), "{:?} misses {:?}", shape, p);
+        }
+        assert!(enclosed.iter().any(|p| shape.is_boundary(*p)), "{:?} has no walls", shape);
+    }
+
+    #[test]
+    fn boundary_and_interior_partition_the_shapes() {
+        for size in 1..8 {
+            assert_partitioned(Rect::new(3, -2, size, size + 2));
+            assert_partitioned(Ellipse::new((5, 5), (size, 8 - size)));
+            assert_partitioned(Ellipse::circle((-3, 4), size - 1));
+            assert_partitioned(Triangle::new((0, 0), (3 * size, size), (size, 2 * size)));
+            assert_partitioned(Triangle::new((0, 0), (size, 0), (2 * size, 0)));
+            assert_partitioned(Polygon::new(&[(0, 0), (2 * size, 0), (size, size), (2 * size, 2 * size), (0, 2 * size)]));
+            assert_partitioned(Ring::new((10, 10), size + 2, size - 1));
+            assert_partitioned(RoundedRect::new(1, 1, 2 * size + 1, size + 2, size / 2));
+        }
+    }
+
+    #[test]
+    fn rooms_are_walled_in() {
+        let triangle = Triangle::new((2, 1), (14, 5), (4, 12));
+        let ellipse = Ellipse::new((8, 8), (6, 3));
+        for p in triangle.into_iter().filter(|p| triangle.is_interior(*p)) {
+            for d in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)].iter() {
+                assert!(triangle.is_enclosed((p.0 + d.0, p.1 + d.1)));
+            }
+        }
+        assert!(ellipse.is_enclosed((2, 8)) && ellipse.is_boundary((2, 8)));
+        assert!(ellipse.is_enclosed((14, 8)) && !ellipse.is_enclosed((15, 8)));
+        assert!(ellipse.is_interior((8, 8)) && ellipse.is_boundary((8, 5)));
+    }
+
+    #[test]
+    fn new_shapes_enclose_what_they_should() {
+        let polygon = Polygon::new(&[(0, 0), (10, 0), (5, 5), (10, 10), (0, 10)]);
+        assert!(polygon.is_enclosed((2, 5)) && !polygon.is_enclosed((8, 5)));
+        assert!(polygon.is_enclosed((7, 3)) && polygon.is_boundary((7, 3)));
+
+        let ring = Ring::new((0, 0), 6, 3);
+        assert!(!ring.is_enclosed((0, 0)) && ring.is_enclosed((5, 0)) && ring.is_boundary((4, 0)));
+
+        let rounded = RoundedRect::new(0, 0, 10, 6, 2);
+        assert!(!rounded.is_enclosed((0, 0)) && rounded.is_enclosed((5, 0)) && rounded.is_enclosed((1, 1)));
+    }
+
+    #[test]
+    fn composites_partition_like_their_parts() {
+        let office = Rect::new(0, 0, 10, 4).union(Rect::new(0, 0, 4, 10));
+        let atrium = Rect::new(0, 0, 12, 12).difference(Ellipse::circle((6, 6), 3));
+        let lens = Ellipse::circle((0, 0), 5).intersection(Ellipse::circle((6, 0), 5));
+        assert_partitioned(office);
+        assert_partitioned(atrium);
+        assert_partitioned(lens);
+        assert_partitioned(office.translate((-7, 3)));
+    }
+
+    #[test]
+    fn l_shaped_offices_have_no_inner_walls() {
+        let office = Rect::new(0, 0, 10, 4).union(Rect::new(0, 0, 4, 10));
+        assert_equals(office.into_iter().count(), 40 + 24);
+        // where the wings meet is floor
+        assert!(office.is_interior((3, 2)) && office.is_interior((2, 3)));
+        assert!(office.is_boundary((9, 2)) && office.is_boundary((3, 5)));
+
+        let apart = Rect::new(0, 0, 2, 2).intersection(Rect::new(5, 5, 2, 2));
+        assert_equals(apart.into_iter().count(), 0);
+
+        let moved = office.translate((10, 20));
+        assert!(moved.is_interior((13, 22)) && !moved.is_enclosed((3, 2)));
+        assert_equals_pos(moved.center(), (15, 25));
+    }
 }
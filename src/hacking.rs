@@ -0,0 +1,325 @@
+use std::cmp::{ max };
+use std::mem;
+use rand::{ Rng, thread_rng };
+use specs::{ World, Entity, Join };
+use tcod::colors::{ self };
+use tcod::input::{ KeyCode };
+
+use engine::state::{ State, Transition };
+use engine::tcod::{ Tcod };
+use engine::input_handler::{ InputHandler };
+use components::appearance::{ Renderable };
+use components::interaction::{ Interactable };
+use components::inventory::{ Inventory };
+use components::item::{ Item, ItemInstance };
+use components::progress::{ Event, AreaInstance };
+use components::space::{ Position, Level };
+use game_state::{ GameState };
+use tower::{ Tower };
+use maps::{ Map };
+
+// codes are made of the digits 0 to SYMBOLS - 1
+const SYMBOLS: u8 = 6;
+const BASE_DIGITS: usize = 3;
+const MIN_DIGITS: usize = 2;
+const BASE_ATTEMPTS: u32 = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Feedback {
+    pub exact: usize,
+    pub partial: usize,
+}
+
+pub struct CodeBreaker {
+    code: Vec<u8>,
+    attempts_left: u32,
+    pub history: Vec<(Vec<u8>, Feedback)>,
+}
+
+impl CodeBreaker {
+    pub fn new(code: Vec<u8>, attempts: u32) -> Self {
+        CodeBreaker { code: code, attempts_left: attempts, history: vec![] }
+    }
+
+    pub fn random<R: Rng>(digits: usize, attempts: u32, rng: &mut R) -> Self {
+        let code = (0..digits).map(|_| rng.gen_range(0, SYMBOLS)).collect();
+        CodeBreaker::new(code, attempts)
+    }
+
+    pub fn digits(&self) -> usize {
+        self.code.len()
+    }
+
+    pub fn attempts_left(&self) -> u32 {
+        self.attempts_left
+    }
+
+    pub fn guess(&mut self, guess: &[u8]) -> Option<Feedback> {
+        if guess.len() != self.code.len() || self.attempts_left == 0 || self.is_solved() {
+            return None;
+        }
+        let exact = self.code.iter().zip(guess.iter())
+            .filter(|&(c, g)| c == g)
+            .count();
+        let common = (0..SYMBOLS)
+            .map(|s| {
+                let in_code = self.code.iter().filter(|c| **c == s).count();
+                let in_guess = guess.iter().filter(|g| **g == s).count();
+                in_code.min(in_guess)
+            })
+            .sum::<usize>();
+        let feedback = Feedback { exact: exact, partial: common - exact };
+        self.attempts_left -= 1;
+        self.history.push((guess.to_vec(), feedback));
+        Some(feedback)
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.history.last().map_or(false, |&(_, f)| f.exact == self.code.len())
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.attempts_left == 0 && !self.is_solved()
+    }
+
+    // pulling out trips the countermeasures just like a wrong code
+    pub fn abort(&mut self) {
+        if !self.is_solved() {
+            self.attempts_left = self.attempts_left.saturating_sub(1);
+        }
+    }
+}
+
+// the security level of a terminal makes the code longer, the right gear makes it easier
+pub fn difficulty(level: i32, items: &[ItemInstance]) -> (usize, u32) {
+    let mut digits = BASE_DIGITS + max(level, 0) as usize;
+    let mut attempts = BASE_ATTEMPTS;
+    for item in items {
+        match *item {
+            ItemInstance::HitachiRam => digits = max(digits - 1, MIN_DIGITS),
+            ItemInstance::Simstim => attempts += 2,
+            ItemInstance::PocketVtr => attempts += 1,
+            _ => (),
+        }
+    }
+    (digits, attempts)
+}
+
+pub struct Hacking {
+    target: Entity,
+    actor: Entity,
+    breaker: CodeBreaker,
+    guess: Vec<u8>,
+}
+
+impl Hacking {
+    pub fn new(target: Entity, actor: Entity) -> Self {
+        Hacking {
+            target: target,
+            actor: actor,
+            breaker: CodeBreaker::new(vec![], 0),
+            guess: vec![],
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.breaker.is_solved() || self.breaker.is_failed()
+    }
+
+    fn finish(&self, world: &mut World) {
+        let entities = world.entities();
+        let mut interactables = world.write::<Interactable>();
+        let mut renderables = world.write::<Renderable>();
+        let positions = world.read::<Position>();
+        let levels = world.read::<Level>();
+        let mut tower = world.write_resource::<Tower>();
+        let mut state = world.write_resource::<GameState>();
+
+        let level = match levels.get(self.target) {
+            Some(level) => *level,
+            None => return,
+        };
+        let (signals, accesses) = match interactables.get_mut(self.target) {
+            Some(terminal) => {
+                let signals = terminal.tagged("signal:");
+                let accesses = terminal.tagged("access:");
+                if self.breaker.is_solved() {
+                    terminal.trigger("hacked");
+                    (signals, accesses)
+                } else {
+                    terminal.trigger("failed");
+                    (vec![], vec![])
+                }
+            }
+            None => return,
+        };
+
+        // access lasts for the loop, like the hacked terminal that granted it
+        for area in accesses.iter().filter_map(|a| AreaInstance::from_name(a)) {
+            state.events.insert(Event::HasAccess(area));
+        }
+
+        let maps = tower.get_mut(&level).unwrap();
+        if signals.iter().any(|s| s == "unlock") && maps.unlock_doors() {
+            state.fov_needs_update = true;
+        }
+        for (id, interactable, _, l) in (&*entities, &mut interactables, &positions, &levels).join() {
+            if *l != level {
+                continue;
+            }
+            let mut changed = id == self.target;
+            for signal in signals.iter() {
+                changed = interactable.trigger(signal) || changed;
+            }
+            if changed {
+                renderables.insert(id, interactable.get_renderable());
+                maps.set_blocking(Map::Character, &id, interactable.is_blocking());
+                maps.set_sight_blocking(Map::Character, &id, interactable.is_sight_blocking());
+                state.fov_needs_update = true;
+            }
+        }
+    }
+}
+
+impl State for Hacking {
+    fn start(&mut self, _tcod: &mut Tcod, world: &mut World) {
+        let level = world.read::<Interactable>().get(self.target).map_or(0, |t| t.level);
+        let items = {
+            let inventories = world.read::<Inventory>();
+            let items = world.read::<Item>();
+            inventories.get(self.actor)
+                .map(|inventory| inventory.items.iter()
+                     .filter_map(|i| items.get(*i))
+                     .map(|i| i.instance)
+                     .collect::<Vec<ItemInstance>>())
+                .unwrap_or(vec![])
+        };
+        // coming back to a terminal resumes the code from before instead of rolling a new one
+        let breaker = world.write_resource::<GameState>().breakers.remove(&self.target);
+        self.breaker = breaker.unwrap_or_else(|| {
+            let (digits, attempts) = difficulty(level, &items);
+            CodeBreaker::random(digits, attempts, &mut thread_rng())
+        });
+    }
+
+    fn handle_events(&mut self, _tcod: &mut Tcod, world: &mut World) -> Transition {
+        let mut submitted = false;
+        let mut aborted = false;
+        let transition = {
+            world.write_resource::<InputHandler>().intercept(|input| if self.is_finished() {
+                if input.key.pressed { Transition::Pop } else { Transition::None }
+            } else if input.is_key_pressed(KeyCode::Escape) {
+                self.breaker.abort();
+                aborted = true;
+                Transition::Pop
+            } else {
+                if input.is_key_pressed(KeyCode::Backspace) {
+                    self.guess.pop();
+                } else if input.is_key_pressed(KeyCode::Enter) {
+                    let guess = self.guess.clone();
+                    submitted = self.breaker.guess(&guess).is_some();
+                    if submitted {
+                        self.guess.clear();
+                    }
+                } else if let Some(digit) = input.pressed_digit {
+                    if (digit as u8) < SYMBOLS && self.guess.len() < self.breaker.digits() {
+                        self.guess.push(digit as u8);
+                    }
+                }
+                Transition::None
+            })
+        };
+        if (submitted || aborted) && self.is_finished() {
+            self.finish(world);
+        } else if aborted {
+            let breaker = mem::replace(&mut self.breaker, CodeBreaker::new(vec![], 0));
+            world.write_resource::<GameState>().breakers.insert(self.target, breaker);
+        }
+        transition
+    }
+
+    fn render(&mut self, tcod: &mut Tcod, _world: &mut World) {
+        tcod.clear(colors::BLACK);
+        tcod.render_overlay_text((2, 2), colors::LIGHT_GREEN, "HITACHI Z-80 // REMOTE ACCESS");
+        tcod.render_overlay_text((2, 4), colors::LIGHT_GREY,
+                                 &format!("Enter the {} digit access code, digits 0 to {}.",
+                                          self.breaker.digits(), SYMBOLS - 1));
+        tcod.render_overlay_text((2, 5), colors::LIGHT_GREY,
+                                 "* right digit in the right place, + right digit in the wrong place");
+
+        let mut y = 7;
+        for &(ref guess, feedback) in self.breaker.history.iter() {
+            let digits = guess.iter().map(|d| d.to_string()).collect::<Vec<String>>().join(" ");
+            let marks = format!("{}{}", "*".repeat(feedback.exact), "+".repeat(feedback.partial));
+            tcod.render_overlay_text((4, y), colors::LIGHT_GREY, &format!("{}   {}", digits, marks));
+            y += 1;
+        }
+
+        y += 1;
+        if self.breaker.is_solved() {
+            tcod.render_overlay_text((2, y), colors::LIGHT_GREEN, "ACCESS GRANTED - press any key");
+        } else if self.breaker.is_failed() {
+            tcod.render_overlay_text((2, y), colors::LIGHT_RED, "LOCKED OUT - press any key");
+        } else {
+            let current = (0..self.breaker.digits())
+                .map(|i| self.guess.get(i).map_or("_".to_string(), |d| d.to_string()))
+                .collect::<Vec<String>>()
+                .join(" ");
+            tcod.render_overlay_text((2, y), colors::WHITE, &format!("> {}", current));
+            tcod.render_overlay_text((2, y + 2), colors::LIGHT_GREY,
+                                     &format!("{} attempts left, enter to submit, escape to abort",
+                                              self.breaker.attempts_left()));
+        }
+        tcod.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hacking::{ CodeBreaker, Feedback, difficulty };
+    use components::item::{ ItemInstance };
+
+    #[test]
+    fn scores_exact_and_partial_digits() {
+        let mut breaker = CodeBreaker::new(vec![1, 2, 3, 3], 5);
+        assert!(breaker.guess(&[1, 3, 0, 0]) == Some(Feedback { exact: 1, partial: 1 }));
+        assert!(breaker.guess(&[3, 3, 3, 3]) == Some(Feedback { exact: 2, partial: 0 }));
+        assert!(breaker.guess(&[3, 3, 2, 1]) == Some(Feedback { exact: 0, partial: 4 }));
+        assert!(breaker.attempts_left() == 2);
+    }
+
+    #[test]
+    fn rejects_wrong_length() {
+        let mut breaker = CodeBreaker::new(vec![1, 2, 3], 5);
+        assert!(breaker.guess(&[1, 2]).is_none());
+        assert!(breaker.attempts_left() == 5);
+    }
+
+    #[test]
+    fn solves_and_fails() {
+        let mut solved = CodeBreaker::new(vec![4, 2], 2);
+        solved.guess(&[4, 2]);
+        assert!(solved.is_solved() && !solved.is_failed());
+        assert!(solved.guess(&[4, 2]).is_none());
+
+        let mut failed = CodeBreaker::new(vec![4, 2], 1);
+        failed.guess(&[2, 4]);
+        assert!(failed.is_failed());
+    }
+
+    #[test]
+    fn aborting_costs_an_attempt() {
+        let mut breaker = CodeBreaker::new(vec![4, 2], 2);
+        breaker.abort();
+        assert!(breaker.attempts_left() == 1 && !breaker.is_failed());
+        breaker.abort();
+        assert!(breaker.is_failed());
+    }
+
+    #[test]
+    fn gear_makes_hacking_easier() {
+        assert!(difficulty(2, &[]) == (5, 8));
+        assert!(difficulty(2, &[ItemInstance::HitachiRam, ItemInstance::Simstim]) == (4, 10));
+        assert!(difficulty(0, &[ItemInstance::HitachiRam, ItemInstance::HitachiRam]).0 == 2);
+    }
+}
@@ -0,0 +1,324 @@
+use std::fmt;
+use std::fs::{ File };
+use std::io::{ Read };
+use std::sync::{ Arc };
+use std::collections::{ HashMap };
+
+use tcod::colors::{ self, Color };
+use components::item::{ ItemInstance };
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Requirement {
+    None,
+    // a keycard of at least the security level of the interactable
+    KeyCard,
+    KeyCardLevel(i32),
+    Item(ItemInstance),
+    // a fact the players learned in an earlier loop, like a door code
+    Knows(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateChange {
+    pub from: Option<usize>,
+    pub to: usize,
+    pub requirement: Requirement,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InteractableState {
+    pub name: String,
+    pub character: char,
+    pub color: Color,
+    pub blocking: bool,
+    pub sight_blocking: bool,
+    pub tags: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InteractableKind {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub states: Vec<InteractableState>,
+    pub interactions: Vec<StateChange>,
+    pub triggers: HashMap<String, Vec<StateChange>>,
+}
+
+impl InteractableKind {
+    fn new(id: &str) -> Self {
+        InteractableKind {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: "".into(),
+            states: vec![],
+            interactions: vec![],
+            triggers: HashMap::new(),
+        }
+    }
+
+    pub fn state_index(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|s| s.name == name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DefinitionError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DefinitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+pub fn error<T>(line: usize, message: String) -> Result<T, DefinitionError> {
+    Err(DefinitionError { line: line, message: message })
+}
+
+fn parse_color(name: &str) -> Option<Color> {
+    match name {
+        "white" => Some(colors::WHITE),
+        "black" => Some(colors::BLACK),
+        "grey" => Some(colors::GREY),
+        "light_grey" => Some(colors::LIGHT_GREY),
+        "dark_grey" => Some(colors::DARK_GREY),
+        "red" => Some(colors::RED),
+        "light_red" => Some(colors::LIGHT_RED),
+        "green" => Some(colors::GREEN),
+        "light_green" => Some(colors::LIGHT_GREEN),
+        "blue" => Some(colors::BLUE),
+        "light_blue" => Some(colors::LIGHT_BLUE),
+        "yellow" => Some(colors::YELLOW),
+        "orange" => Some(colors::ORANGE),
+        "gold" => Some(colors::GOLD),
+        "purple" => Some(colors::PURPLE),
+        "cyan" => Some(colors::CYAN),
+        "sepia" => Some(colors::SEPIA),
+        _ => None,
+    }
+}
+
+fn parse_state_ref(kind: &InteractableKind, name: &str, line: usize) -> Result<Option<usize>, DefinitionError> {
+    if name == "*" {
+        return Ok(None);
+    }
+    match kind.state_index(name) {
+        Some(index) => Ok(Some(index)),
+        None => error(line, format!("unknown state '{}'", name)),
+    }
+}
+
+fn parse_requirement(words: &[&str], line: usize) -> Result<Requirement, DefinitionError> {
+    match words {
+        [] => Ok(Requirement::None),
+        ["keycard"] => Ok(Requirement::KeyCard),
+        ["keycard", level] => match level.parse::<i32>() {
+            Ok(l) => Ok(Requirement::KeyCardLevel(l)),
+            Err(_) => error(line, format!("invalid keycard level '{}'", level)),
+        },
+        ["item", name] => match ItemInstance::from_name(name) {
+            Some(item) => Ok(Requirement::Item(item)),
+            None => error(line, format!("unknown item '{}'", name)),
+        },
+        ["knows", fact] => Ok(Requirement::Knows(fact.to_string())),
+        _ => error(line, format!("invalid requirement '{}'", words.join(" "))),
+    }
+}
+
+fn parse_state_change(kind: &InteractableKind, words: &[&str], line: usize) -> Result<StateChange, DefinitionError> {
+    if words.len() < 2 {
+        return error(line, "expected a source and a target state".into());
+    }
+    let from = parse_state_ref(kind, words[0], line)?;
+    let to = match parse_state_ref(kind, words[1], line)? {
+        Some(to) => to,
+        None => return error(line, "target state must not be '*'".into()),
+    };
+    let requirement = parse_requirement(&words[2..], line)?;
+    Ok(StateChange { from: from, to: to, requirement: requirement })
+}
+
+fn parse_state(words: &[&str], line: usize) -> Result<InteractableState, DefinitionError> {
+    if words.len() < 3 {
+        return error(line, "expected a name, a character and a color".into());
+    }
+    let mut chars = words[1].chars();
+    let character = match (chars.next(), chars.next()) {
+        (Some(c), None) => c,
+        _ => return error(line, format!("'{}' is not a single character", words[1])),
+    };
+    let color = match parse_color(words[2]) {
+        Some(c) => c,
+        None => return error(line, format!("unknown color '{}'", words[2])),
+    };
+    let mut state = InteractableState {
+        name: words[0].to_string(),
+        character: character,
+        color: color,
+        blocking: false,
+        sight_blocking: false,
+        tags: vec![],
+    };
+    for flag in &words[3..] {
+        match *flag {
+            "blocking" => state.blocking = true,
+            "sight_blocking" => state.sight_blocking = true,
+            tag => state.tags.push(tag.to_string()),
+        }
+    }
+    Ok(state)
+}
+
+pub struct InteractableRegistry {
+    kinds: HashMap<String, Arc<InteractableKind>>,
+}
+
+impl InteractableRegistry {
+    pub fn load(path: &str) -> Result<Self, DefinitionError> {
+        let mut content = String::new();
+        match File::open(path).and_then(|mut f| f.read_to_string(&mut content)) {
+            Ok(_) => InteractableRegistry::parse(&content),
+            Err(e) => error(0, format!("could not read {}: {}", path, e)),
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Self, DefinitionError> {
+        let mut kinds = HashMap::new();
+        let mut current: Option<InteractableKind> = None;
+
+        for (index, raw) in content.lines().enumerate() {
+            let line = index + 1;
+            let text = raw.split('#').next().unwrap().trim();
+            if text.is_empty() {
+                continue;
+            }
+            let words = text.split_whitespace().collect::<Vec<&str>>();
+            let rest = text[words[0].len()..].trim();
+
+            if words[0] == "kind" {
+                if current.is_some() {
+                    return error(line, "missing 'end' of previous kind".into());
+                }
+                if words.len() != 2 {
+                    return error(line, "expected a single kind id".into());
+                }
+                if kinds.contains_key(words[1]) {
+                    return error(line, format!("kind '{}' is defined twice", words[1]));
+                }
+                current = Some(InteractableKind::new(words[1]));
+                continue;
+            }
+
+            let finished = {
+                let kind = match current.as_mut() {
+                    Some(kind) => kind,
+                    None => return error(line, format!("'{}' outside of a kind", words[0])),
+                };
+                match words[0] {
+                    "name" => kind.name = rest.to_string(),
+                    "description" => kind.description = rest.to_string(),
+                    "state" => {
+                        let state = parse_state(&words[1..], line)?;
+                        if kind.state_index(&state.name).is_some() {
+                            return error(line, format!("state '{}' is defined twice", state.name));
+                        }
+                        kind.states.push(state);
+                    },
+                    "interact" => {
+                        let change = parse_state_change(kind, &words[1..], line)?;
+                        kind.interactions.push(change);
+                    },
+                    "on" => {
+                        if words.len() < 2 {
+                            return error(line, "expected an event name".into());
+                        }
+                        let change = parse_state_change(kind, &words[2..], line)?;
+                        kind.triggers.entry(words[1].to_string()).or_insert(vec![]).push(change);
+                    },
+                    "end" => {
+                        if kind.states.is_empty() {
+                            return error(line, format!("kind '{}' has no states", kind.id));
+                        }
+                    },
+                    other => return error(line, format!("unknown keyword '{}'", other)),
+                }
+                words[0] == "end"
+            };
+            if finished {
+                let kind = current.take().unwrap();
+                kinds.insert(kind.id.clone(), Arc::new(kind));
+            }
+        }
+
+        if let Some(kind) = current {
+            return error(content.lines().count(), format!("kind '{}' is missing 'end'", kind.id));
+        }
+        Ok(InteractableRegistry { kinds: kinds })
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<InteractableKind>> {
+        self.kinds.get(id).cloned()
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids = self.kinds.keys().cloned().collect::<Vec<String>>();
+        ids.sort();
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use interactables::{ InteractableRegistry, Requirement };
+    use components::item::{ ItemInstance };
+
+    const VENT: &str = "
+        # a vent that has to be unscrewed
+        kind vent
+          name Vent
+          description An air vent
+          state closed = grey blocking sight_blocking
+          state open O grey
+          interact closed open item FlickKnife
+          on lockdown * closed
+        end
+    ";
+
+    #[test]
+    fn parses_kind() {
+        let registry = InteractableRegistry::parse(VENT).unwrap();
+        let vent = registry.get("vent").unwrap();
+        assert!(vent.name == "Vent");
+        assert!(vent.states.len() == 2);
+        assert!(vent.states[0].blocking && vent.states[0].sight_blocking);
+        assert!(!vent.states[1].blocking);
+        assert!(vent.interactions[0].requirement == Requirement::Item(ItemInstance::FlickKnife));
+        assert!(vent.triggers["lockdown"][0].from.is_none());
+    }
+
+    #[test]
+    fn reports_line_of_error() {
+        let error = InteractableRegistry::parse("kind door\n  state closed D gold\n  interact closed open\nend")
+            .err().unwrap();
+        assert!(error.line == 3, "wrong line {}", error.line);
+        assert!(error.message == "unknown state 'open'");
+    }
+
+    #[test]
+    fn reports_unknown_color() {
+        let error = InteractableRegistry::parse("kind door\n  state closed D mauve\nend").err().unwrap();
+        assert!(error.line == 2);
+    }
+
+    #[test]
+    fn shipped_definitions_parse() {
+        let registry = InteractableRegistry::parse(include_str!("../data/interactables.txt")).unwrap();
+        for id in ["camera", "alarm_panel", "turret", "locker", "vent", "switch",
+                   "security_terminal", "mainframe_terminal"].iter() {
+            assert!(registry.get(id).is_some(), "{} is missing", id);
+        }
+    }
+}
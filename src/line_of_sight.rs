@@ -0,0 +1,166 @@
+use geometry::{ Line };
+
+fn distance(a: (i32, i32), b: (i32, i32)) -> f32 {
+    let (dx, dy) = ((b.0 - a.0) as f32, (b.1 - a.1) as f32);
+    (dx * dx + dy * dy).sqrt()
+}
+
+// every cell the segment between the two cell centers touches. Where it runs
+// exactly through a corner both cells beside the corner are touched, so the
+// cells are the same whichever end it starts from
+pub fn supercover(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (nx, ny) = ((to.0 - from.0).abs(), (to.1 - from.1).abs());
+    let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let mut p = from;
+    let mut cells = vec![p];
+    let (mut ix, mut iy) = (0, 0);
+    while ix < nx || iy < ny {
+        // which cell border the segment crosses next, compared without dividing
+        let decision = (1 + 2 * ix) * ny - (1 + 2 * iy) * nx;
+        if decision == 0 {
+            cells.push((p.0 + sx, p.1));
+            cells.push((p.0, p.1 + sy));
+            p = (p.0 + sx, p.1 + sy);
+            ix += 1;
+            iy += 1;
+        } else if decision < 0 {
+            p.0 += sx;
+            ix += 1;
+        } else {
+            p.1 += sy;
+            iy += 1;
+        }
+        cells.push(p);
+    }
+    cells
+}
+
+fn is_clear<F>(from: (i32, i32), to: (i32, i32), blocks: &F) -> bool where F: Fn((i32, i32)) -> bool {
+    Line::new(from.0, from.1, to.0, to.1).into_iter()
+        .filter(|p| *p != from && *p != to)
+        .all(|p| !blocks(p))
+}
+
+// sight runs along a line drawn from either end, so a sees b exactly when b sees a
+pub fn is_visible<F>(from: (i32, i32), to: (i32, i32), blocks: F) -> bool where F: Fn((i32, i32)) -> bool {
+    from == to || is_clear(from, to, &blocks) || is_clear(to, from, &blocks)
+}
+
+// the cells a shot from `from` aimed at `to` would fly through if nothing was
+// in the way, past the target up to its range
+fn flight(from: (i32, i32), to: (i32, i32), range: i32) -> Vec<(i32, i32)> {
+    if from == to {
+        return vec![];
+    }
+    let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs());
+    let k = range / steps + 1;
+    let far = (from.0 + (to.0 - from.0) * k, from.1 + (to.1 - from.1) * k);
+    supercover(from, far).into_iter()
+        .skip(1)
+        .take_while(|p| distance(from, *p) as i32 <= range)
+        .collect()
+}
+
+// the flight of a shot up to the first blocked cell. It never slips
+// diagonally between two cells of which one blocks
+pub fn projectile_path<F>(from: (i32, i32), to: (i32, i32), range: i32, blocks: F) -> Vec<(i32, i32)>
+    where F: Fn((i32, i32)) -> bool {
+    flight(from, to, range).into_iter()
+        .take_while(|p| !blocks(*p))
+        .collect()
+}
+
+// the blocked cell that stops a shot within its range
+pub fn impact<F>(from: (i32, i32), to: (i32, i32), range: i32, blocks: F) -> Option<(i32, i32)>
+    where F: Fn((i32, i32)) -> bool {
+    flight(from, to, range).into_iter().find(|p| blocks(*p))
+}
+
+#[cfg(test)]
+mod tests {
+    use line_of_sight::{ supercover, is_visible, projectile_path, impact };
+
+    const SIZE: i32 = 9;
+
+    // pillars, a wall with a gap and a diagonal of blocks
+    fn blocks(p: (i32, i32)) -> bool {
+        (p.0 % 3 == 1 && p.1 % 3 == 1) || (p.0 == 6 && p.1 != 4) || (p.0 == p.1 + 5)
+    }
+
+    fn cells() -> Vec<(i32, i32)> {
+        (0..SIZE).flat_map(|y| (0..SIZE).map(move |x| (x, y))).collect()
+    }
+
+    fn sorted(mut cells: Vec<(i32, i32)>) -> Vec<(i32, i32)> {
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn sight_is_symmetric() {
+        for a in cells() {
+            for b in cells() {
+                assert_eq!(is_visible(a, b, blocks), is_visible(b, a, blocks), "{:?} and {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn sight_stops_at_walls() {
+        // through the gap in the wall but not through the wall
+        assert!(is_visible((5, 5), (7, 3), blocks));
+        assert!(!is_visible((5, 3), (7, 3), blocks));
+        assert!(is_visible((3, 3), (3, 3), blocks));
+    }
+
+    #[test]
+    fn supercovers_are_symmetric_and_leak_free() {
+        for a in cells() {
+            for b in cells() {
+                let cover = supercover(a, b);
+                assert_eq!(cover.first(), Some(&a));
+                assert_eq!(cover.last(), Some(&b));
+                assert_eq!(sorted(cover.clone()), sorted(supercover(b, a)), "{:?} to {:?}", a, b);
+                for step in cover.windows(2) {
+                    let (p, q) = (step[0], step[1]);
+                    assert!((q.0 - p.0).abs() <= 1 && (q.1 - p.1).abs() <= 1);
+                    // a diagonal step is only taken past both cells beside it
+                    if p.0 != q.0 && p.1 != q.1 {
+                        assert!(cover.contains(&(q.0, p.1)) && cover.contains(&(p.0, q.1)));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn projectiles_fly_past_the_target_until_something_blocks() {
+        let open = |_: (i32, i32)| false;
+        let path = projectile_path((0, 0), (2, 0), 5, open);
+        assert_eq!(path, vec![(1, 0), (2, 0), (3, 0), (4, 0), (5, 0)]);
+
+        let wall = |p: (i32, i32)| p.0 == 3;
+        assert_eq!(projectile_path((0, 0), (5, 0), 10, wall), vec![(1, 0), (2, 0)]);
+        assert_eq!(impact((0, 0), (5, 0), 10, wall), Some((3, 0)));
+        assert_eq!(impact((0, 0), (5, 0), 2, wall), None);
+
+        // grazing a corner next to a block stops the shot
+        let corner = |p: (i32, i32)| p == (1, 0);
+        assert!(projectile_path((0, 0), (1, 1), 5, corner).is_empty());
+        assert!(projectile_path((0, 0), (0, 0), 5, open).is_empty());
+    }
+
+    #[test]
+    fn projectiles_follow_their_supercover() {
+        for a in cells() {
+            for b in cells().into_iter().filter(|b| *b != a) {
+                let path = projectile_path(a, b, 2 * SIZE, blocks);
+                assert!(path.iter().all(|p| !blocks(*p)));
+                let cover = supercover(a, b);
+                if cover.iter().skip(1).all(|p| !blocks(*p)) {
+                    assert!(path.contains(&b), "{:?} misses {:?}", a, b);
+                }
+            }
+        }
+    }
+}
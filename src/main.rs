@@ -13,19 +13,34 @@ mod tile_map;
 mod entity_map;
 mod ui;
 mod geometry;
+mod line_of_sight;
 mod systems;
 mod tower;
 mod maps;
 mod game_stats;
 mod game_state;
 mod event_log;
-
-use specs::{ World, Join, DispatcherBuilder };
+mod noise;
+mod interactables;
+mod hacking;
+mod memory;
+mod dialogue;
+mod factions;
+mod net;
+mod spectator;
+mod floor_plan;
+mod editor;
+
+use std::env;
+use std::collections::{ HashMap };
+
+use specs::{ World, Join, DispatcherBuilder, Entity, ReadStorage };
 
 use engine::state::{ State, Transition };
 use engine::input_handler::{ InputHandler };
 use engine::application::{ Application };
-use engine::tcod::{ Tcod };
+use engine::tcod::{ Tcod, VIEW_WIDTH, VIEW_HEIGHT };
+use engine::fov::{ Fovs };
 
 use tcod::colors::{ self };
 use tcod::input::{ KeyCode };
@@ -35,17 +50,31 @@ use maps::{ Map };
 use game_stats::{ GameStats };
 use game_state::{ GameState };
 use event_log::{ EventLog };
+use noise::{ Noises };
+use interactables::{ InteractableRegistry };
+use hacking::{ Hacking };
+use memory::{ LoopMemory };
+use dialogue::{ Dialogues, Conversation };
+use factions::{ FactionRelations };
+use floor_plan::{ FloorPlan };
+use net::{ Network };
+use spectator::{ Spectator };
+use editor::{ Editor };
+use net::connection::{ Host, Client };
 use ui::{ Ui };
 
 use components::appearance::{ Renderable, Layer0, Layer1 };
 use components::space::{ Position, Spawn, Viewport, Level };
-use components::player::{ Player, Fov, Equipment };
-use components::npc::{ Npc };
-use components::item::{ Item };
+use components::player::{ Player, Remote, Fov, Equipment, Orders };
+use components::npc::{ Npc, Dialogue };
+use components::item::{ Item, ItemInstance };
 use components::common::{ Active, InTurn, WaitForTurn, CharacterStats,
                           MoveToPosition, ItemStats, Description };
 use components::interaction::{ Interactable, Interaction };
 use components::inventory::{ Inventory };
+use components::stealth::{ Vision, Awareness, AlertState, Hearing, Sneaking };
+use components::schedule::{ Schedule };
+use components::faction::{ Faction };
 
 use geometry::{ Rect };
 
@@ -54,12 +83,105 @@ use systems::interaction_system::{ InteractionSystem };
 use systems::move_to_controller::{ MoveToController };
 use systems::round_scheduler::{ RoundScheduler };
 use systems::stats_updater::{ StatsUpdater };
+use systems::detection_system::{ DetectionSystem };
+use systems::hearing_system::{ HearingSystem };
+use systems::security_system::{ SecuritySystem };
+use systems::schedule_system::{ ScheduleSystem };
+use systems::lighting_system::{ LightingSystem };
+use systems::order_system::{ OrderSystem };
+use systems::network_system::{ HostSystem, ClientSystem };
 use systems::ui::{ UiUpdater };
 
 const TORCH_RADIUS: i32 = 10;
-struct Game;
+// how far the players see when standing in the dark
+const DARK_FOV_RADIUS: i32 = 3;
+// the cell and radius a fov was computed from
+type FovSource = ((i32, i32), i32);
+
+struct Game {
+    // all players see what any of them sees
+    shared_vision: bool,
+    // shows the surroundings of the inactive player in a corner
+    picture_in_picture: bool,
+    // the source each fov was last computed from
+    fov_sources: HashMap<usize, FovSource>,
+}
+
+const INSET_WIDTH: i32 = 24;
+const INSET_HEIGHT: i32 = 14;
+
+fn carries_radio(id: Entity, inventories: &ReadStorage<Inventory>, items: &ReadStorage<Item>) -> bool {
+    inventories.get(id).map_or(false, |inventory| inventory.items.iter()
+                               .any(|i| items.get(*i).map_or(false, |i| i.instance == ItemInstance::Radio)))
+}
 
 impl Game {
+    fn render_inset(&self, tcod: &mut Tcod, world: &World) {
+        let entities = world.entities();
+        let players = world.read::<Player>();
+        let actives = world.read::<Active>();
+        let fovs = world.read::<Fov>();
+        let renderables = world.read::<Renderable>();
+        let positions = world.read::<Position>();
+        let levels = world.read::<Level>();
+        let layer0 = world.read::<Layer0>();
+        let layer1 = world.read::<Layer1>();
+        let mut tower = world.write_resource::<Tower>();
+
+        let inactive = (&*entities, &players, &fovs, &positions, &levels).join()
+            .find(|&(id, _, _, _, _)| actives.get(id).is_none());
+        if let Some((_, _, fov, position, level)) = inactive {
+            let frame = Rect::new(VIEW_WIDTH - INSET_WIDTH - 1, VIEW_HEIGHT - INSET_HEIGHT - 1,
+                                  INSET_WIDTH, INSET_HEIGHT);
+            let mut inset = Viewport::inset((frame.left() + 1, frame.top() + 1), INSET_WIDTH - 2, INSET_HEIGHT - 2);
+            inset.center_at(*position);
+            tcod.render_frame(&frame, colors::BLACK, colors::LIGHT_GREY);
+
+            // the inset shows what the inactive player sees, then the view switches back
+            let shown = tcod.fovs.shown_fovs();
+            let own = fov.fov_map.values().cloned().collect::<Vec<usize>>();
+            tcod.fovs.show_fovs(&own);
+            tower.show(&own);
+            if let Some(maps) = tower.get(level) {
+                maps.draw(tcod, &inset, false);
+            }
+            for (_, renderable, p, l) in (&layer0, &renderables, &positions, &levels).join() {
+                if l == level {
+                    render_into_viewport(&inset, p, renderable, tcod);
+                }
+            }
+            for (_, renderable, p, l) in (&layer1, &renderables, &positions, &levels).join() {
+                if l == level {
+                    render_into_viewport(&inset, p, renderable, tcod);
+                }
+            }
+            tcod.fovs.show_fovs(&shown);
+            tower.show(&shown);
+        }
+    }
+
+    // without shared vision only the active player's fov is shown, plus the fovs
+    // of everybody the active player keeps in touch with by radio
+    fn update_shown_fovs(&self, tcod: &mut Tcod, world: &mut World) {
+        let entities = world.entities();
+        let players = world.read::<Player>();
+        let actives = world.read::<Active>();
+        let fovs = world.read::<Fov>();
+        let inventories = world.read::<Inventory>();
+        let items = world.read::<Item>();
+        let mut tower = world.write_resource::<Tower>();
+
+        let active = (&*entities, &players, &actives).join().next().map(|(id, _, _)| id);
+        let on_air = active.map_or(false, |a| carries_radio(a, &inventories, &items));
+        let mut shown = vec![];
+        for (id, _, fov) in (&*entities, &players, &fovs).join() {
+            if self.shared_vision || Some(id) == active || (on_air && carries_radio(id, &inventories, &items)) {
+                shown.extend(fov.fov_map.values().cloned());
+            }
+        }
+        tcod.fovs.show_fovs(&shown);
+        tower.show(&shown);
+    }
 
     fn reset_world(&mut self, tcod: &mut Tcod, world: &mut World) {
         let entities = world.entities();
@@ -67,10 +189,18 @@ impl Game {
         let mut positions = world.write::<Position>();
         let mut levels = world.write::<Level>();
         let mut inventories = world.write::<Inventory>();
+        let mut equipments = world.write::<Equipment>();
         let mut char_stats = world.write::<CharacterStats>();
         let mut interactables = world.write::<Interactable>();
+        let mut renderables = world.write::<Renderable>();
+        let mut visions = world.write::<Vision>();
+        let mut awareness = world.write::<Awareness>();
+        let mut schedules = world.write::<Schedule>();
+        let mut orders = world.write::<Orders>();
         let mut tower = world.write_resource::<Tower>();
         let mut state = world.write_resource::<GameState>();
+        let mut noises = world.write_resource::<Noises>();
+        let mut relations = world.write_resource::<FactionRelations>();
 
         let mut in_turns = world.write::<InTurn>();
         let mut waits = world.write::<WaitForTurn>();
@@ -82,6 +212,8 @@ impl Game {
         let items = world.read::<Item>();
 
         state.reset();
+        noises.clear();
+        relations.reset();
 
         in_turns.clear();
         waits.clear();
@@ -89,28 +221,48 @@ impl Game {
 
         tower.clear();
         for (id, spawn) in (&*entities, &spawns).join() {
+            // items picked up, thrown or handed over during the loop return to where they started
+            for inventory in (&mut inventories).join() {
+                inventory.items.retain(|i| *i != id);
+            }
             if let Some(loc) = spawn.location {
                 positions.insert(id, Position { x: loc.0, y: loc.1 });
                 levels.insert(id, loc.2);
             } else if let Some(owner) = spawn.owner {
-                if positions.remove(id).is_some() {
-                    levels.remove(id);
-                    if let Some(inventory) = inventories.get_mut(owner) {
-                        inventory.push(id);
-                    }
+                positions.remove(id);
+                levels.remove(id);
+                if let Some(inventory) = inventories.get_mut(owner) {
+                    inventory.push(id);
                 }
             }
         }
-        for (_, interactable) in (&*entities, &mut interactables).join() {
+        for (inventory, equipment) in (&inventories, &mut equipments).join() {
+            let carried = |item: Option<Entity>| item.filter(|i| inventory.items.contains(i));
+            equipment.active_item = carried(equipment.active_item);
+            equipment.passive_item = carried(equipment.passive_item);
+            equipment.clothing = carried(equipment.clothing);
+        }
+        for (id, interactable) in (&*entities, &mut interactables).join() {
             interactable.reset();
+            renderables.insert(id, interactable.get_renderable());
+        }
+        for (vision, awareness) in (&mut visions, &mut awareness).join() {
+            vision.reset();
+            awareness.reset();
+        }
+        for schedule in (&mut schedules).join() {
+            schedule.reset();
+        }
+        for orders in (&mut orders).join() {
+            orders.reset();
         }
 
         for (id, interactable, pos, level) in (&*entities, &mut interactables, &mut positions, &mut levels).join() {
             let p = (pos.x as i32, pos.y as i32);
             if let Some(maps) = tower.get_mut(level) {
                 maps.push(Map::Character, &id, p);
-                maps.set_blocking(Map::Character, &id, p, interactable.is_blocking());
-                maps.set_sight_blocking(Map::Character, &id, p, interactable.is_sight_blocking());
+                maps.set_blocking(Map::Character, &id, interactable.is_blocking());
+                maps.set_sight_blocking(Map::Character, &id, interactable.is_sight_blocking());
             }
         }
 
@@ -135,24 +287,75 @@ impl Game {
     }
 }
 
+fn fov_radius(light: f32) -> i32 {
+    DARK_FOV_RADIUS + ((TORCH_RADIUS - DARK_FOV_RADIUS) as f32 * light.min(1.0)).round() as i32
+}
+
+// only the floors somebody looks at are touched, and only when the view changed
+fn discover(fovs: &mut Fovs, tower: &mut Tower, sources: &mut HashMap<usize, FovSource>,
+            viewers: &[(usize, Level, (i32, i32))]) {
+    for &(index, level, p) in viewers {
+        let maps = tower.get_mut(&level).unwrap();
+        let source = (p, fov_radius(maps.light_level(p)));
+        match sources.get(&index) {
+            Some(previous) if *previous == source => continue,
+            Some(_) => (),
+            None => fovs.update_fov(index, maps),
+        }
+        fovs.compute_fov(index, p, source.1);
+        maps.discover(index, &fovs.visible_cells(index, p, source.1));
+        sources.insert(index, source);
+    }
+}
+
 fn render_into_viewport(viewport: &Viewport, position: &Position, renderable: &Renderable, tcod: &mut Tcod) {
     let p = (position.x as i32, position.y as i32);
-    if viewport.visible(p) && tcod.is_in_fov(p) {
+    if viewport.visible(p) && tcod.fovs.is_in_fov(p) {
         let pos = viewport.transform(p);
         tcod.render_character(pos, renderable.color, renderable.character);
     }
 }
 
+fn render_alert_indicator(viewport: &Viewport, position: &Position, awareness: &Awareness, tcod: &mut Tcod) {
+    let p = (position.x as i32, position.y as i32);
+    let above = (p.0, p.1 - 1);
+    if viewport.visible(above) && tcod.fovs.is_in_fov(p) {
+        let pos = viewport.transform(above);
+        match awareness.state {
+            AlertState::Suspicious => tcod.render_character(pos, colors::YELLOW, '?'),
+            AlertState::Alerted => tcod.render_character(pos, colors::RED, '!'),
+            AlertState::Unaware => (),
+        }
+    }
+}
+
 impl State for Game {
     fn start(&mut self, tcod: &mut Tcod, world: &mut World) {
         world.add_resource::<InputHandler>(InputHandler::default());
         world.add_resource::<GameStats>(GameStats::default());
         world.add_resource::<GameState>(GameState::default());
         world.add_resource::<EventLog>(EventLog::default());
-        world.add_resource::<Viewport>(Viewport::new(15, 15, 80, 40));
+        world.add_resource::<Noises>(Noises::default());
+        world.add_resource::<LoopMemory>(LoopMemory::default());
+        world.add_resource::<FactionRelations>(FactionRelations::default());
+        world.add_resource::<Viewport>(Viewport::new(15, 15, VIEW_WIDTH, VIEW_HEIGHT));
+        match InteractableRegistry::load("data/interactables.txt") {
+            Ok(registry) => world.add_resource::<InteractableRegistry>(registry),
+            Err(e) => panic!("could not load interactables: {}", e),
+        }
+        match Dialogues::load("data/dialogues.txt") {
+            Ok(dialogues) => world.add_resource::<Dialogues>(dialogues),
+            Err(e) => panic!("could not load dialogues: {}", e),
+        }
 
-        let mut tower = Tower::new(&[Level::Tower(0)]);
-        tower.build(tcod, world);
+        let floor = match FloorPlan::load("data/tower0.map", &world.read_resource::<InteractableRegistry>()) {
+            Ok(plan) => plan,
+            Err(e) => panic!("could not load the tower: {}", e),
+        };
+        let mut tower = Tower::new();
+        if let Err(e) = tower.build(&[(Level::Tower(0), floor)], tcod, world) {
+            panic!("could not spawn the tower: {}", e);
+        }
         world.add_resource::<Tower>(tower);
 
         let mut ui = Ui::new();
@@ -173,6 +376,16 @@ impl State for Game {
             return Transition::Exit
         } else if input.is_key_pressed(KeyCode::F5) {
             tcod.switch_fullscreen();
+        } else if input.is_char_pressed('v') {
+            self.shared_vision = !self.shared_vision;
+        } else if input.is_char_pressed('i') {
+            self.picture_in_picture = !self.picture_in_picture;
+        } else if input.is_key_pressed(KeyCode::F2) {
+            input.consume();
+            return Transition::Push(Box::new(Spectator::new()));
+        } else if input.is_key_pressed(KeyCode::F3) {
+            input.consume();
+            return Transition::Push(Box::new(Editor::new("data/tower0.map")));
         }
         Transition::None
     }
@@ -182,31 +395,41 @@ impl State for Game {
         {
             let input = world.read_resource::<InputHandler>();
             let stats = world.read_resource::<GameStats>();
-            do_reset = input.is_key_pressed(KeyCode::Backspace) || stats.time_left() < 0;
+            // a client leaves resetting to the host
+            let is_client = world.read_resource::<Network>().is_client();
+            do_reset = !is_client && (input.is_key_pressed(KeyCode::Backspace) || stats.is_loop_over());
         }
         if do_reset {
             self.reset_world(tcod, world);
         }
 
-        let state = world.read_resource::<GameState>();
+        let hacking = world.write_resource::<GameState>().hacking.take();
+        if let Some((terminal, actor)) = hacking {
+            return Transition::Push(Box::new(Hacking::new(terminal, actor)));
+        }
+        let conversation = world.write_resource::<GameState>().conversation.take();
+        if let Some((npc, actor)) = conversation {
+            return Transition::Push(Box::new(Conversation::new(npc, actor)));
+        }
+
+        self.update_shown_fovs(tcod, world);
+
+        let mut state = world.write_resource::<GameState>();
         let fovs = world.read::<Fov>();
         let positions = world.read::<Position>();
         let levels = world.read::<Level>();
+        let mut tower = world.write_resource::<Tower>();
 
+        // changed walls or doors invalidate every fov
         if state.fov_needs_update {
-            let tower = world.read_resource::<Tower>();
-            for (fov, level) in (&fovs, &levels).join() {
-                tcod.update_fov(*fov.fov_map.get(level).unwrap(),
-                                tower.get(level).unwrap());
-            }
-        }
-        for (fov, position, level) in (&fovs, &positions, &levels).join() {
-            tcod.compute_fov(*fov.fov_map.get(level).unwrap(),
-                             (position.x as i32, position.y as i32), TORCH_RADIUS);
+            self.fov_sources.clear();
+            state.fov_needs_update = false;
         }
-
-        let mut tower = world.write_resource::<Tower>();
-        tower.update(tcod);
+        let viewers = (&fovs, &positions, &levels).join()
+            .map(|(fov, position, level)| (*fov.fov_map.get(level).unwrap(), *level,
+                                           (position.x as i32, position.y as i32)))
+            .collect::<Vec<(usize, Level, (i32, i32))>>();
+        discover(&mut tcod.fovs, &mut tower, &mut self.fov_sources, &viewers);
 
         Transition::None
     }
@@ -218,6 +441,7 @@ impl State for Game {
         let layer1 = world.read::<Layer1>();
         let actives = world.read::<Active>();
         let levels = world.read::<Level>();
+        let awareness = world.read::<Awareness>();
         let tower = world.read_resource::<Tower>();
         let ui = world.read_resource::<Ui>();
         let viewport = world.read_resource::<Viewport>();
@@ -226,7 +450,7 @@ impl State for Game {
 
         {
             if let Some((level, _)) = (&levels, &actives).join().next() {
-                tower.draw(level, tcod, &viewport);
+                tower.draw(level, tcod, &viewport, false);
             }
             ui.draw(tcod);
 
@@ -236,6 +460,13 @@ impl State for Game {
             for (_, renderable, position) in (&layer1, &renderables, &positions).join() {
                render_into_viewport(&viewport, position, renderable, tcod);
             }
+            for (awareness, position) in (&awareness, &positions).join() {
+                render_alert_indicator(&viewport, position, awareness, tcod);
+            }
+        }
+
+        if self.picture_in_picture {
+            self.render_inset(tcod, world);
         }
 
         tcod.flush();
@@ -245,8 +476,10 @@ impl State for Game {
 fn main() {
     let mut world = World::new();
     world.register::<Player>();
+    world.register::<Remote>();
     world.register::<Level>();
     world.register::<Npc>();
+    world.register::<Dialogue>();
     world.register::<Spawn>();
     world.register::<Item>();
     world.register::<Fov>();
@@ -261,13 +494,133 @@ fn main() {
     world.register::<CharacterStats>();
     world.register::<ItemStats>();
     world.register::<MoveToPosition>();
+    world.register::<Vision>();
+    world.register::<Awareness>();
+    world.register::<Hearing>();
+    world.register::<Sneaking>();
+    world.register::<Schedule>();
+    world.register::<Faction>();
+    world.register::<Orders>();
+
+    // tlb host <address> or tlb join <address> for two player co-op
+    let args: Vec<String> = env::args().collect();
+    let network = match args.get(1).map(|a| a.as_str()) {
+        Some("host") if args.len() > 2 => match Host::bind(args[2].as_str()) {
+            Ok(host) => Network::Host(host),
+            Err(e) => panic!("could not host on {}: {}", args[2], e),
+        },
+        Some("join") if args.len() > 2 => match Client::connect(args[2].as_str()) {
+            Ok(client) => Network::Client(client),
+            Err(e) => panic!("could not join {}: {}", args[2], e),
+        },
+        _ => Network::Offline,
+    };
+    let is_client = network.is_client();
+    world.add_resource::<Network>(network);
+
+    let dispatcher = if is_client {
+        // the host simulates, a client only mirrors it
+        DispatcherBuilder::new()
+            .add(ClientSystem, "client_system", &[])
+            .add(LightingSystem::new(), "lighting_system", &[])
+            .add(UiUpdater, "ui_updater", &[])
+    } else {
+        DispatcherBuilder::new()
+            .add(PlayerController, "player_controller_system", &[])
+            .add(HostSystem::new(), "host_system", &[])
+            .add(OrderSystem, "order_system", &[])
+            .add(ScheduleSystem, "schedule_system", &[])
+            .add(MoveToController, "move_to_controller", &[])
+            .add(LightingSystem::new(), "lighting_system", &[])
+            .add(DetectionSystem, "detection_system", &[])
+            .add(HearingSystem, "hearing_system", &[])
+            .add(SecuritySystem::new(), "security_system", &[])
+            .add(InteractionSystem, "interaction_system", &[])
+            .add(RoundScheduler, "round_scheduler", &[])
+            .add(StatsUpdater, "stats_updater", &[])
+            .add(UiUpdater, "ui_updater", &[])
+    };
+    Application::new(Game { shared_vision: true, picture_in_picture: false, fov_sources: HashMap::new() }, world, dispatcher.build()).run();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{ HashMap };
+    use std::time::{ Instant };
+
+    use engine::fov::{ Fovs };
+    use components::space::{ Level };
+    use floor_plan::{ FloorPlan };
+    use tower::{ Tower };
+    use { discover, fov_radius };
+
+    const FLOORS: i32 = 11;
+
+    // two players with a fov on every floor of the tower, as Tower::build makes them
+    fn tower(fovs: &mut Fovs) -> (Tower, Vec<HashMap<Level, usize>>) {
+        let plan = FloorPlan::parse(include_str!("../data/tower0.map")).unwrap();
+        let mut tower = Tower::new();
+        for floor in 0..FLOORS {
+            tower.add_floor(Level::Tower(floor), &plan);
+        }
+        let players = (0..2).map(|_| tower.create_fov(fovs)).collect();
+        (tower, players)
+    }
+
+    // one player walks through the big room, the other one stands still
+    fn viewers(players: &[HashMap<Level, usize>], frame: i32) -> Vec<(usize, Level, (i32, i32))> {
+        let level = Level::Tower(0);
+        vec![(players[0][&level], level, (22 + frame % 10, 22)),
+             (players[1][&level], level, (22, 24))]
+    }
+
+    // cargo test --release -- --ignored --nocapture discovery_frame_time
+    #[test]
+    #[ignore]
+    fn discovery_frame_time() {
+        let frames = 100;
+
+        // every fov computed each frame, then every cell of every floor asked against the fovs it tracks
+        let mut fovs = Fovs::new();
+        let (mut full, players) = tower(&mut fovs);
+        let start = Instant::now();
+        for frame in 0..frames {
+            for (index, level, p) in viewers(&players, frame) {
+                let maps = full.get(&level).unwrap();
+                if frame == 0 {
+                    fovs.update_fov(index, maps);
+                }
+                fovs.compute_fov(index, p, fov_radius(maps.light_level(p)));
+            }
+            for level in full.levels() {
+                let maps = full.get_mut(&level).unwrap();
+                for fov in players.iter() {
+                    let index = fov[&level];
+                    let mut cells = vec![];
+                    for y in 0..maps.height() {
+                        for x in 0..maps.width() {
+                            if fovs.is_in_fov_map(index, (x, y)) {
+                                cells.push((x, y));
+                            }
+                        }
+                    }
+                    maps.discover(index, &cells);
+                }
+            }
+        }
+        let full_time = start.elapsed() / frames as u32;
+
+        // what Game::update does
+        let mut fovs = Fovs::new();
+        let (mut cached, players) = tower(&mut fovs);
+        let mut sources = HashMap::new();
+        let start = Instant::now();
+        for frame in 0..frames {
+            discover(&mut fovs, &mut cached, &mut sources, &viewers(&players, frame));
+        }
+        let cached_time = start.elapsed() / frames as u32;
 
-    let dispatcher = DispatcherBuilder::new()
-        .add(PlayerController, "player_controller_system", &[])
-        .add(MoveToController, "move_to_controller", &[])
-        .add(InteractionSystem, "interaction_system", &[])
-        .add(RoundScheduler, "round_scheduler", &[])
-        .add(StatsUpdater, "stats_updater", &[])
-        .add(UiUpdater, "ui_updater", &[]);
-    Application::new(Game, world, dispatcher.build()).run();
+        println!("{} floors: full scan {:?} per frame, cached fovs {:?} per frame",
+                 FLOORS, full_time, cached_time);
+    }
 }
@@ -1,20 +1,20 @@
 use specs::{ Entity };
-use std::collections::VecDeque;
+use std::collections::{ VecDeque, HashMap, HashSet };
 use engine::tcod::{ Tcod };
 use tcod::pathfinding::{ AStar };
-use tile_map::{ TileMap };
-use geometry::{ Shape, Ray, Line, Rect };
+use tile_map::{ TileMap, DoorState, WallDamage };
+use geometry::{ Shape, Line, Rect };
+use line_of_sight;
 use entity_map::{ EntityMap, Entry };
+use floor_plan::{ FloorPlan };
 
 use components::space::{ Viewport, Position };
+use components::progress::{ AreaInstance };
 
-const SCREEN_WIDTH: i32 = 80;
-const SCREEN_HEIGHT: i32 = 50;
-
-const MAP_WIDTH: i32 = 80;
-const MAP_HEIGHT: i32 = 43;
-const MAP_Y: i32 = SCREEN_HEIGHT - MAP_HEIGHT;
+const WALL_DAMPING: f32 = 6.0;
+const DOOR_DAMPING: f32 = 3.0;
 
+#[derive(Copy, Clone, PartialEq)]
 pub enum Map {
     Item,
     Character,
@@ -24,20 +24,42 @@ pub struct Maps {
     characters: EntityMap,
     items: EntityMap,
     tiles: TileMap,
+    areas: Vec<(Rect, AreaInstance)>,
 }
 
 impl Maps {
-    pub fn new() -> Self {
+    pub fn new(width: i32, height: i32) -> Self {
         Maps {
-            characters: EntityMap::new(),
-            items: EntityMap::new(),
-            tiles: TileMap::new(),
+            characters: EntityMap::new(width, height),
+            items: EntityMap::new(width, height),
+            tiles: TileMap::new(width, height),
+            areas: vec![],
+        }
+    }
+
+    // the tiles and areas of a floor plan, spawning is up to the tower
+    pub fn from_plan(plan: &FloorPlan) -> Self {
+        let mut maps = Maps::new(plan.size.0, plan.size.1);
+        for y in 0..plan.size.1 {
+            for x in 0..plan.size.0 {
+                maps.tiles.set_spec((x, y), plan.tile((x, y)));
+            }
         }
+        maps.areas = plan.areas.clone();
+        maps
     }
 
-    pub fn screen_to_map(&self, pos: (i32, i32)) -> Option<(i32, i32)> {
-        let p = (pos.0, pos.1 - MAP_Y);
-        if p.0 >= 0 && p.0 < MAP_WIDTH && p.1 >= 0 && p.1 < MAP_HEIGHT {
+    pub fn width(&self) -> i32 {
+        self.tiles.width()
+    }
+
+    pub fn height(&self) -> i32 {
+        self.tiles.height()
+    }
+
+    // the position if it lies on the map
+    pub fn within(&self, p: (i32, i32)) -> Option<(i32, i32)> {
+        if p.0 >= 0 && p.0 < self.width() && p.1 >= 0 && p.1 < self.height() {
             Some(p)
         } else {
             None
@@ -48,50 +70,105 @@ impl Maps {
                      from: (i32, i32), to: (i32, i32)) -> VecDeque<Position> {
         let callback = |_start: (i32,i32), end:(i32,i32) | if
             self.is_not_planable(entity, end) { 0.0 } else { 1.0 };
-        let mut astar = AStar::new_from_callback(MAP_WIDTH, MAP_HEIGHT, callback, 0.0);
+        let mut astar = AStar::new_from_callback(self.width(), self.height(), callback, 0.0);
         astar.find(from, to);
         astar.walk()
             .map(|p| Position { x: p.0 as f32 + 0.5, y: p.1 as f32 + 0.5 })
             .collect::<VecDeque<Position>>()
     }
 
-    pub fn draw_ray(&self, from: (i32, i32), to: (i32, i32), length: i32) -> VecDeque<Position> {
-        let p0 = Position { x: from.0 as f32 + 0.5, y: from.1 as f32 + 0.5 };
-        Ray::new(from, to).into_iter()
-            .skip(1)
-            .take_while(|p| !self.is_projectile_blocking(*p))
+    // a path to the cell next to another character
+    pub fn find_path_to_character(&self, entity: &Entity,
+                                  from: (i32, i32), to: (i32, i32)) -> VecDeque<Position> {
+        let callback = |_start: (i32,i32), end:(i32,i32) | if
+            end != to && self.is_not_planable(entity, end) { 0.0 } else { 1.0 };
+        let mut astar = AStar::new_from_callback(self.width(), self.height(), callback, 0.0);
+        astar.find(from, to);
+        let mut path = astar.walk()
+            .map(|p| Position { x: p.0 as f32 + 0.5, y: p.1 as f32 + 0.5 })
+            .collect::<VecDeque<Position>>();
+        path.pop_back();
+        path
+    }
+
+    pub fn find_npc_path(&self, entity: &Entity,
+                         from: (i32, i32), to: (i32, i32)) -> VecDeque<Position> {
+        // npcs know their floor, so they do not care about discovered tiles. The target itself
+        // may be occupied, they will just stop next to it. Doors they can open are in the way
+        // for a moment only.
+        let callback = |_start: (i32,i32), end:(i32,i32) | if end == to {
+            1.0
+        } else if self.tiles.door(end) == Some(DoorState::Closed) && !self.is_occupied(entity, end) {
+            2.0
+        } else if self.is_impassable(entity, end) { 0.0 } else { 1.0 };
+        let mut astar = AStar::new_from_callback(self.width(), self.height(), callback, 0.0);
+        astar.find(from, to);
+        astar.walk()
             .map(|p| Position { x: p.0 as f32 + 0.5, y: p.1 as f32 + 0.5 })
-            .take_while(|p| (p0-*p).length() as i32 <= length)
             .collect::<VecDeque<Position>>()
     }
 
-    pub fn collect_characters_with_ray(&self,
-                            from: (i32, i32), to: (i32, i32), length: i32) -> VecDeque<Entity> {
-        let p0 = Position { x: from.0 as f32 + 0.5, y: from.1 as f32 + 0.5 };
-        Ray::new(from, to).into_iter()
-            .skip(1)
-            .take_while(|p| !self.is_projectile_blocking(*p))
-            .take_while(|p| {
-                let ps = Position { x: p.0 as f32 + 0.5, y: p.1 as f32 + 0.5 };
-                (p0-ps).length() as i32 <= length
-            })
-            .flat_map(|p| self.characters.get(p))
-            .map(|e| e.0)
-            .collect::<VecDeque<Entity>>()
+    pub fn propagate_noise(&self, origin: (i32, i32), loudness: f32) -> HashMap<(i32, i32), f32> {
+        let mut heard = HashMap::new();
+        let mut open = VecDeque::new();
+        heard.insert(origin, loudness);
+        open.push_back(origin);
+        while let Some(p) = open.pop_front() {
+            let current = heard[&p];
+            for &(dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)].iter() {
+                let next = (p.0 + dx, p.1 + dy);
+                if let Some(damping) = self.noise_damping(next) {
+                    let l = current - 1.0 - damping;
+                    if l > 0.0 && heard.get(&next).map_or(true, |old| *old < l) {
+                        heard.insert(next, l);
+                        open.push_back(next);
+                    }
+                }
+            }
+        }
+        heard
+    }
+
+    fn noise_damping(&self, p: (i32, i32)) -> Option<f32> {
+        if self.tiles.is_wall(p) {
+            Some(WALL_DAMPING)
+        } else if self.tiles.door(p).map_or(false, |d| d.is_blocking()) {
+            Some(DOOR_DAMPING)
+        } else if self.tiles.is_blocking(p) {
+            None
+        } else if self.characters.get(p).iter().any(|e| e.sight_blocking) {
+            Some(DOOR_DAMPING)
+        } else {
+            Some(0.0)
+        }
+    }
+
+    // the cells a shot flies through, the same for aiming and for hitting
+    pub fn projectile_path(&self, from: (i32, i32), to: (i32, i32), range: i32) -> Vec<(i32, i32)> {
+        line_of_sight::projectile_path(from, to, range, |p| self.is_projectile_blocking(p))
+    }
+
+    // the wall or door that stops the shot, if it comes that far
+    pub fn impact(&self, from: (i32, i32), to: (i32, i32), range: i32) -> Option<(i32, i32)> {
+        line_of_sight::impact(from, to, range, |p| self.is_projectile_blocking(p))
+    }
+
+    pub fn collect_characters_on_path(&self, path: &[(i32, i32)]) -> Vec<Entity> {
+        path.iter()
+            .flat_map(|p| self.characters.get(*p).iter().map(|e| e.entity))
+            .collect()
     }
 
     pub fn collect_characters_with_shape<T>(&self, shape: T) -> Vec<Entity> where T: Shape {
-        shape.into_iter()
-            .flat_map(|p| self.characters.get(p))
-            .map(|e| e.0)
-            .collect::<Vec<Entity>>()
+        self.characters.in_shape(shape)
+    }
+
+    pub fn collect_characters_in_radius(&self, center: (i32, i32), radius: f32) -> Vec<Entity> {
+        self.characters.within_radius(center, radius)
     }
 
     pub fn collect_items_with_shape<T>(&self, shape: T) -> Vec<Entity> where T: Shape {
-        shape.into_iter()
-            .flat_map(|p| self.items.get(p))
-            .map(|e| e.0)
-            .collect::<Vec<Entity>>()
+        self.items.in_shape(shape)
     }
 
     pub fn is_blocking(&self, p: (i32, i32)) -> bool {
@@ -103,33 +180,222 @@ impl Maps {
     }
 
     pub fn is_not_planable(&self, entity: &Entity, p: (i32, i32)) -> bool {
-        !self.tiles.is_discovered(p) || self.tiles.is_blocking(p)
-            || self.characters.get(p).iter().any(|e| e.0 != *entity && e.1 )
+        !self.tiles.is_discovered(p) || self.is_impassable(entity, p)
+    }
+
+    pub fn is_impassable(&self, entity: &Entity, p: (i32, i32)) -> bool {
+        self.tiles.is_blocking(p) || self.is_occupied(entity, p)
+    }
+
+    fn is_occupied(&self, entity: &Entity, p: (i32, i32)) -> bool {
+        self.characters.get(p).iter().any(|e| e.entity != *entity && e.blocking)
+    }
+
+    pub fn door(&self, p: (i32, i32)) -> Option<DoorState> {
+        self.tiles.door(p)
+    }
+
+    pub fn doors(&self) -> Vec<((i32, i32), DoorState)> {
+        let mut doors = vec![];
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                if let Some(state) = self.tiles.door((x, y)) {
+                    doors.push(((x, y), state));
+                }
+            }
+        }
+        doors
+    }
+
+    pub fn set_door(&mut self, p: (i32, i32), state: DoorState) {
+        self.tiles.set_door(p, state);
+    }
+
+    // a bullet hole in a wall or door. True if it opened a sight line
+    pub fn shoot(&mut self, p: (i32, i32), damage: f32) -> bool {
+        self.tiles.damage(p, damage, false)
+    }
+
+    // wears down the walls and doors in the radius. True if any of them broke
+    pub fn blast(&mut self, center: (i32, i32), radius: i32, damage: f32) -> bool {
+        let mut changed = false;
+        for p in Rect::new(center.0 - radius, center.1 - radius, 2 * radius + 1, 2 * radius + 1) {
+            let (dx, dy) = ((p.0 - center.0) as f32, (p.1 - center.1) as f32);
+            if dx * dx + dy * dy <= (radius * radius) as f32 {
+                changed |= self.tiles.damage(p, damage, true);
+            }
+        }
+        changed
+    }
+
+    pub fn damaged(&self) -> Vec<((i32, i32), WallDamage)> {
+        self.tiles.damaged()
+    }
+
+    pub fn set_wall_damage(&mut self, p: (i32, i32), damage: WallDamage) {
+        self.tiles.set_wall_damage(p, damage);
+    }
+
+    // the tiles as they were at the start of the loop
+    pub fn restore(&mut self) {
+        self.tiles.restore();
+    }
+
+    // closed doors open, locked ones stay shut. True if the door opened
+    pub fn open_door(&mut self, p: (i32, i32)) -> bool {
+        if self.tiles.door(p) == Some(DoorState::Closed) {
+            self.tiles.set_door(p, DoorState::Open);
+            true
+        } else {
+            false
+        }
+    }
+
+    // a keycard of at least the level of a locked door unlocks and opens it
+    pub fn unlock_door(&mut self, p: (i32, i32), keycard: i32) -> bool {
+        match self.tiles.door(p) {
+            Some(DoorState::Locked(level)) if keycard >= level => {
+                self.tiles.set_door(p, DoorState::Open);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    // opens every locked door of the floor. True if there was any
+    pub fn unlock_doors(&mut self) -> bool {
+        let locked: Vec<(i32, i32)> = self.doors().into_iter()
+            .filter(|&(_, state)| state.is_locked())
+            .map(|(p, _)| p)
+            .collect();
+        for p in locked.iter() {
+            self.tiles.set_door(*p, DoorState::Open);
+        }
+        !locked.is_empty()
+    }
+
+    // the doors locked at the start of the loop lock again unless they are broken
+    // or somebody stands in them. True if any of them changed
+    pub fn lock_doors(&mut self) -> bool {
+        let mut changed = false;
+        for (p, state) in self.doors() {
+            match self.tiles.initial_door(p) {
+                Some(initial) if initial.is_locked() && state != initial && state != DoorState::Broken
+                    && self.characters.is_empty(p) => {
+                    self.tiles.set_door(p, initial);
+                    changed = true;
+                },
+                _ => (),
+            }
+        }
+        changed
+    }
+
+    // opens or closes a door, nothing closes on a character. True if it changed
+    pub fn toggle_door(&mut self, p: (i32, i32)) -> bool {
+        match self.tiles.door(p) {
+            Some(DoorState::Open) if self.characters.is_empty(p) => {
+                self.tiles.set_door(p, DoorState::Closed);
+                true
+            },
+            Some(DoorState::Closed) => self.open_door(p),
+            _ => false,
+        }
     }
 
     pub fn is_sight_blocking(&self, p: (i32, i32)) -> bool {
         self.tiles.is_sight_blocking(p)
-            || self.characters.get(p).iter().any(|e| e.2 )
+            || self.characters.get(p).iter().any(|e| e.sight_blocking)
     }
 
-    pub fn build(&mut self) {
-        self.tiles.create_room(&Rect::new(20, 20, 15, 15));
-        self.tiles.create_anti_room(&Rect::new(25, 25, 5, 5));
-        self.tiles.draw_line(&Line::new(25, 22, 25, 27));
+    pub fn is_in_line_of_sight(&self, from: (i32, i32), to: (i32, i32)) -> bool {
+        line_of_sight::is_visible(from, to, |p| self.is_sight_blocking(p))
+    }
+
+    pub fn revision(&self) -> u32 {
+        self.tiles.revision()
+    }
+
+    pub fn light_level(&self, p: (i32, i32)) -> f32 {
+        self.tiles.light_level(p)
+    }
+
+    pub fn room_at(&self, p: (i32, i32)) -> Option<i32> {
+        self.tiles.room_at(p)
+    }
+
+    // relights the floor from the ceiling lamps of all rooms that are not dark
+    // and from every light source, which is blocked like sight
+    pub fn update_lighting(&mut self, dark_rooms: &HashSet<i32>, sources: &[((i32, i32), i32)]) {
+        self.tiles.reset_light(dark_rooms);
+        for &(center, radius) in sources {
+            let mut lit = vec![];
+            for p in Rect::new(center.0 - radius, center.1 - radius, 2 * radius + 1, 2 * radius + 1) {
+                let (dx, dy) = ((p.0 - center.0) as f32, (p.1 - center.1) as f32);
+                let distance = (dx * dx + dy * dy).sqrt();
+                if distance <= radius as f32 && self.is_in_line_of_sight(center, p) {
+                    lit.push((p, 1.0 - distance / (radius as f32 + 1.0)));
+                }
+            }
+            for (p, light) in lit {
+                self.tiles.add_light(p, light);
+            }
+        }
+    }
+
+    pub fn area_at(&self, p: (i32, i32)) -> Option<AreaInstance> {
+        self.areas.iter()
+            .find(|&&(rect, _)| rect.is_enclosed(p))
+            .map(|&(_, area)| area)
+    }
+
+    pub fn paint_room<T: Shape>(&mut self, shape: &T) {
+        self.tiles.create_room(shape);
+    }
+
+    pub fn carve<T: Shape>(&mut self, shape: &T) {
+        self.tiles.create_anti_room(shape);
+    }
 
-        self.tiles.create_room(&Rect::new(10, 25, 5, 5));
-        self.tiles.create_room(&Rect::new(10, 12, 8, 8));
+    pub fn paint_corridor(&mut self, rect: &Rect) {
+        self.tiles.create_corridor(rect);
+    }
+
+    pub fn paint_wall(&mut self, line: &Line) {
+        self.tiles.draw_line(line);
+    }
+
+    pub fn erase(&mut self, p: (i32, i32)) {
+        self.tiles.set_spec(p, None);
+    }
+
+    // a floor plan of the tiles and areas, without anything spawned on them
+    pub fn export(&self) -> FloorPlan {
+        let mut plan = FloorPlan::default();
+        plan.size = (self.width(), self.height());
+        plan.tiles = (0..self.tiles.height())
+            .map(|y| (0..self.tiles.width()).map(|x| self.tiles.spec((x, y))).collect())
+            .collect();
+        plan.areas = self.areas.clone();
+        plan
+    }
+
+    pub fn discover(&mut self, index: usize, cells: &[(i32, i32)]) {
+        for p in cells {
+            self.tiles.discover(index, *p);
+        }
+    }
 
-        self.tiles.create_corridor(&Rect::new(10, 18, 3, 9));
-        self.tiles.create_corridor(&Rect::new(13, 27, 9, 3));
+    pub fn track_fov(&mut self, index: usize) {
+        self.tiles.track_fov(index);
     }
 
-    pub fn update(&mut self, tcod: &mut Tcod) {
-        self.tiles.update(tcod);
+    pub fn show(&mut self, fovs: &[usize]) {
+        self.tiles.show(fovs);
     }
 
-    pub fn draw(&self, tcod: &mut Tcod, viewport: &Viewport) {
-        self.tiles.draw(tcod, viewport);
+    pub fn draw(&self, tcod: &mut Tcod, viewport: &Viewport, reveal: bool) {
+        self.tiles.draw(tcod, viewport, reveal);
     }
 
     pub fn clear_all(&mut self) {
@@ -137,54 +403,198 @@ impl Maps {
         self.characters.clear();
     }
 
-    pub fn move_entity(&mut self, map: Map, entity: &Entity, from: (i32, i32), to: (i32, i32)) {
-        if from.0 != to.0 || from.1 != to.1 {
-            match map {
-                Map::Item => {
-                    if let Some(entry) = self.items.remove(entity, from) {
-                        self.items.push_entry(entry, to);
-                    }                },
-                Map::Character => {
-                    if let Some(entry) = self.characters.remove(entity, from) {
-                        self.characters.push_entry(entry, to);
-                    }
-                }
-            }
+    fn entity_map(&mut self, map: Map) -> &mut EntityMap {
+        match map {
+            Map::Item => &mut self.items,
+            Map::Character => &mut self.characters,
         }
     }
 
-    pub fn remove(&mut self, map: Map, entity: &Entity, p: (i32, i32)) -> Option<Entry> {
+    pub fn contains(&self, map: Map, entity: &Entity) -> bool {
         match map {
-            Map::Item => self.items.remove(entity, p),
-            Map::Character => self.characters.remove(entity, p),
+            Map::Item => self.items.position(entity).is_some(),
+            Map::Character => self.characters.position(entity).is_some(),
         }
     }
 
+    pub fn move_entity(&mut self, map: Map, entity: &Entity, to: (i32, i32)) {
+        self.entity_map(map).move_to(entity, to);
+    }
+
+    pub fn remove(&mut self, map: Map, entity: &Entity) -> Option<Entry> {
+        self.entity_map(map).remove(entity)
+    }
+
     pub fn push(&mut self, map: Map, entity: &Entity, p: (i32, i32)) {
-        match map {
-            Map::Item => self.items.push(entity, p),
-            Map::Character => self.characters.push(entity, p),
-        }
+        self.entity_map(map).push(entity, p);
     }
 
     pub fn pop(&mut self, map: Map, p: (i32, i32)) -> Option<Entry> {
-        match map {
-            Map::Item => self.items.pop(p),
-            Map::Character => self.items.pop(p),
-        }
+        self.entity_map(map).pop(p)
     }
 
-    pub fn set_blocking(&mut self, map: Map, entity: &Entity, p: (i32, i32), blocking: bool) {
-        match map {
-            Map::Item => self.items.set_blocking(entity, p, blocking),
-            Map::Character => self.characters.set_blocking(entity, p, blocking),
-        }
+    pub fn set_blocking(&mut self, map: Map, entity: &Entity, blocking: bool) {
+        self.entity_map(map).set_blocking(entity, blocking);
     }
 
-    pub fn set_sight_blocking(&mut self, map: Map, entity: &Entity, p: (i32, i32), blocking: bool) {
-        match map {
-            Map::Item => self.items.set_sight_blocking(entity, p, blocking),
-            Map::Character => self.characters.set_sight_blocking(entity, p, blocking),
+    pub fn set_sight_blocking(&mut self, map: Map, entity: &Entity, blocking: bool) {
+        self.entity_map(map).set_sight_blocking(entity, blocking);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{ HashSet };
+    use specs::{ World };
+    use maps::{ Maps, Map };
+    use tile_map::{ DoorState };
+    use geometry::{ Rect };
+    use floor_plan::{ FloorPlan };
+
+    fn tower_floor() -> Maps {
+        Maps::from_plan(&FloorPlan::parse(include_str!("../data/tower0.map")).unwrap())
+    }
+
+    #[test]
+    fn noise_fades_with_distance() {
+        let mut maps = tower_floor();
+        let heard = maps.propagate_noise((12, 14), 5.0);
+        assert!(heard[&(12, 14)] == 5.0);
+        assert!(heard[&(13, 14)] == 4.0);
+        assert!(heard[&(14, 15)] == 2.0);
+        assert!(heard.get(&(12, 30)).is_none());
+    }
+
+    #[test]
+    fn walls_dampen_noise() {
+        let mut maps = tower_floor();
+        // (20, 22) is the west wall of the big room, (19, 22) lies outside of any room
+        let heard = maps.propagate_noise((21, 22), 10.0);
+        assert!(heard[&(20, 22)] == 3.0);
+        assert!(heard.get(&(19, 22)).is_none());
+        assert!(heard[&(22, 22)] == 9.0);
+    }
+
+    #[test]
+    fn doors_dampen_noise_until_opened() {
+        let mut maps = tower_floor();
+        // (14, 28) is the door between the main frame room and the corridor
+        assert_eq!(maps.door((14, 28)), Some(DoorState::Closed));
+        assert!(maps.propagate_noise((13, 28), 10.0)[&(14, 28)] == 6.0);
+
+        assert!(maps.toggle_door((14, 28)));
+        assert!(!maps.is_blocking((14, 28)));
+        assert!(maps.propagate_noise((13, 28), 10.0)[&(14, 28)] == 9.0);
+
+        // nobody gets shut in the doorway and locked doors stay shut
+        let mut world = World::new();
+        let guard = world.create_entity().build();
+        maps.push(Map::Character, &guard, (14, 28));
+        assert!(!maps.toggle_door((14, 28)));
+        maps.tiles.set_door((14, 28), DoorState::Locked(2));
+        assert!(!maps.open_door((14, 28)));
+        assert!(maps.is_sight_blocking((14, 28)));
+    }
+
+    #[test]
+    fn keycards_unlock_doors_until_the_lockdown() {
+        let mut maps = tower_floor();
+        // (25, 21) is the door between the two halves of the office
+        assert!(maps.door((25, 21)) == Some(DoorState::Locked(3)));
+        assert!(!maps.toggle_door((25, 21)));
+        assert!(!maps.unlock_door((25, 21), 2));
+        assert!(maps.unlock_door((25, 21), 3));
+        assert!(!maps.is_blocking((25, 21)));
+
+        assert!(maps.lock_doors());
+        assert!(maps.door((25, 21)) == Some(DoorState::Locked(3)));
+        assert!(!maps.lock_doors());
+        assert!(maps.unlock_doors());
+        assert!(maps.door((25, 21)) == Some(DoorState::Open));
+    }
+
+    #[test]
+    fn walls_break_until_the_loop_resets() {
+        let mut maps = tower_floor();
+        // a bullet hole in the west wall of the big room
+        assert!(maps.shoot((20, 22), 80.0));
+        assert!(maps.is_blocking((20, 22)) && !maps.is_sight_blocking((20, 22)));
+        assert!(maps.is_in_line_of_sight((19, 22), (21, 22)));
+        assert!(!maps.shoot((20, 22), 80.0));
+
+        // the door at (20, 28) gives way to the first blast, its frame to the second
+        assert!(maps.blast((21, 28), 2, 60.0));
+        assert_eq!(maps.door((20, 28)), Some(DoorState::Broken));
+        assert!(maps.is_blocking((20, 27)));
+        assert!(maps.blast((21, 28), 2, 60.0));
+        assert!(!maps.is_blocking((20, 27)) && !maps.is_sight_blocking((20, 27)));
+        assert!(!maps.shoot((20, 27), 80.0));
+
+        maps.restore();
+        assert!(maps.is_sight_blocking((20, 22)));
+        assert_eq!(maps.door((20, 28)), Some(DoorState::Closed));
+        assert!(maps.is_blocking((20, 27)) && maps.tiles.is_wall((20, 27)));
+    }
+
+    #[test]
+    fn lights_follow_sight() {
+        let mut maps = tower_floor();
+        let big_room = maps.room_at((22, 22)).unwrap();
+        let mut dark = HashSet::new();
+        dark.insert(big_room);
+
+        maps.update_lighting(&dark, &[]);
+        assert!(maps.light_level((22, 22)) == 0.0);
+        assert!(maps.light_level((12, 14)) == 1.0);
+
+        // a lamp at (22, 22) with radius 3, the west wall stops it
+        maps.update_lighting(&dark, &[((22, 22), 3)]);
+        assert!(maps.light_level((22, 22)) == 1.0);
+        assert!(maps.light_level((24, 22)) == 0.5);
+        assert!(maps.light_level((26, 22)) == 0.0);
+        assert!(maps.light_level((19, 22)) == 0.0);
+    }
+
+    #[test]
+    fn export_matches_the_loaded_plan() {
+        let maps = tower_floor();
+        let plan = FloorPlan::parse(include_str!("../data/tower0.map")).unwrap();
+        let exported = maps.export();
+        assert!(exported.size == plan.size);
+        let (width, height) = plan.size;
+        for y in 0..height {
+            for x in 0..width {
+                assert!(exported.tile((x, y)) == plan.tile((x, y)));
+            }
         }
+        assert!(exported.areas == plan.areas);
+    }
+
+    #[test]
+    fn characters_remember_their_own_tiles() {
+        let mut maps = tower_floor();
+        maps.track_fov(0);
+        maps.track_fov(1);
+        maps.tiles.discover(0, (12, 14));
+        assert!(maps.tiles.is_discovered((12, 14)));
+
+        maps.show(&[1]);
+        assert!(!maps.tiles.is_discovered((12, 14)));
+        maps.show(&[0, 1]);
+        assert!(maps.tiles.is_discovered((12, 14)));
+    }
+
+    #[test]
+    fn floors_can_be_larger_than_the_screen() {
+        let mut maps = Maps::new(120, 60);
+        maps.paint_room(&Rect::new(90, 50, 10, 5));
+        assert_eq!(maps.within((110, 55)), Some((110, 55)));
+        assert_eq!(maps.within((120, 55)), None);
+        assert!(maps.propagate_noise((95, 52), 3.0).contains_key(&(96, 52)));
+
+        // off the map nothing is there and nothing can be put
+        assert!(maps.collect_characters_with_shape(Rect::new(115, 55, 10, 10)).is_empty());
+        assert!(maps.is_blocking((-1, 3)));
+        assert!(maps.pop(Map::Item, (130, 3)).is_none());
     }
 }
@@ -0,0 +1,22 @@
+use std::collections::{ HashSet };
+
+// knowledge that survives the reset of the time loop
+pub struct LoopMemory {
+    facts: HashSet<String>,
+}
+
+impl Default for LoopMemory {
+    fn default() -> Self {
+        LoopMemory { facts: HashSet::new() }
+    }
+}
+
+impl LoopMemory {
+    pub fn learn(&mut self, fact: &str) {
+        self.facts.insert(fact.to_string());
+    }
+
+    pub fn knows(&self, fact: &str) -> bool {
+        self.facts.contains(fact)
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::{ self, Read, Write };
+use std::net::{ TcpListener, TcpStream, ToSocketAddrs };
+
+use net::protocol::{ Action, Delta };
+
+// a non blocking, line based tcp stream
+pub struct Connection {
+    stream: TcpStream,
+    buffer: String,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Connection { stream: stream, buffer: String::new() })
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        // the messages are tiny, so a short blocking write is fine
+        self.stream.set_nonblocking(false)?;
+        let result = self.stream.write_all(format!("{}\n", line).as_bytes());
+        self.stream.set_nonblocking(true)?;
+        result
+    }
+
+    // all complete lines that arrived since the last poll
+    fn poll(&mut self) -> io::Result<Vec<String>> {
+        let mut chunk = [0; 1024];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "peer hung up")),
+                Ok(n) => self.buffer.push_str(&String::from_utf8_lossy(&chunk[..n])),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        let mut lines = vec![];
+        while let Some(end) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..end + 1).collect();
+            lines.push(line.trim().to_string());
+        }
+        Ok(lines)
+    }
+}
+
+// the authoritative side, it accepts a single client
+pub struct Host {
+    listener: TcpListener,
+    client: Option<Connection>,
+}
+
+impl Host {
+    pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Host { listener: listener, client: None })
+    }
+
+    // tests bind to any free port and need to know which one they got
+    #[cfg(test)]
+    pub fn local_addr(&self) -> io::Result<::std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    // true if a client connected just now
+    pub fn accept(&mut self) -> bool {
+        if self.client.is_some() {
+            return false;
+        }
+        match self.listener.accept() {
+            Ok((stream, _)) => {
+                self.client = Connection::new(stream).ok();
+                self.client.is_some()
+            },
+            Err(_) => false,
+        }
+    }
+
+    pub fn poll_actions(&mut self) -> Vec<Action> {
+        let lines = match self.client {
+            Some(ref mut client) => client.poll(),
+            None => return vec![],
+        };
+        match lines {
+            Ok(lines) => lines.iter().filter_map(|l| Action::decode(l)).collect(),
+            Err(_) => {
+                self.client = None;
+                vec![]
+            }
+        }
+    }
+
+    pub fn send_deltas(&mut self, deltas: &[Delta]) {
+        let failed = match self.client {
+            Some(ref mut client) => deltas.iter().any(|d| client.send(&d.encode()).is_err()),
+            None => false,
+        };
+        if failed {
+            self.client = None;
+        }
+    }
+}
+
+pub struct Client {
+    connection: Connection,
+}
+
+impl Client {
+    pub fn connect<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        Ok(Client { connection: Connection::new(stream)? })
+    }
+
+    pub fn send_action(&mut self, action: Action) -> io::Result<()> {
+        self.connection.send(&action.encode())
+    }
+
+    pub fn poll_deltas(&mut self) -> io::Result<Vec<Delta>> {
+        let lines = self.connection.poll()?;
+        Ok(lines.iter().filter_map(|l| Delta::decode(l)).collect())
+    }
+}
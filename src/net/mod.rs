@@ -0,0 +1,353 @@
+pub mod protocol;
+pub mod connection;
+pub mod snapshot;
+
+use net::connection::{ Host, Client };
+
+// which end of a co-op game this process is, the host runs the simulation
+// and the client only mirrors it
+pub enum Network {
+    Offline,
+    Host(Host),
+    Client(Client),
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Network::Offline
+    }
+}
+
+impl Network {
+    pub fn is_client(&self) -> bool {
+        match *self {
+            Network::Client(_) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::{ Duration, Instant };
+    use specs::{ World, Entity, Dispatcher, DispatcherBuilder, Join };
+    use tcod::input::{ Key, KeyCode };
+
+    use components::appearance::{ Renderable };
+    use components::common::{ Active, CharacterStats, InTurn, WaitForTurn, MoveToPosition };
+    use components::interaction::{ Interactable, Interaction };
+    use components::inventory::{ Inventory };
+    use components::item::{ Item, ItemInstance };
+    use components::npc::{ Dialogue };
+    use components::player::{ Player, Remote, Equipment };
+    use components::space::{ Position, Level, Viewport };
+    use components::stealth::{ Sneaking };
+    use engine::input_handler::{ InputHandler };
+    use engine::tcod::{ VIEW_WIDTH, VIEW_HEIGHT };
+    use engine::time::{ Time };
+    use event_log::{ EventLog };
+    use floor_plan::{ FloorPlan };
+    use game_state::{ GameState };
+    use game_stats::{ GameStats };
+    use maps::{ Map };
+    use net::{ Network };
+    use net::connection::{ Host, Client };
+    use net::protocol::{ Action, Delta, NetId };
+    use systems::network_system::{ HostSystem, ClientSystem, net_id };
+    use systems::round_scheduler::{ RoundScheduler };
+    use tile_map::{ DoorState, WallDamage };
+    use tower::{ Tower };
+
+    fn wait_for<T, F>(mut poll: F) -> Vec<T> where F: FnMut() -> Vec<T> {
+        for _ in 0..200 {
+            let received = poll();
+            if !received.is_empty() {
+                return received;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        vec![]
+    }
+
+    #[test]
+    fn host_and_client_talk_over_loopback() {
+        let mut host = Host::bind("127.0.0.1:0").unwrap();
+        let address = host.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut client = Client::connect(address).unwrap();
+            client.send_action(Action::Move(3, 4)).unwrap();
+            client.send_action(Action::EndTurn).unwrap();
+            wait_for(|| client.poll_deltas().unwrap())
+        });
+
+        wait_for(|| if host.accept() { vec![()] } else { vec![] });
+        assert!(host.is_connected());
+
+        let mut actions = vec![];
+        while actions.len() < 2 {
+            actions.extend(wait_for(|| host.poll_actions()));
+        }
+        assert!(actions == vec![Action::Move(3, 4), Action::EndTurn]);
+
+        let id = NetId { id: 1, gen: 1 };
+        host.send_deltas(&[Delta::Welcome(id), Delta::Position(id, 3.5, 4.5, 0)]);
+        let deltas = handle.join().unwrap();
+        assert!(deltas[0] == Delta::Welcome(id));
+    }
+
+    const DOOR: (i32, i32) = (14, 28);
+
+    // both ends load the same floors and spawn the same players and a watch in the same
+    // order, the second player stands right next to a door and is played by the client
+    fn peer(network: Network) -> (World, Entity, Entity, Entity) {
+        let mut world = World::new();
+        world.register::<Player>();
+        world.register::<Remote>();
+        world.register::<Active>();
+        world.register::<Position>();
+        world.register::<Level>();
+        world.register::<CharacterStats>();
+        world.register::<Interactable>();
+        world.register::<Interaction>();
+        world.register::<Renderable>();
+        world.register::<Dialogue>();
+        world.register::<Item>();
+        world.register::<Inventory>();
+        world.register::<Equipment>();
+        world.register::<Sneaking>();
+        world.register::<InTurn>();
+        world.register::<WaitForTurn>();
+        world.register::<MoveToPosition>();
+
+        let plan = FloorPlan::parse(include_str!("../../data/tower0.map")).unwrap();
+        let mut tower = Tower::new();
+        tower.add_floor(Level::Tower(0), &plan);
+        tower.add_floor(Level::Tower(1), &plan);
+        world.add_resource(tower);
+        world.add_resource(network);
+        world.add_resource(GameState::default());
+        world.add_resource(GameStats::default());
+        world.add_resource(EventLog::default());
+        world.add_resource(InputHandler::default());
+        world.add_resource(Viewport::new(0, 0, VIEW_WIDTH, VIEW_HEIGHT));
+        world.add_resource(Time {
+            delta_time: Duration::from_millis(20),
+            fixed_step: Duration::from_millis(20),
+            last_fixed_update: Instant::now(),
+        });
+
+        let mut players = vec![];
+        for &(x, y) in [(15, 15), (13, 28)].iter() {
+            let id = world.create_entity()
+                .with(Player)
+                .with(Position { x: x as f32 + 0.5, y: y as f32 + 0.5 })
+                .with(Level::Tower(0))
+                .with(CharacterStats { health: 100.0, max_health: 100.0 })
+                .with(Inventory::new())
+                .with(Equipment::new())
+                .build();
+            world.write_resource::<Tower>().get_mut(&Level::Tower(0)).unwrap().push(Map::Character, &id, (x, y));
+            players.push(id);
+        }
+        let watch = world.create_entity()
+            .with(Item { instance: ItemInstance::Watch })
+            .with(Position { x: 16.5, y: 15.5 })
+            .with(Level::Tower(0))
+            .build();
+        world.write_resource::<Tower>().get_mut(&Level::Tower(0)).unwrap().push(Map::Item, &watch, (16, 15));
+        world.write::<Active>().insert(players[0], Active);
+        (world, players[0], players[1], watch)
+    }
+
+    // what a player of either end gets to see
+    fn view(world: &World) -> (bool, Option<DoorState>, Vec<((i32, i32), WallDamage)>, Vec<(u32, (i32, i32), Level)>,
+                               Vec<(NetId, Vec<NetId>, Option<NetId>)>) {
+        let door = world.read_resource::<Tower>().get(&Level::Tower(0)).unwrap().door(DOOR);
+        let mut walls = world.read_resource::<Tower>().get(&Level::Tower(0)).unwrap().damaged();
+        walls.sort_by_key(|&(p, _)| p);
+        let players = (&*world.entities(), &world.read::<Player>(), &world.read::<Position>(), &world.read::<Level>())
+            .join()
+            .map(|(id, _, p, level)| (id.id(), (p.x as i32, p.y as i32), *level))
+            .collect();
+        let carried = (&*world.entities(), &world.read::<Inventory>(), &world.read::<Equipment>()).join()
+            .map(|(id, inventory, equipment)| (net_id(id), inventory.items.iter().map(|i| net_id(*i)).collect(),
+                                               equipment.active_item.map(net_id)))
+            .collect();
+        (world.read_resource::<GameState>().is_turn_based, door, walls, players, carried)
+    }
+
+    fn press(world: &mut World, code: KeyCode, printable: char) {
+        world.write_resource::<InputHandler>().key = Key { code: code, printable: printable, pressed: true,
+                                                           ..Default::default() };
+    }
+
+    struct Session {
+        host: (World, Dispatcher<'static, 'static>),
+        client: (World, Dispatcher<'static, 'static>),
+    }
+
+    impl Session {
+        fn step(&mut self) {
+            self.host.1.dispatch(&mut self.host.0.res);
+            self.host.0.maintain();
+            self.client.1.dispatch(&mut self.client.0.res);
+            self.client.0.maintain();
+            *self.host.0.write_resource::<InputHandler>() = InputHandler::default();
+            *self.client.0.write_resource::<InputHandler>() = InputHandler::default();
+        }
+
+        // keeps both ends running until the client caught up with the host
+        fn sync(&mut self) {
+            for _ in 0..200 {
+                self.step();
+                if view(&self.host.0) == view(&self.client.0) {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+            panic!("the client never caught up");
+        }
+    }
+
+    #[test]
+    fn host_and_client_stay_in_step_across_turns() {
+        let host = Host::bind("127.0.0.1:0").unwrap();
+        let client = Client::connect(host.local_addr().unwrap()).unwrap();
+        let (host_world, local, remote, watch) = peer(Network::Host(host));
+        let (client_world, _, _, _) = peer(Network::Client(client));
+        let mut session = Session {
+            host: (host_world, DispatcherBuilder::new()
+                   .add(HostSystem::new(), "host_system", &[])
+                   .add(RoundScheduler, "round_scheduler", &[])
+                   .build()),
+            client: (client_world, DispatcherBuilder::new()
+                     .add(ClientSystem, "client_system", &[])
+                     .build()),
+        };
+
+        // the client opens the door next to its player
+        session.sync();
+        assert!(session.client.0.read::<Active>().get(remote).is_some());
+        press(&mut session.client.0, KeyCode::Char, 'e');
+        for _ in 0..200 {
+            session.step();
+            if view(&session.client.0).1 == Some(DoorState::Open) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        session.sync();
+        assert!(view(&session.host.0).1 == Some(DoorState::Open));
+
+        // the remote player is not the host's to rotate to
+        press(&mut session.host.0, KeyCode::Tab, ' ');
+        session.step();
+        assert!(session.host.0.read::<Active>().get(local).is_some());
+        assert!(session.host.0.read::<Active>().get(remote).is_none());
+
+        // in turns the round only ends once both players are done
+        press(&mut session.host.0, KeyCode::Spacebar, ' ');
+        session.sync();
+        assert!(view(&session.client.0).0);
+        let round = session.host.0.read_resource::<GameStats>().elapsed();
+        press(&mut session.client.0, KeyCode::Enter, ' ');
+        for _ in 0..200 {
+            session.step();
+            if session.host.0.read::<InTurn>().get(remote).is_none() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(session.host.0.read::<WaitForTurn>().get(remote).is_some());
+        assert!(session.host.0.read_resource::<GameStats>().elapsed() == round);
+
+        press(&mut session.host.0, KeyCode::Enter, ' ');
+        session.step();
+        assert!(session.host.0.read_resource::<GameStats>().elapsed() > round);
+        assert!(session.host.0.read::<InTurn>().get(remote).is_some());
+        assert!(session.host.0.read::<Active>().get(local).is_some());
+
+        // shot and blown up walls look the same on both ends until the loop resets
+        {
+            let mut tower = session.host.0.write_resource::<Tower>();
+            let maps = tower.get_mut(&Level::Tower(0)).unwrap();
+            maps.shoot((20, 22), 80.0);
+            maps.blast((21, 28), 2, 60.0);
+            maps.blast((21, 28), 2, 60.0);
+        }
+        session.sync();
+        {
+            let tower = session.client.0.read_resource::<Tower>();
+            let maps = tower.get(&Level::Tower(0)).unwrap();
+            assert!(!maps.is_sight_blocking((20, 22)) && !maps.is_blocking((20, 27)));
+            assert!(maps.door((20, 28)) == Some(DoorState::Broken));
+        }
+        session.host.0.write_resource::<Tower>().get_mut(&Level::Tower(0)).unwrap().restore();
+        session.sync();
+        {
+            let tower = session.client.0.read_resource::<Tower>();
+            let maps = tower.get(&Level::Tower(0)).unwrap();
+            assert!(maps.damaged().is_empty());
+            assert!(maps.is_sight_blocking((20, 22)) && maps.is_blocking((20, 27)));
+        }
+
+        // what the client's player picks up and equips is mirrored
+        {
+            let world = &mut session.host.0;
+            world.write::<Position>().remove(watch);
+            world.write_resource::<Tower>().get_mut(&Level::Tower(0)).unwrap().remove(Map::Item, &watch);
+            world.write::<Inventory>().get_mut(remote).unwrap().items.push(watch);
+            world.write::<Equipment>().get_mut(remote).unwrap().active_item = Some(watch);
+        }
+        session.sync();
+        assert!(session.client.0.read::<Inventory>().get(remote).unwrap().items == vec![watch]);
+        assert!(session.client.0.read::<Equipment>().get(remote).unwrap().active_item == Some(watch));
+        assert!(session.client.0.read::<Position>().get(watch).is_none());
+
+        // an entity that took over the id of one the client lost is left alone
+        let stranger = {
+            let world = &mut session.client.0;
+            world.write_resource::<Tower>().get_mut(&Level::Tower(0)).unwrap().remove(Map::Item, &watch);
+            world.delete_entity(watch);
+            world.maintain();
+            world.create_entity().build()
+        };
+        assert!(stranger.id() == watch.id() && stranger != watch);
+        {
+            let world = &mut session.host.0;
+            world.write::<Position>().insert(watch, Position { x: 16.5, y: 15.5 });
+            world.write_resource::<Tower>().get_mut(&Level::Tower(0)).unwrap().push(Map::Item, &watch, (16, 15));
+            world.write::<Inventory>().get_mut(remote).unwrap().items.clear();
+            world.write::<Equipment>().get_mut(remote).unwrap().active_item = None;
+        }
+        session.sync();
+        assert!(session.client.0.read::<Position>().get(stranger).is_none());
+        assert!(session.client.0.read::<Equipment>().get(remote).unwrap().active_item.is_none());
+
+        // the countdown follows the host's loop clock and resets with it
+        assert!(session.client.0.read_resource::<GameStats>().elapsed() == session.host.0.read_resource::<GameStats>().elapsed());
+        session.host.0.write_resource::<GameStats>().reset();
+        for _ in 0..200 {
+            session.step();
+            if session.client.0.read_resource::<GameStats>().elapsed() == 0.0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(session.client.0.read_resource::<GameStats>().elapsed() == 0.0);
+
+        // riding the elevator takes the player off the floor on both ends
+        {
+            let world = &mut session.host.0;
+            let mut tower = world.write_resource::<Tower>();
+            tower.get_mut(&Level::Tower(0)).unwrap().remove(Map::Character, &remote);
+            tower.get_mut(&Level::Tower(1)).unwrap().push(Map::Character, &remote, (13, 28));
+            world.write::<Level>().insert(remote, Level::Tower(1));
+        }
+        session.sync();
+        let tower = session.client.0.read_resource::<Tower>();
+        assert!(!tower.get(&Level::Tower(0)).unwrap().contains(Map::Character, &remote));
+        assert!(tower.get(&Level::Tower(1)).unwrap().contains(Map::Character, &remote));
+    }
+}
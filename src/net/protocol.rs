@@ -0,0 +1,178 @@
+// every message is a single line of whitespace separated words
+
+use tile_map::{ DoorState, WallDamage };
+
+// what a client asks the host to do with its character
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    Move(i32, i32),
+    Interact,
+    Pick,
+    Drop,
+    Sneak,
+    Select(i32),
+    EndTurn,
+}
+
+// an entity as both ends know it, the generation tells apart entities that reuse an id
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NetId {
+    pub id: u32,
+    pub gen: i32,
+}
+
+// what changed on the host since the last frame
+#[derive(Clone, Debug, PartialEq)]
+pub enum Delta {
+    // the entity the client controls
+    Welcome(NetId),
+    Position(NetId, f32, f32, i32),
+    Gone(NetId),
+    Health(NetId, f32),
+    State(NetId, usize),
+    // the items a character carries
+    Inventory(NetId, Vec<NetId>),
+    // active item, passive item and clothing of a character
+    Equipment(NetId, [Option<NetId>; 3]),
+    // seconds of the loop that were played
+    Clock(f32),
+    TurnBased(bool),
+    // a door tile at x and y on a level
+    Door(i32, i32, i32, DoorState),
+    // a wall or door tile shot or blown up at x and y on a level
+    Wall(i32, i32, i32, WallDamage),
+}
+
+fn parse<T: ::std::str::FromStr>(word: Option<&str>) -> Option<T> {
+    word.and_then(|w| w.parse().ok())
+}
+
+impl NetId {
+    // written as id:generation
+    pub fn encode(&self) -> String {
+        format!("{}:{}", self.id, self.gen)
+    }
+
+    pub fn decode(word: Option<&str>) -> Option<NetId> {
+        let mut parts = word?.split(':');
+        let id = NetId { id: parse(parts.next())?, gen: parse(parts.next())? };
+        match parts.next() {
+            None => Some(id),
+            Some(_) => None,
+        }
+    }
+}
+
+// an empty equipment slot is written as -
+fn encode_slot(slot: &Option<NetId>) -> String {
+    slot.map_or("-".into(), |id| id.encode())
+}
+
+fn decode_slot(word: Option<&str>) -> Option<Option<NetId>> {
+    match word? {
+        "-" => Some(None),
+        other => NetId::decode(Some(other)).map(Some),
+    }
+}
+
+impl Action {
+    pub fn encode(&self) -> String {
+        match *self {
+            Action::Move(x, y) => format!("move {} {}", x, y),
+            Action::Interact => "interact".into(),
+            Action::Pick => "pick".into(),
+            Action::Drop => "drop".into(),
+            Action::Sneak => "sneak".into(),
+            Action::Select(digit) => format!("select {}", digit),
+            Action::EndTurn => "end_turn".into(),
+        }
+    }
+
+    pub fn decode(line: &str) -> Option<Action> {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("move") => Some(Action::Move(parse(words.next())?, parse(words.next())?)),
+            Some("interact") => Some(Action::Interact),
+            Some("pick") => Some(Action::Pick),
+            Some("drop") => Some(Action::Drop),
+            Some("sneak") => Some(Action::Sneak),
+            Some("select") => Some(Action::Select(parse(words.next())?)),
+            Some("end_turn") => Some(Action::EndTurn),
+            _ => None,
+        }
+    }
+}
+
+impl Delta {
+    pub fn encode(&self) -> String {
+        match *self {
+            Delta::Welcome(id) => format!("welcome {}", id.encode()),
+            Delta::Position(id, x, y, level) => format!("pos {} {} {} {}", id.encode(), x, y, level),
+            Delta::Gone(id) => format!("gone {}", id.encode()),
+            Delta::Health(id, health) => format!("hp {} {}", id.encode(), health),
+            Delta::State(id, state) => format!("state {} {}", id.encode(), state),
+            Delta::Inventory(id, ref items) => items.iter()
+                .fold(format!("inventory {}", id.encode()), |line, item| line + " " + &item.encode()),
+            Delta::Equipment(id, ref slots) => format!("equipment {} {} {} {}", id.encode(), encode_slot(&slots[0]),
+                                                       encode_slot(&slots[1]), encode_slot(&slots[2])),
+            Delta::Clock(clock) => format!("clock {}", clock),
+            Delta::TurnBased(on) => format!("turn_based {}", on),
+            Delta::Door(x, y, level, state) => format!("door {} {} {} {}", x, y, level, state.name()),
+            Delta::Wall(x, y, level, damage) => format!("wall {} {} {} {}", x, y, level, damage.name()),
+        }
+    }
+
+    pub fn decode(line: &str) -> Option<Delta> {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("welcome") => Some(Delta::Welcome(NetId::decode(words.next())?)),
+            Some("pos") => Some(Delta::Position(NetId::decode(words.next())?, parse(words.next())?,
+                                                parse(words.next())?, parse(words.next())?)),
+            Some("gone") => Some(Delta::Gone(NetId::decode(words.next())?)),
+            Some("hp") => Some(Delta::Health(NetId::decode(words.next())?, parse(words.next())?)),
+            Some("state") => Some(Delta::State(NetId::decode(words.next())?, parse(words.next())?)),
+            Some("inventory") => {
+                let id = NetId::decode(words.next())?;
+                let items = words.map(|w| NetId::decode(Some(w))).collect::<Option<Vec<NetId>>>()?;
+                Some(Delta::Inventory(id, items))
+            },
+            Some("equipment") => Some(Delta::Equipment(NetId::decode(words.next())?, [decode_slot(words.next())?,
+                                                       decode_slot(words.next())?, decode_slot(words.next())?])),
+            Some("clock") => Some(Delta::Clock(parse(words.next())?)),
+            Some("turn_based") => Some(Delta::TurnBased(parse(words.next())?)),
+            Some("door") => Some(Delta::Door(parse(words.next())?, parse(words.next())?, parse(words.next())?,
+                                             DoorState::from_name(words.next()?)?)),
+            Some("wall") => Some(Delta::Wall(parse(words.next())?, parse(words.next())?, parse(words.next())?,
+                                             WallDamage::from_name(words.next()?)?)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use net::protocol::{ Action, Delta, NetId };
+    use tile_map::{ DoorState, WallDamage };
+
+    #[test]
+    fn messages_survive_a_round_trip() {
+        let actions = [Action::Move(3, -4), Action::Interact, Action::Select(7), Action::EndTurn];
+        for action in actions.iter() {
+            assert!(Action::decode(&action.encode()) == Some(*action));
+        }
+        let id = |id, gen| NetId { id: id, gen: gen };
+        let deltas = [Delta::Welcome(id(2, 1)), Delta::Position(id(5, 3), 12.5, 3.0, 0), Delta::Gone(id(9, 1)),
+                      Delta::Health(id(1, 1), 7.5), Delta::State(id(4, 2), 2), Delta::TurnBased(true),
+                      Delta::Inventory(id(1, 1), vec![id(7, 1), id(8, 2)]), Delta::Inventory(id(1, 1), vec![]),
+                      Delta::Equipment(id(1, 1), [Some(id(7, 1)), None, Some(id(8, 2))]), Delta::Clock(431.25),
+                      Delta::Door(14, 28, 0, DoorState::Open), Delta::Door(25, 21, 0, DoorState::Locked(3)),
+                      Delta::Wall(20, 22, 0, WallDamage::Holed)];
+        for delta in deltas.iter() {
+            assert!(Delta::decode(&delta.encode()) == Some(delta.clone()));
+        }
+        assert!(Action::decode("move 1") == None);
+        assert!(Delta::decode("teleport 1 2") == None);
+        assert!(Delta::decode("gone 9").is_none());
+        assert!(Delta::decode("inventory 1:1 7").is_none());
+    }
+}
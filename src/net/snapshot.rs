@@ -0,0 +1,144 @@
+use std::collections::{ HashMap };
+
+use net::protocol::{ Delta, NetId };
+use tile_map::{ DoorState, WallDamage };
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntityState {
+    pub position: Option<(f32, f32, i32)>,
+    pub health: Option<f32>,
+    pub state: Option<usize>,
+    pub inventory: Option<Vec<NetId>>,
+    pub equipment: Option<[Option<NetId>; 3]>,
+}
+
+// everything a client needs to mirror the host, keyed by entity
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    pub entities: HashMap<NetId, EntityState>,
+    // keyed by position and level
+    pub doors: HashMap<(i32, i32, i32), DoorState>,
+    // only the damaged walls, keyed like the doors
+    pub walls: HashMap<(i32, i32, i32), WallDamage>,
+    pub is_turn_based: bool,
+    pub clock: f32,
+}
+
+impl Snapshot {
+    pub fn entry(&mut self, id: NetId) -> &mut EntityState {
+        self.entities.entry(id).or_insert_with(EntityState::default)
+    }
+
+    // the deltas that turn the previous snapshot into this one
+    pub fn diff(&self, previous: &Snapshot) -> Vec<Delta> {
+        let empty = EntityState::default();
+        let mut deltas = vec![];
+        if self.is_turn_based != previous.is_turn_based {
+            deltas.push(Delta::TurnBased(self.is_turn_based));
+        }
+        if self.clock != previous.clock {
+            deltas.push(Delta::Clock(self.clock));
+        }
+        for (id, now) in &self.entities {
+            let before = previous.entities.get(id).unwrap_or(&empty);
+            if now.position != before.position {
+                match now.position {
+                    Some((x, y, level)) => deltas.push(Delta::Position(*id, x, y, level)),
+                    None => deltas.push(Delta::Gone(*id)),
+                }
+            }
+            if let Some(health) = now.health {
+                if before.health != Some(health) {
+                    deltas.push(Delta::Health(*id, health));
+                }
+            }
+            if let Some(state) = now.state {
+                if before.state != Some(state) {
+                    deltas.push(Delta::State(*id, state));
+                }
+            }
+            if let Some(ref items) = now.inventory {
+                if before.inventory.as_ref() != Some(items) {
+                    deltas.push(Delta::Inventory(*id, items.clone()));
+                }
+            }
+            if let Some(slots) = now.equipment {
+                if before.equipment != Some(slots) {
+                    deltas.push(Delta::Equipment(*id, slots));
+                }
+            }
+        }
+        for (&(x, y, level), state) in &self.doors {
+            if previous.doors.get(&(x, y, level)) != Some(state) {
+                deltas.push(Delta::Door(x, y, level, *state));
+            }
+        }
+        for (&(x, y, level), damage) in &self.walls {
+            if previous.walls.get(&(x, y, level)) != Some(damage) {
+                deltas.push(Delta::Wall(x, y, level, *damage));
+            }
+        }
+        for &(x, y, level) in previous.walls.keys() {
+            if !self.walls.contains_key(&(x, y, level)) {
+                deltas.push(Delta::Wall(x, y, level, WallDamage::Intact));
+            }
+        }
+        for (id, before) in &previous.entities {
+            if before.position.is_some() && !self.entities.contains_key(id) {
+                deltas.push(Delta::Gone(*id));
+            }
+        }
+        deltas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use net::snapshot::{ Snapshot };
+    use net::protocol::{ Delta, NetId };
+    use tile_map::{ WallDamage };
+
+    fn id(id: u32) -> NetId {
+        NetId { id: id, gen: 1 }
+    }
+
+    #[test]
+    fn only_changes_are_sent() {
+        let mut previous = Snapshot::default();
+        previous.entry(id(1)).position = Some((1.0, 1.0, 0));
+        previous.entry(id(1)).health = Some(10.0);
+        previous.entry(id(1)).inventory = Some(vec![id(4)]);
+        previous.entry(id(1)).equipment = Some([Some(id(4)), None, None]);
+        previous.entry(id(2)).position = Some((5.0, 5.0, 0));
+        previous.entry(id(3)).position = Some((7.0, 7.0, 0));
+
+        let mut next = previous.clone();
+        next.entry(id(1)).position = Some((2.0, 1.0, 0));
+        next.entry(id(1)).inventory = Some(vec![id(4), id(5)]);
+        next.entry(id(2)).position = None;
+        next.entities.remove(&id(3));
+        next.is_turn_based = true;
+        next.clock = 12.5;
+
+        let mut deltas = next.diff(&previous);
+        deltas.sort_by_key(|d| format!("{:?}", d));
+        assert!(deltas == vec![Delta::Clock(12.5), Delta::Gone(id(2)), Delta::Gone(id(3)),
+                               Delta::Inventory(id(1), vec![id(4), id(5)]), Delta::Position(id(1), 2.0, 1.0, 0),
+                               Delta::TurnBased(true)]);
+        assert!(next.diff(&next).is_empty());
+    }
+
+    #[test]
+    fn repaired_walls_are_sent_as_intact() {
+        let mut previous = Snapshot::default();
+        previous.walls.insert((20, 22, 0), WallDamage::Holed);
+        previous.walls.insert((20, 27, 0), WallDamage::Holed);
+
+        let mut next = Snapshot::default();
+        next.walls.insert((20, 27, 0), WallDamage::Rubble);
+
+        let mut deltas = next.diff(&previous);
+        deltas.sort_by_key(|d| format!("{:?}", d));
+        assert!(deltas == vec![Delta::Wall(20, 22, 0, WallDamage::Intact), Delta::Wall(20, 27, 0, WallDamage::Rubble)]);
+    }
+}
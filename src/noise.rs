@@ -0,0 +1,27 @@
+use components::space::{ Level };
+
+pub struct Noise {
+    pub origin: (i32, i32),
+    pub level: Level,
+    pub loudness: f32,
+}
+
+pub struct Noises {
+    pub events: Vec<Noise>,
+}
+
+impl Default for Noises {
+    fn default() -> Self {
+        Noises { events: vec![] }
+    }
+}
+
+impl Noises {
+    pub fn emit(&mut self, origin: (i32, i32), level: Level, loudness: f32) {
+        self.events.push(Noise { origin: origin, level: level, loudness: loudness });
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
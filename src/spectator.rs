@@ -0,0 +1,313 @@
+use specs::{ World, Entity, Join };
+use tcod::colors::{ self, Color };
+use tcod::input::{ KeyCode };
+
+use engine::state::{ State, Transition };
+use engine::tcod::{ Tcod, VIEW_WIDTH, VIEW_HEIGHT };
+use engine::input_handler::{ InputHandler };
+use components::appearance::{ Renderable, Layer0, Layer1 };
+use components::common::{ Active, Description, MoveToPosition };
+use components::npc::{ Npc };
+use components::player::{ Player };
+use components::space::{ Position, Level, Viewport };
+use components::stealth::{ Awareness, AlertState };
+use tower::{ Tower };
+
+const CAMERA_SPEED: i32 = 4;
+
+// a free camera over the running game, it can follow anybody, see through
+// walls and show what the npcs are up to
+pub struct Spectator {
+    camera: Viewport,
+    level: Level,
+    follow: Option<Entity>,
+    reveal: bool,
+    overlay: bool,
+}
+
+fn alert_color(state: AlertState) -> Color {
+    match state {
+        AlertState::Unaware => colors::LIGHT_GREY,
+        AlertState::Suspicious => colors::YELLOW,
+        AlertState::Alerted => colors::RED,
+    }
+}
+
+impl Spectator {
+    pub fn new() -> Self {
+        Spectator {
+            camera: Viewport::new(0, 0, VIEW_WIDTH, VIEW_HEIGHT),
+            level: Level::Tower(0),
+            follow: None,
+            reveal: false,
+            overlay: true,
+        }
+    }
+
+    // everybody worth following, in a stable order
+    fn characters(&self, world: &World) -> Vec<Entity> {
+        let entities = world.entities();
+        let players = world.read::<Player>();
+        let npcs = world.read::<Npc>();
+        let positions = world.read::<Position>();
+        (&*entities, &positions).join()
+            .filter(|&(id, _)| players.get(id).is_some() || npcs.get(id).is_some())
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    fn follow_next(&mut self, world: &World) {
+        let characters = self.characters(world);
+        let next = match self.follow.and_then(|f| characters.iter().position(|c| *c == f)) {
+            Some(index) => characters.get(index + 1).cloned(),
+            None => characters.first().cloned(),
+        };
+        self.follow = next;
+    }
+
+    fn switch_level(&mut self, world: &World) {
+        let levels = world.read_resource::<Tower>().levels();
+        if let Some(index) = levels.iter().position(|l| *l == self.level) {
+            self.level = levels[(index + 1) % levels.len()];
+        }
+        self.follow = None;
+    }
+
+    fn track(&mut self, world: &World) {
+        if let Some(id) = self.follow {
+            let positions = world.read::<Position>();
+            let levels = world.read::<Level>();
+            match (positions.get(id), levels.get(id)) {
+                (Some(p), Some(level)) => {
+                    self.camera.center_at(*p);
+                    self.level = *level;
+                },
+                // picked up or gone, keep the camera where it is
+                _ => self.follow = None,
+            }
+        }
+    }
+
+    fn is_shown(&self, tcod: &Tcod, p: (i32, i32)) -> bool {
+        self.camera.visible(p) && (self.reveal || tcod.fovs.is_in_fov(p))
+    }
+
+    fn render_npc_states(&self, tcod: &mut Tcod, world: &World) {
+        let entities = world.entities();
+        let positions = world.read::<Position>();
+        let levels = world.read::<Level>();
+        let descriptions = world.read::<Description>();
+        let awareness = world.read::<Awareness>();
+        let move_to_positions = world.read::<MoveToPosition>();
+        let npcs = world.read::<Npc>();
+
+        for (id, _, p, level, aware) in (&*entities, &npcs, &positions, &levels, &awareness).join() {
+            if *level != self.level {
+                continue;
+            }
+            if let Some(movement) = move_to_positions.get(id) {
+                for step in movement.path.iter() {
+                    let s = (step.x as i32, step.y as i32);
+                    if self.camera.visible(s) {
+                        tcod.highlight(self.camera.transform(s), colors::DARKER_BLUE);
+                    }
+                }
+            }
+            let p = (p.x as i32, p.y as i32);
+            if self.camera.visible(p) {
+                let name = descriptions.get(id).map_or("?".into(), |d| d.name.clone());
+                let text = format!("{} {:.0}%", name, aware.value * 100.0);
+                let screen = self.camera.transform((p.0 + 1, p.1));
+                tcod.render_overlay_text(screen, alert_color(aware.state), &text);
+            }
+        }
+    }
+}
+
+impl State for Spectator {
+    fn start(&mut self, _tcod: &mut Tcod, world: &mut World) {
+        let actives = world.read::<Active>();
+        let entities = world.entities();
+        self.follow = (&*entities, &actives).join().map(|(id, _)| id).next();
+        self.track(world);
+    }
+
+    fn handle_events(&mut self, _tcod: &mut Tcod, world: &mut World) -> Transition {
+        let mut delta = (0, 0);
+        let (follow_next, switch_level) = {
+            let pressed = world.write_resource::<InputHandler>().intercept(|input| {
+                if input.is_key_pressed(KeyCode::Escape) || input.is_key_pressed(KeyCode::F2) {
+                    return None;
+                }
+                if input.is_char_pressed('h') || input.is_key_pressed(KeyCode::Left) {
+                    delta.0 -= CAMERA_SPEED;
+                }
+                if input.is_char_pressed('l') || input.is_key_pressed(KeyCode::Right) {
+                    delta.0 += CAMERA_SPEED;
+                }
+                if input.is_char_pressed('k') || input.is_key_pressed(KeyCode::Up) {
+                    delta.1 -= CAMERA_SPEED;
+                }
+                if input.is_char_pressed('j') || input.is_key_pressed(KeyCode::Down) {
+                    delta.1 += CAMERA_SPEED;
+                }
+                if input.is_char_pressed('r') {
+                    self.reveal = !self.reveal;
+                }
+                if input.is_char_pressed('o') {
+                    self.overlay = !self.overlay;
+                }
+                Some((input.is_key_pressed(KeyCode::Tab), input.is_char_pressed('n')))
+            });
+            match pressed {
+                Some(pressed) => pressed,
+                None => return Transition::Pop,
+            }
+        };
+
+        if delta != (0, 0) {
+            // panning lets go of whoever was followed
+            self.follow = None;
+            self.camera.pan(delta.0, delta.1);
+        }
+        if follow_next {
+            self.follow_next(world);
+        }
+        if switch_level {
+            self.switch_level(world);
+        }
+        Transition::None
+    }
+
+    fn update(&mut self, _tcod: &mut Tcod, world: &mut World) -> Transition {
+        self.track(world);
+        Transition::None
+    }
+
+    fn render(&mut self, tcod: &mut Tcod, world: &mut World) {
+        let renderables = world.read::<Renderable>();
+        let positions = world.read::<Position>();
+        let levels = world.read::<Level>();
+        let layer0 = world.read::<Layer0>();
+        let layer1 = world.read::<Layer1>();
+        let descriptions = world.read::<Description>();
+        let tower = world.read_resource::<Tower>();
+
+        tcod.clear(colors::BLACK);
+        tower.draw(&self.level, tcod, &self.camera, self.reveal);
+
+        let mut shown = vec![];
+        for (_, renderable, position, level) in (&layer0, &renderables, &positions, &levels).join() {
+            shown.push((renderable, position, level));
+        }
+        for (_, renderable, position, level) in (&layer1, &renderables, &positions, &levels).join() {
+            shown.push((renderable, position, level));
+        }
+        for (renderable, position, level) in shown {
+            let p = (position.x as i32, position.y as i32);
+            if *level == self.level && self.is_shown(tcod, p) {
+                tcod.render_character(self.camera.transform(p), renderable.color, renderable.character);
+            }
+        }
+
+        if self.overlay {
+            self.render_npc_states(tcod, world);
+        }
+
+        let Level::Tower(floor) = self.level;
+        let following = self.follow
+            .and_then(|id| descriptions.get(id))
+            .map_or("free camera".into(), |d| format!("following {}", d.name));
+        tcod.render_text((1, 1), colors::BLACK, colors::WHITE,
+                         &format!("SPECTATOR - floor {} - {}", floor, following));
+        tcod.render_text((1, 3), colors::BLACK, colors::LIGHT_GREY,
+                         &"hjkl pan, tab follow next, n next floor, r reveal, o npc overlay, esc back".to_string());
+        tcod.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::{ World };
+    use spectator::{ Spectator };
+    use tower::{ Tower };
+    use floor_plan::{ FloorPlan };
+    use components::npc::{ Npc, NpcInstance };
+    use components::player::{ Player };
+    use components::space::{ Position, Level };
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Player>();
+        world.register::<Npc>();
+        world.register::<Position>();
+        world.register::<Level>();
+        let mut tower = Tower::new();
+        for floor in 0..3 {
+            tower.add_floor(Level::Tower(floor), &FloorPlan::default());
+        }
+        world.add_resource(tower);
+        world
+    }
+
+    #[test]
+    fn following_cycles_through_everybody_and_back_to_the_free_camera() {
+        let mut world = world();
+        let player = world.create_entity()
+            .with(Player)
+            .with(Position { x: 1.0, y: 1.0 })
+            .with(Level::Tower(0))
+            .build();
+        let npc = world.create_entity()
+            .with(Npc { instance: NpcInstance::Guard })
+            .with(Position { x: 5.0, y: 5.0 })
+            .with(Level::Tower(1))
+            .build();
+        world.create_entity().with(Position { x: 3.0, y: 3.0 }).build();
+
+        let mut spectator = Spectator::new();
+        spectator.follow_next(&world);
+        assert!(spectator.follow == Some(player));
+        spectator.follow_next(&world);
+        assert!(spectator.follow == Some(npc));
+        spectator.track(&world);
+        assert!(spectator.level == Level::Tower(1));
+        spectator.follow_next(&world);
+        assert!(spectator.follow.is_none());
+        spectator.follow_next(&world);
+        assert!(spectator.follow == Some(player));
+    }
+
+    #[test]
+    fn switching_levels_wraps_around() {
+        let world = world();
+        let mut spectator = Spectator::new();
+        spectator.switch_level(&world);
+        assert!(spectator.level == Level::Tower(1));
+        spectator.switch_level(&world);
+        assert!(spectator.level == Level::Tower(2));
+        spectator.switch_level(&world);
+        assert!(spectator.level == Level::Tower(0));
+    }
+
+    #[test]
+    fn tracking_lets_go_of_a_target_without_a_position() {
+        let mut world = world();
+        let npc = world.create_entity()
+            .with(Npc { instance: NpcInstance::Guard })
+            .with(Position { x: 5.0, y: 5.0 })
+            .with(Level::Tower(2))
+            .build();
+
+        let mut spectator = Spectator::new();
+        spectator.follow = Some(npc);
+        spectator.track(&world);
+        assert!(spectator.follow == Some(npc));
+        assert!(spectator.level == Level::Tower(2));
+
+        world.write::<Position>().remove(npc);
+        spectator.track(&world);
+        assert!(spectator.follow.is_none());
+        assert!(spectator.level == Level::Tower(2));
+    }
+}
@@ -0,0 +1,133 @@
+use specs::{ System, ReadStorage, Fetch, FetchMut, Entities, WriteStorage, Join, Entity };
+
+use components::faction::{ Faction, FactionInstance };
+use components::item::{ Item, ItemInstance, Type };
+use components::npc::{ Npc };
+use components::player::{ Player, Equipment };
+use components::space::{ Position, Level };
+use components::stealth::{ Vision, Awareness, Sneaking };
+use engine::time::{ Time };
+
+use event_log::{ EventLog, LogEvent };
+use factions::{ FactionRelations, suspicion };
+use game_state::{ GameState };
+use tower::{ Tower };
+
+// awareness gained per second when a player stands fully lit right in front of an npc
+const AWARENESS_GAIN: f32 = 1.5;
+const AWARENESS_DECAY: f32 = 0.1;
+const ALERT_RADIUS: f32 = 15.0;
+
+fn same_side(a: Option<FactionInstance>, b: Option<FactionInstance>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a == b,
+        _ => true,
+    }
+}
+
+// what a player wears and whether they act out of character for staff, who
+// neither sneak around nor walk about with a weapon drawn
+pub fn appearance(id: Entity, equipment: &Equipment, items: &ReadStorage<Item>,
+                  sneaking: &ReadStorage<Sneaking>) -> (Option<ItemInstance>, bool) {
+    let clothing = equipment.clothing.and_then(|i| items.get(i)).map(|i| i.instance);
+    let armed = equipment.active_item.and_then(|i| items.get(i))
+        .map_or(false, |i| i.get_type().1 == Type::Weapon);
+    (clothing, armed || sneaking.get(id).is_some())
+}
+
+pub struct DetectionSystem;
+unsafe impl Sync for DetectionSystem {}
+
+#[derive(SystemData)]
+pub struct DetectionSystemData<'a> {
+    entities: Entities<'a>,
+    npcs: ReadStorage<'a, Npc>,
+    players: ReadStorage<'a, Player>,
+    positions: ReadStorage<'a, Position>,
+    levels: ReadStorage<'a, Level>,
+    factions: ReadStorage<'a, Faction>,
+    equipments: ReadStorage<'a, Equipment>,
+    items: ReadStorage<'a, Item>,
+    sneaking: ReadStorage<'a, Sneaking>,
+    visions: WriteStorage<'a, Vision>,
+    awareness: WriteStorage<'a, Awareness>,
+    time: Fetch<'a, Time>,
+    tower: Fetch<'a, Tower>,
+    relations: Fetch<'a, FactionRelations>,
+    state: Fetch<'a, GameState>,
+    log: FetchMut<'a, EventLog>,
+}
+
+impl<'a> System<'a> for DetectionSystem {
+    type SystemData = DetectionSystemData<'a>;
+
+    fn run(&mut self, mut data: DetectionSystemData) {
+        let delta_time = data.time.delta_time.subsec_nanos() as f32 / 1.0e9;
+
+        let mut targets = vec![];
+        for (id, _, p, level, equipment) in (&*data.entities, &data.players, &data.positions,
+                                             &data.levels, &data.equipments).join() {
+            let (clothing, out_of_character) = appearance(id, equipment, &data.items, &data.sneaking);
+            targets.push((id, *p, *level, clothing, out_of_character));
+        }
+
+        let factions = &data.factions;
+        let relations = &data.relations;
+        let events = &data.state.events;
+
+        let mut alerted = vec![];
+        for (id, npc, p, level, vision, awareness) in (&*data.entities, &data.npcs, &data.positions, &data.levels,
+                                                       &mut data.visions, &mut data.awareness).join() {
+            let maps = data.tower.get(level).unwrap();
+            let p0 = (p.x as i32, p.y as i32);
+            let faction = factions.get(id).map(|f| f.instance);
+
+            let mut exposure: f32 = 0.0;
+            let mut spotted = None;
+            for &(player, target, target_level, clothing, out_of_character) in targets.iter() {
+                let p1 = (target.x as i32, target.y as i32);
+                // npcs without a faction mistrust everybody
+                let minds = faction.map_or(1.0, |f| suspicion(f, npc.is_guard(), relations.attitude(f, player),
+                                                              maps.area_at(p1), events, clothing, out_of_character));
+                if target_level == *level && minds > 0.0
+                    && vision.is_in_cone(p0, p1) && maps.is_in_line_of_sight(p0, p1) {
+                    let distance = (target - *p).length();
+                    let closeness = 1.0 - distance / (vision.range as f32 + 1.0);
+                    let e = maps.light_level(p1) * closeness * minds;
+                    if e > exposure {
+                        exposure = e;
+                        spotted = Some(target);
+                    }
+                }
+            }
+
+            let was_alerted = awareness.is_alerted();
+            if let Some(target) = spotted {
+                awareness.notice(delta_time * AWARENESS_GAIN * exposure);
+                // keep an eye on whatever caught the attention
+                vision.look_at(target - *p);
+            } else {
+                awareness.calm(delta_time * AWARENESS_DECAY);
+            }
+            if !was_alerted && awareness.is_alerted() {
+                data.log.log(LogEvent::Spotted(id));
+                alerted.push((*p, *level, faction));
+            }
+        }
+
+        // alerts spread to guards of the same faction in earshot
+        for (p0, level0, faction0) in alerted {
+            let nearby = data.tower.get(&level0)
+                .map_or(vec![], |maps| maps.collect_characters_in_radius((p0.x as i32, p0.y as i32), ALERT_RADIUS));
+            for id in nearby {
+                let faction = factions.get(id).map(|f| f.instance);
+                let is_guard = data.npcs.get(id).map_or(false, |npc| npc.is_guard());
+                if is_guard && same_side(faction0, faction) {
+                    if let Some(awareness) = data.awareness.get_mut(id) {
+                        awareness.alert();
+                    }
+                }
+            }
+        }
+    }
+}
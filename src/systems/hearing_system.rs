@@ -0,0 +1,70 @@
+use specs::{ System, ReadStorage, Fetch, FetchMut, Entities, WriteStorage, Join };
+
+use components::npc::{ Npc };
+use components::common::{ MoveToPosition };
+use components::space::{ Position, Level };
+use components::stealth::{ Vision, Awareness, Hearing };
+
+use noise::{ Noises };
+use tower::{ Tower };
+
+const INVESTIGATION_SPEED: f32 = 2.0;
+// how much a heard noise adds to the awareness of a listener per unit of loudness
+const NOISE_SUSPICION: f32 = 0.1;
+
+pub struct HearingSystem;
+unsafe impl Sync for HearingSystem {}
+
+#[derive(SystemData)]
+pub struct HearingSystemData<'a> {
+    entities: Entities<'a>,
+    npcs: ReadStorage<'a, Npc>,
+    hearings: ReadStorage<'a, Hearing>,
+    positions: ReadStorage<'a, Position>,
+    levels: ReadStorage<'a, Level>,
+    visions: WriteStorage<'a, Vision>,
+    awareness: WriteStorage<'a, Awareness>,
+    move_to_positions: WriteStorage<'a, MoveToPosition>,
+    noises: FetchMut<'a, Noises>,
+    tower: Fetch<'a, Tower>,
+}
+
+impl<'a> System<'a> for HearingSystem {
+    type SystemData = HearingSystemData<'a>;
+
+    fn run(&mut self, mut data: HearingSystemData) {
+        for noise in data.noises.events.iter() {
+            let maps = match data.tower.get(&noise.level) {
+                Some(maps) => maps,
+                None => continue,
+            };
+            let heard = maps.propagate_noise(noise.origin, noise.loudness);
+
+            for (id, _, hearing, p, level, vision, awareness) in (&*data.entities, &data.npcs, &data.hearings,
+                                                                  &data.positions, &data.levels,
+                                                                  &mut data.visions, &mut data.awareness).join() {
+                let p0 = (p.x as i32, p.y as i32);
+                if *level != noise.level || p0 == noise.origin {
+                    continue;
+                }
+                if let Some(loudness) = heard.get(&p0) {
+                    if *loudness >= hearing.threshold {
+                        let source = Position { x: noise.origin.0 as f32 + 0.5, y: noise.origin.1 as f32 + 0.5 };
+                        vision.look_at(source - *p);
+                        awareness.notice(loudness * NOISE_SUSPICION);
+
+                        // go and have a look
+                        let path = maps.find_npc_path(&id, p0, noise.origin);
+                        if path.len() > 0 {
+                            data.move_to_positions.insert(id, MoveToPosition {
+                                path: path,
+                                speed: INVESTIGATION_SPEED,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        data.noises.clear();
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::{ HashMap };
+use specs::{ System, ReadStorage, FetchMut, Join };
+
+use components::interaction::{ Interactable };
+use components::item::{ Item };
+use components::player::{ Player, Equipment };
+use components::space::{ Position, Level };
+
+use tower::{ Tower };
+
+// everything the light of a floor depends on: its dark rooms, its light sources,
+// whatever blocks them and the revision of its tiles
+#[derive(Clone, PartialEq)]
+struct Lighting {
+    dark_rooms: Vec<i32>,
+    sources: Vec<((i32, i32), i32)>,
+    blockers: Vec<(i32, i32)>,
+    revision: u32,
+}
+
+impl Lighting {
+    fn new(revision: u32) -> Self {
+        Lighting { dark_rooms: vec![], sources: vec![], blockers: vec![], revision: revision }
+    }
+}
+
+pub struct LightingSystem {
+    // what every floor was last lit with
+    lit: HashMap<Level, Lighting>,
+}
+unsafe impl Sync for LightingSystem {}
+
+impl LightingSystem {
+    pub fn new() -> Self {
+        LightingSystem { lit: HashMap::new() }
+    }
+}
+
+#[derive(SystemData)]
+pub struct LightingSystemData<'a> {
+    players: ReadStorage<'a, Player>,
+    interactables: ReadStorage<'a, Interactable>,
+    equipments: ReadStorage<'a, Equipment>,
+    items: ReadStorage<'a, Item>,
+    positions: ReadStorage<'a, Position>,
+    levels: ReadStorage<'a, Level>,
+    tower: FetchMut<'a, Tower>,
+}
+
+impl<'a> System<'a> for LightingSystem {
+    type SystemData = LightingSystemData<'a>;
+
+    fn run(&mut self, mut data: LightingSystemData) {
+        // only the floors the players are on are looked at
+        let mut floors: HashMap<Level, Lighting> = HashMap::new();
+        for (_, level) in (&data.players, &data.levels).join() {
+            if let Some(maps) = data.tower.get(level) {
+                floors.insert(*level, Lighting::new(maps.revision()));
+            }
+        }
+
+        for (interactable, p, level) in (&data.interactables, &data.positions, &data.levels).join() {
+            let lighting = match floors.get_mut(level) {
+                Some(lighting) => lighting,
+                None => continue,
+            };
+            let p0 = (p.x as i32, p.y as i32);
+            // a switched off light switch darkens the room it is in
+            if interactable.has_tag("lights_off") {
+                if let Some(room) = data.tower.get(level).and_then(|maps| maps.room_at(p0)) {
+                    lighting.dark_rooms.push(room);
+                }
+            }
+            for radius in interactable.tagged("light:").iter().filter_map(|r| r.parse::<i32>().ok()) {
+                lighting.sources.push((p0, radius));
+            }
+            if interactable.is_sight_blocking() {
+                lighting.blockers.push(p0);
+            }
+        }
+        for (equipment, p, level) in (&data.equipments, &data.positions, &data.levels).join() {
+            let radius = equipment.active_item
+                .and_then(|i| data.items.get(i))
+                .and_then(|i| i.light_radius());
+            if let (Some(radius), Some(lighting)) = (radius, floors.get_mut(level)) {
+                lighting.sources.push(((p.x as i32, p.y as i32), radius));
+            }
+        }
+
+        for (level, mut lighting) in floors {
+            lighting.dark_rooms.sort();
+            lighting.sources.sort();
+            lighting.blockers.sort();
+            if self.lit.get(&level) == Some(&lighting) {
+                continue;
+            }
+            let dark = lighting.dark_rooms.iter().cloned().collect();
+            let maps = data.tower.get_mut(&level).unwrap();
+            maps.update_lighting(&dark, &lighting.sources);
+            self.lit.insert(level, lighting);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{ HashSet };
+    use specs::{ World, RunNow };
+
+    use components::interaction::{ Interactable };
+    use components::item::{ Item };
+    use components::player::{ Player, Equipment };
+    use components::space::{ Position, Level };
+    use floor_plan::{ FloorPlan };
+    use interactables::{ InteractableRegistry };
+    use systems::lighting_system::{ LightingSystem };
+    use tower::{ Tower };
+
+    #[test]
+    fn relights_occupied_floors_when_they_change() {
+        let mut world = World::new();
+        world.register::<Player>();
+        world.register::<Interactable>();
+        world.register::<Equipment>();
+        world.register::<Item>();
+        world.register::<Position>();
+        world.register::<Level>();
+
+        let plan = FloorPlan::parse(include_str!("../../data/tower0.map")).unwrap();
+        let mut tower = Tower::new();
+        tower.add_floor(Level::Tower(0), &plan);
+        tower.add_floor(Level::Tower(1), &plan);
+        world.add_resource(tower);
+
+        // a glowing terminal in the dim corridor between the two doors of each floor
+        let registry = InteractableRegistry::parse(include_str!("../../data/interactables.txt")).unwrap();
+        for level in [Level::Tower(0), Level::Tower(1)].iter() {
+            world.create_entity()
+                .with(Interactable::new(registry.get("security_terminal").unwrap(), 0))
+                .with(Position { x: 17.0, y: 27.0 })
+                .with(*level)
+                .build();
+        }
+        world.create_entity().with(Player).with(Position { x: 15.0, y: 15.0 }).with(Level::Tower(0)).build();
+
+        let mut lighting = LightingSystem::new();
+        let light = |world: &World, level: Level| {
+            world.read_resource::<Tower>().get(&level).unwrap().light_level((17, 28))
+        };
+        lighting.run_now(&world.res);
+        assert!(light(&world, Level::Tower(0)) > 0.4);
+        assert!(light(&world, Level::Tower(1)) == 0.4);
+
+        // nothing changed, so the floor is left alone
+        world.write_resource::<Tower>().get_mut(&Level::Tower(0)).unwrap().update_lighting(&HashSet::new(), &[]);
+        lighting.run_now(&world.res);
+        assert!(light(&world, Level::Tower(0)) == 0.4);
+
+        world.write_resource::<Tower>().get_mut(&Level::Tower(0)).unwrap().toggle_door((20, 28));
+        lighting.run_now(&world.res);
+        assert!(light(&world, Level::Tower(0)) > 0.4);
+    }
+}
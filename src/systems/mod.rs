@@ -4,3 +4,10 @@ pub mod stats_updater;
 pub mod interaction_system;
 pub mod player_controller;
 pub mod move_to_controller;
+pub mod detection_system;
+pub mod hearing_system;
+pub mod security_system;
+pub mod schedule_system;
+pub mod lighting_system;
+pub mod order_system;
+pub mod network_system;
@@ -2,11 +2,18 @@ use specs::{ System, ReadStorage, Fetch, FetchMut, Entities, WriteStorage, Join
 
 use components::space::{ Position, Level, mul, Viewport };
 use components::common::{ Active, MoveToPosition };
+use components::player::{ Player };
+use components::stealth::{ Sneaking };
 use engine::time::{ Time };
 
+use game_state::{ GameState };
+use noise::{ Noises };
 use tower::{ Tower };
 use maps::{ Map };
 
+const FOOTSTEP_NOISE: f32 = 4.0;
+const SNEAK_FOOTSTEP_NOISE: f32 = 1.0;
+
 pub struct MoveToController;
 unsafe impl Sync for MoveToController {}
 
@@ -17,6 +24,10 @@ pub struct MoveToControllerData<'a> {
     levels: ReadStorage<'a, Level>,
     move_to_positions: WriteStorage<'a, MoveToPosition>,
     actives: ReadStorage<'a, Active>,
+    players: ReadStorage<'a, Player>,
+    sneaking: ReadStorage<'a, Sneaking>,
+    noises: FetchMut<'a, Noises>,
+    state: FetchMut<'a, GameState>,
     tower: FetchMut<'a, Tower>,
     viewport: FetchMut<'a, Viewport>,
     time: Fetch<'a, Time>,
@@ -40,6 +51,11 @@ impl<'a> System<'a> for MoveToController {
     fn run(&mut self, mut data: MoveToControllerData) {
         let delta_time = data.time.delta_time.subsec_nanos() as f32 / 1.0e9;
 
+        let players = &data.players;
+        let sneaking = &data.sneaking;
+        let noises = &mut data.noises;
+        let state = &mut data.state;
+
         let mut finished_entities = vec![];
         for (id, p, level, t) in (&*data.entities, &mut data.positions, &data.levels, &mut data.move_to_positions).join() {
             let maps = data.tower.get_mut(level).unwrap();
@@ -47,13 +63,25 @@ impl<'a> System<'a> for MoveToController {
             if t.path.front().map_or(false, |next_pos|
                 if !p.approx_equal(&next_pos) {
                     let np = move_to(p, next_pos, t, delta_time);
+                    let from = (p.x as i32, p.y as i32);
+                    let to = (np.x as i32, np.y as i32);
                     // actually walk to target
-                    if !maps.is_not_planable(&id, (np.x as i32, np.y as i32)) {
-                        maps.move_entity(Map::Character, &id,
-                                        (p.x as i32, p.y as i32),
-                                        (np.x as i32, np.y as i32));
+                    if !maps.is_impassable(&id, to) {
+                        maps.move_entity(Map::Character, &id, to);
+                        if from != to && players.get(id).is_some() {
+                            let loudness = if sneaking.get(id).is_some() {
+                                SNEAK_FOOTSTEP_NOISE
+                            } else {
+                                FOOTSTEP_NOISE
+                            };
+                            noises.emit(to, *level, loudness);
+                        }
                         *p = np;
                         false
+                    } else if maps.open_door(to) {
+                        // walks on once the door is open
+                        state.fov_needs_update = true;
+                        false
                     } else {
                         // target is unreachable
                         true
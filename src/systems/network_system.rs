@@ -0,0 +1,394 @@
+use specs::{ System, ReadStorage, Fetch, FetchMut, Entities, Entity, WriteStorage, Join };
+
+use components::appearance::{ Renderable };
+use components::common::{ Active, InTurn, CharacterStats, MoveToPosition };
+use components::interaction::{ Interactable, Interaction };
+use components::inventory::{ Inventory };
+use components::item::{ Item };
+use components::npc::{ Dialogue };
+use components::player::{ Player, Remote, Equipment };
+use components::space::{ Position, Level, Viewport };
+use components::stealth::{ Sneaking };
+use engine::input_handler::{ InputHandler };
+use systems::player_controller::{ speed, reach, distance_cost, pick_up, drop_item, toggle_sneaking, select_item, use_door, keycard };
+
+use game_state::{ GameState };
+use game_stats::{ GameStats };
+use net::{ Network };
+use net::protocol::{ Action, Delta, NetId };
+use net::snapshot::{ Snapshot };
+use tcod::input::{ KeyCode };
+use tower::{ Tower };
+use maps::{ Map };
+
+fn level_index(level: &Level) -> i32 {
+    match *level {
+        Level::Tower(index) => index,
+    }
+}
+
+pub fn net_id(entity: Entity) -> NetId {
+    NetId { id: entity.id(), gen: entity.gen().id() }
+}
+
+// entities are the same on both ends since both build the same tower, a delta for
+// an entity the client does not have, even one that took over its id, is dropped
+fn find(entities: &Entities, id: NetId) -> Option<Entity> {
+    entities.join().find(|e| net_id(*e) == id)
+}
+
+// runs the actions of the remote player and sends back what changed
+pub struct HostSystem {
+    previous: Snapshot,
+}
+unsafe impl Sync for HostSystem {}
+
+impl HostSystem {
+    pub fn new() -> Self {
+        HostSystem { previous: Snapshot::default() }
+    }
+}
+
+#[derive(SystemData)]
+pub struct HostSystemData<'a> {
+    entities: Entities<'a>,
+    players: ReadStorage<'a, Player>,
+    remotes: WriteStorage<'a, Remote>,
+    levels: ReadStorage<'a, Level>,
+    positions: WriteStorage<'a, Position>,
+    char_stats: ReadStorage<'a, CharacterStats>,
+    interactables: ReadStorage<'a, Interactable>,
+    dialogues: ReadStorage<'a, Dialogue>,
+    items: ReadStorage<'a, Item>,
+    inventories: WriteStorage<'a, Inventory>,
+    equipments: WriteStorage<'a, Equipment>,
+    sneaking: WriteStorage<'a, Sneaking>,
+    in_turns: WriteStorage<'a, InTurn>,
+    move_to_positions: WriteStorage<'a, MoveToPosition>,
+    interactions: WriteStorage<'a, Interaction>,
+    state: FetchMut<'a, GameState>,
+    stats: Fetch<'a, GameStats>,
+    network: FetchMut<'a, Network>,
+    tower: FetchMut<'a, Tower>,
+}
+
+impl HostSystem {
+    fn apply(&self, id: Entity, action: Action, data: &mut HostSystemData) {
+        let (p, level) = match (data.positions.get(id), data.levels.get(id)) {
+            (Some(p), Some(level)) => (*p, *level),
+            _ => return,
+        };
+        let p0 = (p.x as i32, p.y as i32);
+        let maps = data.tower.get_mut(&level).unwrap();
+        // in turns the host waits for the client, actions out of turn are dropped
+        if data.state.is_turn_based && data.in_turns.get(id).is_none() {
+            return;
+        }
+        match action {
+            Action::Move(x, y) => {
+                let path = maps.find_path(&id, p0, (x, y));
+                let speed = speed(data.sneaking.get(id).is_some());
+                if let Some(turn) = data.in_turns.get_mut(id) {
+                    match distance_cost(path.len(), turn) {
+                        Some(cost) => turn.walk(cost),
+                        None => return,
+                    }
+                }
+                data.move_to_positions.insert(id, MoveToPosition { path: path, speed: speed });
+            },
+            Action::Interact => {
+                if data.state.is_turn_based {
+                    return;
+                }
+                let (interactables, dialogues) = (&data.interactables, &data.dialogues);
+                let target = maps.collect_characters_with_shape(reach(p0))
+                    .into_iter()
+                    .filter(|i| interactables.get(*i).is_some() || dialogues.get(*i).is_some())
+                    .next();
+                if let Some(target) = target {
+                    data.interactions.insert(target, Interaction { actor: id });
+                } else if use_door(maps, p0, keycard(data.equipments.get(id), &data.items)) {
+                    data.state.fov_needs_update = true;
+                }
+            },
+            Action::Pick => {
+                if let Some(inventory) = data.inventories.get_mut(id) {
+                    pick_up(maps, inventory, &mut data.positions, p);
+                }
+            },
+            Action::Drop => {
+                if let Some(inventory) = data.inventories.get_mut(id) {
+                    drop_item(maps, inventory, &mut data.positions, p);
+                }
+            },
+            Action::Sneak => toggle_sneaking(id, &mut data.sneaking),
+            Action::Select(digit) => {
+                if let (Some(inventory), Some(equipment)) = (data.inventories.get(id), data.equipments.get_mut(id)) {
+                    select_item(digit, inventory, equipment, &data.items);
+                }
+            },
+            Action::EndTurn => {
+                if let Some(turn) = data.in_turns.get_mut(id) {
+                    turn.end();
+                }
+            },
+        }
+    }
+
+    fn capture(&self, data: &HostSystemData) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        snapshot.is_turn_based = data.state.is_turn_based;
+        snapshot.clock = data.stats.elapsed();
+        for (id, p, level) in (&*data.entities, &data.positions, &data.levels).join() {
+            snapshot.entry(net_id(id)).position = Some((p.x, p.y, level_index(level)));
+        }
+        for (id, stats) in (&*data.entities, &data.char_stats).join() {
+            snapshot.entry(net_id(id)).health = Some(stats.health);
+        }
+        for (id, interactable) in (&*data.entities, &data.interactables).join() {
+            snapshot.entry(net_id(id)).state = Some(interactable.state());
+        }
+        for (id, inventory) in (&*data.entities, &data.inventories).join() {
+            snapshot.entry(net_id(id)).inventory = Some(inventory.items.iter().map(|i| net_id(*i)).collect());
+        }
+        for (id, equipment) in (&*data.entities, &data.equipments).join() {
+            let slots = [equipment.active_item, equipment.passive_item, equipment.clothing];
+            snapshot.entry(net_id(id)).equipment = Some([slots[0].map(net_id), slots[1].map(net_id), slots[2].map(net_id)]);
+        }
+        for level in data.tower.levels() {
+            for ((x, y), state) in data.tower.get(&level).unwrap().doors() {
+                snapshot.doors.insert((x, y, level_index(&level)), state);
+            }
+            for ((x, y), damage) in data.tower.get(&level).unwrap().damaged() {
+                snapshot.walls.insert((x, y, level_index(&level)), damage);
+            }
+        }
+        snapshot
+    }
+}
+
+impl<'a> System<'a> for HostSystem {
+    type SystemData = HostSystemData<'a>;
+
+    fn run(&mut self, mut data: HostSystemData) {
+        match *data.network {
+            Network::Host(_) => (),
+            _ => return,
+        }
+        // the client plays the last of the players, marked so nothing on this end drives it
+        let mut remote = (&*data.entities, &data.remotes).join().map(|(id, _)| id).next();
+        if remote.is_none() {
+            remote = (&*data.entities, &data.players).join().map(|(id, _)| id).last();
+            if let Some(id) = remote {
+                data.remotes.insert(id, Remote);
+            }
+        }
+        let actions = match *data.network {
+            Network::Host(ref mut host) => {
+                if host.accept() {
+                    // a new client gets the whole world
+                    self.previous = Snapshot::default();
+                    if let Some(id) = remote {
+                        host.send_deltas(&[Delta::Welcome(net_id(id))]);
+                    }
+                }
+                host.poll_actions()
+            },
+            _ => return,
+        };
+
+        if let Some(id) = remote {
+            for action in actions {
+                self.apply(id, action, &mut data);
+            }
+        }
+
+        let snapshot = self.capture(&data);
+        if let Network::Host(ref mut host) = *data.network {
+            if host.is_connected() {
+                host.send_deltas(&snapshot.diff(&self.previous));
+                self.previous = snapshot;
+            }
+        }
+    }
+}
+
+// sends the local input to the host and mirrors the world it sends back
+pub struct ClientSystem;
+unsafe impl Sync for ClientSystem {}
+
+#[derive(SystemData)]
+pub struct ClientSystemData<'a> {
+    entities: Entities<'a>,
+    items: ReadStorage<'a, Item>,
+    levels: WriteStorage<'a, Level>,
+    actives: WriteStorage<'a, Active>,
+    positions: WriteStorage<'a, Position>,
+    char_stats: WriteStorage<'a, CharacterStats>,
+    interactables: WriteStorage<'a, Interactable>,
+    renderables: WriteStorage<'a, Renderable>,
+    inventories: WriteStorage<'a, Inventory>,
+    equipments: WriteStorage<'a, Equipment>,
+    input: Fetch<'a, InputHandler>,
+    viewport: FetchMut<'a, Viewport>,
+    state: FetchMut<'a, GameState>,
+    stats: FetchMut<'a, GameStats>,
+    network: FetchMut<'a, Network>,
+    tower: FetchMut<'a, Tower>,
+}
+
+impl ClientSystem {
+    fn actions(&self, data: &ClientSystemData) -> Vec<Action> {
+        let mut actions = vec![];
+        if data.input.is_mouse_pressed() && !data.input.ctrl {
+            let pos_trans = data.viewport.inv_transform(data.input.mouse_pos);
+            if let Some((_, level)) = (&data.actives, &data.levels).join().next() {
+                let maps = data.tower.get(level).unwrap();
+                if let Some((x, y)) = maps.within(pos_trans) {
+                    if data.viewport.visible(pos_trans) {
+                        actions.push(Action::Move(x, y));
+                    }
+                }
+            }
+        }
+        if data.input.is_char_pressed('e') {
+            actions.push(Action::Interact);
+        } else if data.input.is_char_pressed('p') {
+            actions.push(Action::Pick);
+        } else if data.input.is_char_pressed('d') {
+            actions.push(Action::Drop);
+        } else if data.input.is_char_pressed('c') {
+            actions.push(Action::Sneak);
+        } else if let Some(digit) = data.input.pressed_digit {
+            actions.push(Action::Select(digit));
+        }
+        if data.input.is_key_pressed(KeyCode::Enter) {
+            actions.push(Action::EndTurn);
+        }
+        actions
+    }
+
+    fn apply(&self, delta: Delta, data: &mut ClientSystemData) {
+        match delta {
+            Delta::Welcome(id) => {
+                if let Some(entity) = find(&data.entities, id) {
+                    data.actives.clear();
+                    data.actives.insert(entity, Active);
+                }
+            },
+            Delta::Position(id, x, y, level) => {
+                let entity = match find(&data.entities, id) {
+                    Some(entity) => entity,
+                    None => return,
+                };
+                let map = if data.items.get(entity).is_some() { Map::Item } else { Map::Character };
+                let to = (x as i32, y as i32);
+                let level = Level::Tower(level);
+                data.positions.insert(entity, Position { x: x, y: y });
+                // taking the elevator leaves the old floor behind
+                if let Some(old) = data.levels.get(entity).cloned() {
+                    if old != level {
+                        data.tower.get_mut(&old).unwrap().remove(map, &entity);
+                        data.state.fov_needs_update = true;
+                    }
+                }
+                data.levels.insert(entity, level);
+                let maps = data.tower.get_mut(&level).unwrap();
+                if maps.contains(map, &entity) {
+                    maps.move_entity(map, &entity, to);
+                } else {
+                    maps.push(map, &entity, to);
+                    if map == Map::Item {
+                        maps.set_blocking(map, &entity, false);
+                        maps.set_sight_blocking(map, &entity, false);
+                    }
+                }
+            },
+            Delta::Gone(id) => {
+                let entity = match find(&data.entities, id) {
+                    Some(entity) => entity,
+                    None => return,
+                };
+                let map = if data.items.get(entity).is_some() { Map::Item } else { Map::Character };
+                if let (Some(_), Some(level)) = (data.positions.remove(entity), data.levels.get(entity)) {
+                    data.tower.get_mut(level).unwrap().remove(map, &entity);
+                }
+            },
+            Delta::Health(id, health) => {
+                if let Some(stats) = find(&data.entities, id).and_then(|e| data.char_stats.get_mut(e)) {
+                    stats.health = health;
+                }
+            },
+            Delta::State(id, state) => {
+                let entity = match find(&data.entities, id) {
+                    Some(entity) => entity,
+                    None => return,
+                };
+                if let Some(interactable) = data.interactables.get_mut(entity) {
+                    interactable.set_state(state);
+                    data.renderables.insert(entity, interactable.get_renderable());
+                    if let (Some(_), Some(level)) = (data.positions.get(entity), data.levels.get(entity)) {
+                        let maps = data.tower.get_mut(level).unwrap();
+                        maps.set_blocking(Map::Character, &entity, interactable.is_blocking());
+                        maps.set_sight_blocking(Map::Character, &entity, interactable.is_sight_blocking());
+                    }
+                    data.state.fov_needs_update = true;
+                }
+            },
+            Delta::Inventory(id, items) => {
+                let items = items.into_iter().filter_map(|i| find(&data.entities, i)).collect::<Vec<Entity>>();
+                if let Some(inventory) = find(&data.entities, id).and_then(|e| data.inventories.get_mut(e)) {
+                    inventory.items = items;
+                }
+            },
+            Delta::Equipment(id, slots) => {
+                let slots = [slots[0].and_then(|i| find(&data.entities, i)), slots[1].and_then(|i| find(&data.entities, i)),
+                             slots[2].and_then(|i| find(&data.entities, i))];
+                if let Some(equipment) = find(&data.entities, id).and_then(|e| data.equipments.get_mut(e)) {
+                    equipment.active_item = slots[0];
+                    equipment.passive_item = slots[1];
+                    equipment.clothing = slots[2];
+                }
+            },
+            // the countdown runs on the host's clock, it also resets with it
+            Delta::Clock(clock) => data.stats.set_elapsed(clock),
+            Delta::TurnBased(on) => data.state.is_turn_based = on,
+            Delta::Door(x, y, level, state) => {
+                if let Some(maps) = data.tower.get_mut(&Level::Tower(level)) {
+                    maps.set_door((x, y), state);
+                    data.state.fov_needs_update = true;
+                }
+            },
+            Delta::Wall(x, y, level, damage) => {
+                if let Some(maps) = data.tower.get_mut(&Level::Tower(level)) {
+                    maps.set_wall_damage((x, y), damage);
+                    data.state.fov_needs_update = true;
+                }
+            },
+        }
+    }
+}
+
+impl<'a> System<'a> for ClientSystem {
+    type SystemData = ClientSystemData<'a>;
+
+    fn run(&mut self, mut data: ClientSystemData) {
+        let actions = self.actions(&data);
+        let deltas = match *data.network {
+            Network::Client(ref mut client) => {
+                for action in actions {
+                    let _ = client.send_action(action);
+                }
+                client.poll_deltas().unwrap_or(vec![])
+            },
+            _ => return,
+        };
+        for delta in deltas {
+            self.apply(delta, &mut data);
+        }
+
+        if let Some((p, _)) = (&data.positions, &data.actives).join().next() {
+            data.viewport.center_at(*p);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use specs::{ System, ReadStorage, Fetch, Entities, WriteStorage, Join };
+
+use components::common::{ Active, MoveToPosition };
+use components::player::{ Player, Orders, Order };
+use components::space::{ Position, Level };
+use components::stealth::{ Sneaking };
+use systems::player_controller::{ speed };
+
+use game_state::{ GameState };
+use tower::{ Tower };
+
+// followers keep this many cells away from their leader
+const FOLLOW_DISTANCE: i32 = 2;
+
+pub struct OrderSystem;
+unsafe impl Sync for OrderSystem {}
+
+#[derive(SystemData)]
+pub struct OrderSystemData<'a> {
+    entities: Entities<'a>,
+    players: ReadStorage<'a, Player>,
+    actives: ReadStorage<'a, Active>,
+    sneaking: ReadStorage<'a, Sneaking>,
+    positions: ReadStorage<'a, Position>,
+    levels: ReadStorage<'a, Level>,
+    orders: WriteStorage<'a, Orders>,
+    move_to_positions: WriteStorage<'a, MoveToPosition>,
+    state: Fetch<'a, GameState>,
+    tower: Fetch<'a, Tower>,
+}
+
+impl<'a> System<'a> for OrderSystem {
+    type SystemData = OrderSystemData<'a>;
+
+    fn run(&mut self, mut data: OrderSystemData) {
+        // in turns every step is paid for by hand
+        if data.state.is_turn_based {
+            return;
+        }
+        for (id, _, orders, p, level) in (&*data.entities, &data.players, &mut data.orders,
+                                          &data.positions, &data.levels).join() {
+            // whatever a character walks to, it walks there until it arrives
+            if data.move_to_positions.get(id).is_some() {
+                continue;
+            }
+            let maps = data.tower.get(level).unwrap();
+            let p0 = (p.x as i32, p.y as i32);
+            let path = if let Some(waypoint) = orders.queue.pop_front() {
+                maps.find_path(&id, p0, waypoint)
+            } else if data.actives.get(id).is_some() {
+                continue;
+            } else {
+                match orders.order {
+                    Order::Hold => continue,
+                    Order::Follow(leader) => {
+                        let target = match (data.positions.get(leader), data.levels.get(leader)) {
+                            (Some(target), Some(l)) if l == level => (target.x as i32, target.y as i32),
+                            _ => continue,
+                        };
+                        if (target.0 - p0.0).abs() <= FOLLOW_DISTANCE && (target.1 - p0.1).abs() <= FOLLOW_DISTANCE {
+                            continue;
+                        }
+                        maps.find_path_to_character(&id, p0, target)
+                    },
+                    Order::Guard(post) => {
+                        if post == p0 {
+                            continue;
+                        }
+                        maps.find_path(&id, p0, post)
+                    },
+                }
+            };
+            if path.len() > 0 {
+                let speed = speed(data.sneaking.get(id).is_some());
+                data.move_to_positions.insert(id, MoveToPosition { path: path, speed: speed });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{ VecDeque };
+    use specs::{ World, Entity, RunNow };
+
+    use components::common::{ Active, MoveToPosition };
+    use components::player::{ Player, Orders, Order };
+    use components::space::{ Position, Level };
+    use components::stealth::{ Sneaking };
+    use floor_plan::{ FloorPlan };
+    use game_state::{ GameState };
+    use systems::order_system::{ OrderSystem, FOLLOW_DISTANCE };
+    use tower::{ Tower };
+
+    const ROOM: &str = "size 12 7\nlegend\nA wall 0\na floor 0\nend\ntiles\n\
+                        AAAAAAAAAAAA\n\
+                        AaaaaaaaaaaA\n\
+                        AaaaaaaaaaaA\n\
+                        AaaaaaaaaaaA\n\
+                        AaaaaaaaaaaA\n\
+                        AaaaaaaaaaaA\n\
+                        AAAAAAAAAAAA\n\
+                        end\n";
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Player>();
+        world.register::<Active>();
+        world.register::<Sneaking>();
+        world.register::<Position>();
+        world.register::<Level>();
+        world.register::<Orders>();
+        world.register::<MoveToPosition>();
+
+        let mut tower = Tower::new();
+        tower.add_floor(Level::Tower(0), &FloorPlan::parse(ROOM).unwrap());
+        {
+            // players only plan through what they have seen
+            let maps = tower.get_mut(&Level::Tower(0)).unwrap();
+            let cells: Vec<(i32, i32)> = (0..12).flat_map(|x| (0..7).map(move |y| (x, y))).collect();
+            maps.track_fov(0);
+            maps.discover(0, &cells);
+        }
+        world.add_resource(tower);
+        world.add_resource(GameState::default());
+        world
+    }
+
+    fn character(world: &mut World, p: (i32, i32), order: Order) -> Entity {
+        let mut orders = Orders::new();
+        orders.order = order;
+        world.create_entity()
+            .with(Player)
+            .with(Position { x: p.0 as f32 + 0.5, y: p.1 as f32 + 0.5 })
+            .with(Level::Tower(0))
+            .with(orders)
+            .build()
+    }
+
+    fn destination(world: &World, id: Entity) -> Option<(i32, i32)> {
+        world.read::<MoveToPosition>().get(id)
+            .and_then(|m| m.path.back().map(|p| (p.x as i32, p.y as i32)))
+    }
+
+    fn run(world: &mut World) {
+        OrderSystem.run_now(&world.res);
+        world.maintain();
+    }
+
+    #[test]
+    fn waypoints_are_walked_after_the_current_path() {
+        let mut world = world();
+        let player = character(&mut world, (2, 3), Order::Hold);
+        world.write::<Active>().insert(player, Active);
+        let mut path = VecDeque::new();
+        path.push_back(Position { x: 3.5, y: 3.5 });
+        world.write::<MoveToPosition>().insert(player, MoveToPosition { path: path, speed: 1.0 });
+        world.write::<Orders>().get_mut(player).unwrap().queue.push_back((8, 2));
+
+        run(&mut world);
+        assert!(destination(&world, player) == Some((3, 3)));
+        assert!(world.read::<Orders>().get(player).unwrap().queue.len() == 1);
+
+        // arrived at the end of the current path
+        world.write::<MoveToPosition>().remove(player);
+        world.write::<Position>().insert(player, Position { x: 3.5, y: 3.5 });
+        run(&mut world);
+        assert!(destination(&world, player) == Some((8, 2)));
+        assert!(world.read::<Orders>().get(player).unwrap().queue.is_empty());
+    }
+
+    #[test]
+    fn followers_stop_close_to_their_leader() {
+        let mut world = world();
+        let leader = character(&mut world, (9, 3), Order::Hold);
+        world.write::<Active>().insert(leader, Active);
+        let follower = character(&mut world, (2, 3), Order::Follow(leader));
+
+        run(&mut world);
+        let (x, y) = destination(&world, follower).unwrap();
+        assert!((x - 9).abs() <= FOLLOW_DISTANCE && (y - 3).abs() <= FOLLOW_DISTANCE);
+
+        world.write::<MoveToPosition>().remove(follower);
+        world.write::<Position>().insert(follower, Position { x: 7.5, y: 4.5 });
+        run(&mut world);
+        assert!(destination(&world, follower).is_none());
+    }
+
+    #[test]
+    fn guards_return_to_their_post() {
+        let mut world = world();
+        let guard = character(&mut world, (8, 4), Order::Guard((3, 2)));
+
+        run(&mut world);
+        assert!(destination(&world, guard) == Some((3, 2)));
+
+        world.write::<MoveToPosition>().remove(guard);
+        world.write::<Position>().insert(guard, Position { x: 3.5, y: 2.5 });
+        run(&mut world);
+        assert!(destination(&world, guard).is_none());
+    }
+}
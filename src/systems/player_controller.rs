@@ -1,21 +1,27 @@
 use std::collections::VecDeque;
-use specs::{System, ReadStorage, Fetch, FetchMut, Entities, WriteStorage, Join};
+use specs::{System, ReadStorage, Fetch, FetchMut, Entities, Entity, WriteStorage, Join};
 
 use game_state::GameState;
 
-use geometry::Rect;
+use geometry::{ Shape, Rect, Difference, Translate };
 
 use components::space::{Position, Level, Vector, Viewport, mul};
-use components::player::{Player, Equipment};
+use components::player::{Player, Remote, Equipment, Orders, Order};
 use components::common::{Active, InTurn, MoveToPosition, CharacterStats, ItemStats};
 use components::inventory::Inventory;
+use components::item::{Item, ItemInstance};
 use components::interaction::{Interactable, Interaction};
+use components::npc::Dialogue;
+use components::stealth::{Sneaking, Awareness};
+use components::faction::Faction;
 use engine::input_handler::InputHandler;
 use engine::time::Time;
 
 use event_log::{EventLog, LogEvent};
+use factions::FactionRelations;
+use noise::Noises;
 use tower::Tower;
-use maps::Map;
+use maps::{Map, Maps};
 
 pub struct PlayerController;
 
@@ -39,7 +45,12 @@ fn get_delta(input: &InputHandler) -> Vector {
     delta
 }
 
-fn distance_cost(dist: usize, turn: &InTurn) -> Option<i32> {
+// the cells around a character it can interact with
+pub fn reach(p: (i32, i32)) -> Translate<Difference<Rect, Rect>> {
+    Rect::new(-1, -1, 3, 3).difference(Rect::new(0, 0, 1, 1)).translate(p)
+}
+
+pub fn distance_cost(dist: usize, turn: &InTurn) -> Option<i32> {
     if dist < 5 {
         return Some(1);
     } else if dist < 10 && !turn.has_walked {
@@ -53,51 +64,108 @@ pub struct PlayerControllerData<'a> {
     entities: Entities<'a>,
     players: ReadStorage<'a, Player>,
     actives: ReadStorage<'a, Active>,
+    remotes: ReadStorage<'a, Remote>,
     levels: ReadStorage<'a, Level>,
     positions: WriteStorage<'a, Position>,
     interactions: WriteStorage<'a, Interaction>,
     interactables: WriteStorage<'a, Interactable>,
+    dialogues: ReadStorage<'a, Dialogue>,
     inventories: WriteStorage<'a, Inventory>,
+    items: ReadStorage<'a, Item>,
     move_to_positions: WriteStorage<'a, MoveToPosition>,
     equipments: WriteStorage<'a, Equipment>,
+    orders: WriteStorage<'a, Orders>,
     char_stats: WriteStorage<'a, CharacterStats>,
     item_stats: WriteStorage<'a, ItemStats>,
     in_turns: WriteStorage<'a, InTurn>,
+    sneaking: WriteStorage<'a, Sneaking>,
+    factions: ReadStorage<'a, Faction>,
+    awareness: WriteStorage<'a, Awareness>,
     time: Fetch<'a, Time>,
-    state: Fetch<'a, GameState>,
+    state: FetchMut<'a, GameState>,
     input: Fetch<'a, InputHandler>,
     log: FetchMut<'a, EventLog>,
+    noises: FetchMut<'a, Noises>,
+    relations: FetchMut<'a, FactionRelations>,
     tower: FetchMut<'a, Tower>,
     viewport: Fetch<'a, Viewport>,
 }
 
+pub fn speed(sneaking: bool) -> f32 {
+    if sneaking {
+        PLAYER_SPEED * SNEAK_SPEED_FACTOR
+    } else {
+        PLAYER_SPEED
+    }
+}
+
 impl PlayerController {
     fn process_turn_based(&self, data: &mut PlayerControllerData) {
-        if let Some((id, p, _, _, turn, equipment, level)) = (&*data.entities, &data.positions, &data.actives, &data.players, &mut data.in_turns, &data.equipments, &data.levels).join().next() {
+        if let Some((id, p, _, _, _, turn, equipment, level)) = (&*data.entities, &data.positions, &data.actives, &data.players, !&data.remotes, &mut data.in_turns, &mut data.equipments, &data.levels).join().next() {
             if data.input.is_mouse_pressed() {
                 let pos_trans = data.viewport.inv_transform(data.input.mouse_pos);
-                let maps = data.tower.get(level).unwrap();
-                if let Some(p1) = maps.screen_to_map(pos_trans) {
+                let maps = data.tower.get_mut(level).unwrap();
+                if let Some(p1) = maps.within(pos_trans) {
                     let p0 = (p.x as i32, p.y as i32);
                     if data.viewport.visible(pos_trans) {
                         if !data.input.ctrl {
                             let path = maps.find_path(&id, p0, p1);
                             if let Some(c) = distance_cost(path.len(), &turn) {
-                                data.move_to_positions.insert(id, MoveToPosition { path: path, speed: PLAYER_SPEED });
+                                let speed = speed(data.sneaking.get(id).is_some());
+                                data.move_to_positions.insert(id, MoveToPosition { path: path, speed: speed });
                                 turn.walk(c);
                             }
                         } else {
                             if let Some(entity) = equipment.active_item {
                                 if let Some(item_stat) = data.item_stats.get(entity) {
-                                    let characters = maps.collect_characters_with_ray(p0, p1, item_stat.range);
-                                    if let Some(target) = characters.front() {
+                                    let path = maps.projectile_path(p0, p1, item_stat.range);
+                                    let impact = maps.impact(p0, p1, item_stat.range);
+                                    let hit = path.iter().cloned()
+                                        .find(|c| !maps.collect_characters_on_path(&[*c]).is_empty());
+                                    let mut targets = hit.map_or(vec![], |c| maps.collect_characters_on_path(&[c]));
+                                    let mut changed = false;
+                                    let mut detonated = false;
+                                    if item_stat.blast > 0 {
+                                        // it goes off at whoever or whatever it hits first
+                                        if let Some(center) = hit.or(impact).or(path.last().cloned()) {
+                                            targets = maps.collect_characters_in_radius(center, item_stat.blast as f32);
+                                            changed = maps.blast(center, item_stat.blast, item_stat.damage);
+                                            detonated = true;
+                                        }
+                                    } else {
+                                        targets.truncate(1);
+                                        let firearm = data.items.get(entity).map_or(false, |i| i.is_firearm());
+                                        if let (true, None, Some(wall)) = (firearm, hit, impact) {
+                                            changed = maps.shoot(wall, item_stat.damage);
+                                        }
+                                    }
+                                    if detonated {
+                                        // thrown away, the loop reset puts it back where it was found
+                                        if let Some(inventory) = data.inventories.get_mut(id) {
+                                            inventory.items.retain(|i| *i != entity);
+                                        }
+                                        equipment.unequip(entity);
+                                    }
+                                    for target in targets.iter() {
                                         if let Some(character_stat) = data.char_stats.get_mut(*target) {
                                             let damage = character_stat.apply_damage(item_stat);
                                             data.log.log(LogEvent::DidDamage(id, *target, damage));
-                                            turn.fight();
-                                            turn.action_done();
+                                            if let Some(faction) = data.factions.get(*target) {
+                                                data.relations.provoke(faction.instance, id);
+                                            }
+                                            if let Some(awareness) = data.awareness.get_mut(*target) {
+                                                awareness.alert();
+                                            }
                                         }
                                     }
+                                    if changed {
+                                        data.state.fov_needs_update = true;
+                                    }
+                                    if changed || detonated || !targets.is_empty() {
+                                        data.noises.emit(p0, *level, item_stat.noise);
+                                        turn.fight();
+                                        turn.action_done();
+                                    }
                                 }
                             }
                         }
@@ -108,43 +176,56 @@ impl PlayerController {
     }
 
     fn process_free(&self, data: &mut PlayerControllerData) {
-        if let Some((id, p, level, _, _)) = (&*data.entities, &data.positions, &data.levels, &data.players, &data.actives).join().next() {
-            let maps = data.tower.get(level).unwrap();
+        if let Some((id, p, level, _, _, _)) = (&*data.entities, &data.positions, &data.levels, &data.players, &data.actives, !&data.remotes).join().next() {
+            let maps = data.tower.get_mut(level).unwrap();
             let p0 = (p.x as i32, p.y as i32);
+            let speed = speed(data.sneaking.get(id).is_some());
             if data.input.is_mouse_pressed() {
                 let pos_trans = data.viewport.inv_transform(data.input.mouse_pos);
-                if let Some(p1) = maps.screen_to_map(pos_trans) {
+                if let Some(p1) = maps.within(pos_trans) {
                     if data.viewport.visible(pos_trans) {
-                        // set the position to the middle of the cell to avoid twitching.
-                        let path = maps.find_path(&id, p0, p1);
-                        data.move_to_positions.insert(id, MoveToPosition {
-                            path: path,
-                            speed: PLAYER_SPEED
-                        });
+                        if data.input.ctrl {
+                            // walked to after the current path
+                            if let Some(orders) = data.orders.get_mut(id) {
+                                orders.queue.push_back(p1);
+                            }
+                        } else {
+                            if let Some(orders) = data.orders.get_mut(id) {
+                                orders.queue.clear();
+                            }
+                            // set the position to the middle of the cell to avoid twitching.
+                            let path = maps.find_path(&id, p0, p1);
+                            data.move_to_positions.insert(id, MoveToPosition {
+                                path: path,
+                                speed: speed
+                            });
+                        }
                     }
                 }
             } else {
                 let delta = get_delta(&data.input);
                 if delta.x != 0.0 || delta.y != 0.0 {
                     let delta_time = data.time.delta_time.subsec_nanos() as f32 / 1.0e9;
-                    let np = *p + mul(delta.norm(), delta_time * PLAYER_SPEED);
+                    let np = *p + mul(delta.norm(), delta_time * speed);
                     let mut path = VecDeque::new();
                     path.push_back(np);
                     data.move_to_positions.insert(id, MoveToPosition {
                         path: path,
-                        speed: PLAYER_SPEED
+                        speed: speed
                     });
                 }
 
                 if data.input.is_char_pressed('e') {
-                    let targets = maps.collect_characters_with_shape(
-                        Rect::new(p0.0 - 1, p0.1 - 1, 3, 3));
+                    let targets = maps.collect_characters_with_shape(reach(p0));
+                    let (interactables, dialogues) = (&data.interactables, &data.dialogues);
 
                     let first_interactable_id = targets.into_iter()
-                        .filter(|i| data.interactables.get(*i).is_some())
+                        .filter(|i| interactables.get(*i).is_some() || dialogues.get(*i).is_some())
                         .next();
                     if let Some(target_id) = first_interactable_id {
                         data.interactions.insert(target_id, Interaction { actor: id });
+                    } else if use_door(maps, p0, keycard(data.equipments.get(id), &data.items)) {
+                        data.state.fov_needs_update = true;
                     }
                 }
             }
@@ -152,7 +233,55 @@ impl PlayerController {
     }
 }
 
+// the level of the keycard in hand
+pub fn keycard(equipment: Option<&Equipment>, items: &ReadStorage<Item>) -> Option<i32> {
+    match equipment.and_then(|e| e.active_item).and_then(|i| items.get(i)).map(|i| i.instance) {
+        Some(ItemInstance::KeyCard(level)) => Some(level),
+        _ => None,
+    }
+}
+
+// opens or closes the first door within reach, a locked one only with a keycard
+pub fn use_door(maps: &mut Maps, p: (i32, i32), keycard: Option<i32>) -> bool {
+    let door = reach(p).into_iter().find(|c| maps.door(*c).is_some());
+    door.map_or(false, |c| maps.toggle_door(c) || keycard.map_or(false, |level| maps.unlock_door(c, level)))
+}
+
+pub fn pick_up(maps: &mut Maps, inventory: &mut Inventory, positions: &mut WriteStorage<Position>, p: Position) {
+    if let Some(entry) = maps.pop(Map::Item, (p.x as i32, p.y as i32)) {
+        inventory.push(entry.entity);
+        positions.remove(entry.entity);
+    }
+}
+
+pub fn drop_item(maps: &mut Maps, inventory: &mut Inventory, positions: &mut WriteStorage<Position>, p: Position) {
+    if let Some(item_id) = inventory.pop() {
+        maps.push(Map::Item, &item_id, (p.x as i32, p.y as i32));
+        maps.set_blocking(Map::Item, &item_id, false);
+        maps.set_sight_blocking(Map::Item, &item_id, false);
+        positions.insert(item_id, p);
+    }
+}
+
+pub fn toggle_sneaking(id: Entity, sneaking: &mut WriteStorage<Sneaking>) {
+    if sneaking.remove(id).is_none() {
+        sneaking.insert(id, Sneaking);
+    }
+}
+
+pub fn select_item(digit: i32, inventory: &Inventory, equipment: &mut Equipment, items: &ReadStorage<Item>) {
+    if let Some(item) = inventory.get(((digit + 9) % 10) as usize) {
+        if items.get(*item).map_or(false, |i| i.is_clothing()) {
+            // selecting the worn clothing again takes it off
+            equipment.clothing = if equipment.clothing == Some(*item) { None } else { Some(*item) };
+        } else {
+            equipment.active_item = Some(*item);
+        }
+    }
+}
+
 const PLAYER_SPEED: f32 = 4.0;
+const SNEAK_SPEED_FACTOR: f32 = 0.5;
 
 impl<'a> System<'a> for PlayerController {
     type SystemData = PlayerControllerData<'a>;
@@ -164,27 +293,158 @@ impl<'a> System<'a> for PlayerController {
             self.process_free(&mut data);
         }
 
-        if let Some((id, inventory, equipment, level, _, _)) = (&*data.entities, &mut data.inventories, &mut data.equipments, &data.levels, &data.players, &data.actives).join().next() {
+        if let Some((id, inventory, equipment, level, _, _, _)) = (&*data.entities, &mut data.inventories, &mut data.equipments, &data.levels, &data.players, &data.actives, !&data.remotes).join().next() {
             let p = data.positions.get(id).unwrap().clone();
             let maps = data.tower.get_mut(level).unwrap();
             // player interaction
             if data.input.is_char_pressed('p') {
-                if let Some(entry) = maps.pop(Map::Item, (p.x as i32, p.y as i32)) {
-                    inventory.push(entry.0);
-                    data.positions.remove(entry.0);
-                }
+                pick_up(maps, inventory, &mut data.positions, p);
             } else if data.input.is_char_pressed('d') {
-                if let Some(item_id) = inventory.pop() {
-                    maps.push(Map::Item, &item_id, (p.x as i32, p.y as i32));
-                    maps.set_blocking(Map::Item, &item_id, (p.x as i32, p.y as i32), false);
-                    maps.set_sight_blocking(Map::Item, &item_id, (p.x as i32, p.y as i32), false);
-                    data.positions.insert(item_id, p);
+                drop_item(maps, inventory, &mut data.positions, p);
+            } else if data.input.is_char_pressed('c') {
+                toggle_sneaking(id, &mut data.sneaking);
+            } else if data.input.is_char_pressed('f') || data.input.is_char_pressed('x')
+                || data.input.is_char_pressed('g') {
+                // orders go to everybody else: f follow, g guard, x hold
+                for (other, _, orders, other_p) in (&*data.entities, &data.players,
+                                                    &mut data.orders, &data.positions).join() {
+                    if other == id {
+                        continue;
+                    }
+                    orders.order = if data.input.is_char_pressed('f') {
+                        Order::Follow(id)
+                    } else if data.input.is_char_pressed('x') {
+                        Order::Hold
+                    } else {
+                        Order::Guard((other_p.x as i32, other_p.y as i32))
+                    };
+                    orders.queue.clear();
                 }
             } else if let Some(digit) = data.input.pressed_digit {
-                if let Some(item) = inventory.get(((digit + 9) % 10) as usize) {
-                    equipment.active_item = Some(*item);
-                }
+                select_item(digit, inventory, equipment, &data.items);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{ Duration, Instant };
+    use specs::{ World, Entity, RunNow };
+    use tcod::input::{ Mouse };
+
+    use components::common::{ Active, CharacterStats, InTurn, ItemStats, MoveToPosition };
+    use components::faction::{ Faction };
+    use components::interaction::{ Interactable, Interaction };
+    use components::inventory::{ Inventory };
+    use components::item::{ Item, ItemInstance };
+    use components::npc::{ Dialogue };
+    use components::player::{ Player, Remote, Equipment, Orders };
+    use components::space::{ Position, Level, Viewport };
+    use components::stealth::{ Sneaking, Awareness };
+    use engine::input_handler::{ InputHandler };
+    use engine::tcod::{ VIEW_WIDTH, VIEW_HEIGHT };
+    use engine::time::{ Time };
+    use event_log::{ EventLog };
+    use factions::{ FactionRelations };
+    use floor_plan::{ FloorPlan };
+    use game_state::{ GameState };
+    use maps::{ Map };
+    use noise::{ Noises };
+    use systems::player_controller::{ PlayerController };
+    use tower::{ Tower };
+
+    // the player stands in the big room, two cells east of its west wall
+    fn armed(instance: ItemInstance) -> (World, Entity, Entity) {
+        let mut world = World::new();
+        world.register::<Player>();
+        world.register::<Active>();
+        world.register::<Remote>();
+        world.register::<Level>();
+        world.register::<Position>();
+        world.register::<Interaction>();
+        world.register::<Interactable>();
+        world.register::<Dialogue>();
+        world.register::<Inventory>();
+        world.register::<Item>();
+        world.register::<MoveToPosition>();
+        world.register::<Equipment>();
+        world.register::<Orders>();
+        world.register::<CharacterStats>();
+        world.register::<ItemStats>();
+        world.register::<InTurn>();
+        world.register::<Sneaking>();
+        world.register::<Faction>();
+        world.register::<Awareness>();
+
+        let mut tower = Tower::new();
+        tower.add_floor(Level::Tower(0), &FloorPlan::parse(include_str!("../../data/tower0.map")).unwrap());
+        world.add_resource(tower);
+        world.add_resource(GameState { is_turn_based: true, ..GameState::default() });
+        world.add_resource(EventLog::default());
+        world.add_resource(Noises::default());
+        world.add_resource(FactionRelations::default());
+        world.add_resource(Viewport::new(0, 0, VIEW_WIDTH, VIEW_HEIGHT));
+        world.add_resource(Time {
+            delta_time: Duration::from_millis(20),
+            fixed_step: Duration::from_millis(20),
+            last_fixed_update: Instant::now(),
+        });
+        // a ctrl click west of the wall
+        world.add_resource(InputHandler {
+            mouse: Mouse { lbutton_pressed: true, ..Default::default() },
+            mouse_pos: (18, 22),
+            ctrl: true,
+            ..Default::default()
+        });
+
+        let i = Item { instance: instance };
+        let item = world.create_entity().with(i.get_stats().unwrap()).with(i).build();
+        let mut inventory = Inventory::new();
+        inventory.push(item);
+        let mut equipment = Equipment::new();
+        equipment.active_item = Some(item);
+        let player = world.create_entity()
+            .with(Player)
+            .with(Active)
+            .with(Position { x: 22.5, y: 22.5 })
+            .with(Level::Tower(0))
+            .with(InTurn::default())
+            .with(inventory)
+            .with(equipment)
+            .build();
+        {
+            let mut tower = world.write_resource::<Tower>();
+            let maps = tower.get_mut(&Level::Tower(0)).unwrap();
+            maps.push(Map::Character, &player, (22, 22));
+            // shots only fly through what the player has seen
+            maps.track_fov(0);
+            maps.discover(0, &[(18, 22), (19, 22), (20, 22), (21, 22), (22, 22)]);
+        }
+        (world, player, item)
+    }
+
+    fn west_wall_is_holed(world: &World) -> bool {
+        !world.read_resource::<Tower>().get(&Level::Tower(0)).unwrap().is_sight_blocking((20, 22))
+    }
+
+    #[test]
+    fn only_firearms_shoot_through_walls() {
+        let (world, _, _) = armed(ItemInstance::Shuriken);
+        PlayerController.run_now(&world.res);
+        assert!(!west_wall_is_holed(&world));
+
+        let (world, _, _) = armed(ItemInstance::DartGun);
+        PlayerController.run_now(&world.res);
+        assert!(west_wall_is_holed(&world));
+    }
+
+    #[test]
+    fn grenades_are_used_up() {
+        let (world, player, grenade) = armed(ItemInstance::Grenade);
+        PlayerController.run_now(&world.res);
+        assert!(!world.read::<Inventory>().get(player).unwrap().items.contains(&grenade));
+        assert!(world.read::<Equipment>().get(player).unwrap().active_item.is_none());
+        assert!(world.read::<InTurn>().get(player).unwrap().action_points == 0);
+    }
+}
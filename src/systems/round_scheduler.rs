@@ -2,11 +2,16 @@ use specs::{ System, ReadStorage, Fetch, FetchMut, Entities, WriteStorage, Join
 
 use tcod::input::{ KeyCode };
 use game_state::{ GameState };
+use game_stats::{ GameStats };
 use event_log::{ EventLog, LogEvent };
 
-use components::player::{ Player };
+use components::player::{ Player, Remote };
 use components::common::{ Active, InTurn, InTurnState, WaitForTurn, MoveToPosition };
 use engine::input_handler::{ InputHandler };
+use engine::time::{ Time };
+
+// how much of the loop passes in a round of turns
+const ROUND_DURATION: f32 = 1.0;
 
 pub struct RoundScheduler;
 unsafe impl Sync for RoundScheduler {}
@@ -15,13 +20,16 @@ unsafe impl Sync for RoundScheduler {}
 pub struct RoundSchedulerData<'a> {
     entities: Entities<'a>,
     players: ReadStorage<'a, Player>,
+    remotes: ReadStorage<'a, Remote>,
     actives: WriteStorage<'a, Active>,
     in_turns: WriteStorage<'a, InTurn>,
     waits: WriteStorage<'a, WaitForTurn>,
     move_to_positions: ReadStorage<'a, MoveToPosition>,
     log: FetchMut<'a, EventLog>,
     state: FetchMut<'a, GameState>,
+    stats: FetchMut<'a, GameStats>,
     input: Fetch<'a, InputHandler>,
+    time: Fetch<'a, Time>,
 }
 
 impl<'a> System<'a> for RoundScheduler {
@@ -78,28 +86,29 @@ impl<'a> System<'a> for RoundScheduler {
 
             // if no one is in turn, put all in turn
             if data.in_turns.join().next().is_none() {
+                data.stats.advance(ROUND_DURATION);
                 for (id, _) in (&*data.entities, &data.waits).join() {
                     data.in_turns.insert(id, InTurn::default());
                 }
                 data.waits.clear();
             }
 
-            // if active became waiting, activate first in turn
+            // if active became waiting, activate first local player in turn
             if active_became_waiting {
-                if let Some ((id, _)) = (&*data.entities, &data.in_turns).join().next() {
-                    if data.players.get(id).is_some() {
-                        data.actives.clear();
-                        data.actives.insert(id, Active);
-                    }
+                if let Some((id, _, _, _)) = (&*data.entities, &data.in_turns, &data.players, !&data.remotes).join().next() {
+                    data.actives.clear();
+                    data.actives.insert(id, Active);
                 }
             }
+        } else {
+            data.stats.advance(data.time.delta_time.subsec_nanos() as f32 / 1.0e9);
         }
 
         if data.input.is_key_pressed(KeyCode::Tab) {
-            // rotate players
+            // rotate players, the remote one is played elsewhere
             let mut take_first = true;
             let mut active_player_seen = false;
-            for (id, _) in (&*data.entities, &data.players).join() {
+            for (id, _, _) in (&*data.entities, &data.players, !&data.remotes).join() {
                 if active_player_seen {
                     data.actives.insert(id, Active);
                     take_first = false;
@@ -110,7 +119,7 @@ impl<'a> System<'a> for RoundScheduler {
                 }
             }
             if take_first {
-                if let Some ((id, _)) = (&*data.entities, &data.players).join().next() {
+                if let Some((id, _, _)) = (&*data.entities, &data.players, !&data.remotes).join().next() {
                     data.actives.insert(id, Active);
                 }
             }
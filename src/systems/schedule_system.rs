@@ -0,0 +1,81 @@
+use specs::{ System, ReadStorage, Fetch, FetchMut, Entities, WriteStorage, Join };
+
+use components::common::{ MoveToPosition };
+use components::schedule::{ Schedule };
+use components::space::{ Position, Level };
+use components::stealth::{ Awareness };
+
+use game_state::{ GameState };
+use game_stats::{ GameStats };
+use tower::{ Tower };
+use maps::{ Map };
+
+const ROUTINE_SPEED: f32 = 1.5;
+// npcs stop next to an occupied target
+const ARRIVAL_DISTANCE: i32 = 1;
+
+pub struct ScheduleSystem;
+unsafe impl Sync for ScheduleSystem {}
+
+#[derive(SystemData)]
+pub struct ScheduleSystemData<'a> {
+    entities: Entities<'a>,
+    awareness: ReadStorage<'a, Awareness>,
+    schedules: WriteStorage<'a, Schedule>,
+    positions: WriteStorage<'a, Position>,
+    levels: WriteStorage<'a, Level>,
+    move_to_positions: WriteStorage<'a, MoveToPosition>,
+    stats: Fetch<'a, GameStats>,
+    state: FetchMut<'a, GameState>,
+    tower: FetchMut<'a, Tower>,
+}
+
+impl<'a> System<'a> for ScheduleSystem {
+    type SystemData = ScheduleSystemData<'a>;
+
+    fn run(&mut self, mut data: ScheduleSystemData) {
+        let elapsed = data.stats.elapsed();
+        for (id, schedule, p, level) in (&*data.entities, &mut data.schedules,
+                                         &mut data.positions, &mut data.levels).join() {
+            // alerted npcs drop their routine, npcs on the move finish what they do first
+            if data.awareness.get(id).map_or(false, |a| a.is_alerted())
+                || data.move_to_positions.get(id).is_some() {
+                continue;
+            }
+            let (index, errand) = match schedule.current(elapsed) {
+                Some(current) => current,
+                None => continue,
+            };
+
+            let p0 = (p.x as i32, p.y as i32);
+            if *level != errand.level {
+                // riding the elevator
+                let target = Position { x: errand.target.0 as f32 + 0.5, y: errand.target.1 as f32 + 0.5 };
+                if let Some(maps) = data.tower.get_mut(level) {
+                    maps.remove(Map::Character, &id);
+                }
+                if let Some(maps) = data.tower.get_mut(&errand.level) {
+                    maps.push(Map::Character, &id, errand.target);
+                }
+                *p = target;
+                *level = errand.level;
+                data.state.fov_needs_update = true;
+                schedule.finish(index);
+                continue;
+            }
+
+            let (dx, dy) = (errand.target.0 - p0.0, errand.target.1 - p0.1);
+            if dx.abs() <= ARRIVAL_DISTANCE && dy.abs() <= ARRIVAL_DISTANCE {
+                schedule.finish(index);
+                continue;
+            }
+            let path = data.tower.get(level).unwrap().find_npc_path(&id, p0, errand.target);
+            if path.len() > 0 {
+                data.move_to_positions.insert(id, MoveToPosition { path: path, speed: ROUTINE_SPEED });
+            } else {
+                // the way is blocked, maybe by the players. Wait for the next errand
+                schedule.finish(index);
+            }
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use std::collections::{ HashSet };
+use std::f32::consts::{ PI };
+use specs::{ System, ReadStorage, Fetch, FetchMut, Entities, WriteStorage, Join, Entity };
+
+use components::appearance::{ Renderable };
+use components::common::{ CharacterStats, ItemStats };
+use components::faction::{ FactionInstance };
+use components::interaction::{ Interactable };
+use components::item::{ Item };
+use components::npc::{ Npc };
+use components::player::{ Player, Equipment };
+use components::space::{ Position, Level, Vector };
+use components::stealth::{ Vision, Awareness, Sneaking };
+use engine::time::{ Time };
+
+use event_log::{ EventLog, LogEvent };
+use factions::{ FactionRelations, suspicion };
+use game_state::{ GameState };
+use game_stats::{ GameStats };
+use noise::{ Noises };
+use tower::{ Tower };
+use maps::{ Map };
+use systems::detection_system::{ appearance };
+
+const CAMERA_RANGE: i32 = 10;
+const CAMERA_ANGLE: f32 = PI / 3.0;
+// cameras swing this far to each side of their heading
+const CAMERA_SWEEP: f32 = PI / 4.0;
+const CAMERA_SWEEP_SPEED: f32 = 0.5;
+// cameras cannot make out anyone in the dark
+const CAMERA_MIN_LIGHT: f32 = 0.3;
+
+const TURRET_RANGE: i32 = 8;
+const TURRET_RELOAD: f32 = 1.0;
+const TURRET_DAMAGE: f32 = 15.0;
+const TURRET_NOISE: f32 = 10.0;
+
+pub struct SecuritySystem {
+    reload: f32,
+}
+unsafe impl Sync for SecuritySystem {}
+
+impl SecuritySystem {
+    pub fn new() -> Self {
+        SecuritySystem { reload: TURRET_RELOAD }
+    }
+}
+
+#[derive(SystemData)]
+pub struct SecuritySystemData<'a> {
+    entities: Entities<'a>,
+    players: ReadStorage<'a, Player>,
+    npcs: ReadStorage<'a, Npc>,
+    positions: ReadStorage<'a, Position>,
+    levels: ReadStorage<'a, Level>,
+    equipments: ReadStorage<'a, Equipment>,
+    items: ReadStorage<'a, Item>,
+    sneaking: ReadStorage<'a, Sneaking>,
+    interactables: WriteStorage<'a, Interactable>,
+    renderables: WriteStorage<'a, Renderable>,
+    awareness: WriteStorage<'a, Awareness>,
+    char_stats: WriteStorage<'a, CharacterStats>,
+    time: Fetch<'a, Time>,
+    stats: Fetch<'a, GameStats>,
+    state: FetchMut<'a, GameState>,
+    log: FetchMut<'a, EventLog>,
+    noises: FetchMut<'a, Noises>,
+    tower: FetchMut<'a, Tower>,
+    relations: Fetch<'a, FactionRelations>,
+}
+
+fn camera_vision(heading: f32, elapsed: f32) -> Vision {
+    // the sweep only depends on the loop clock, so cameras move the same way every loop
+    let facing = heading + CAMERA_SWEEP * (elapsed * CAMERA_SWEEP_SPEED).sin();
+    Vision::new(Vector { x: facing.cos(), y: facing.sin() }, CAMERA_ANGLE, CAMERA_RANGE)
+}
+
+impl SecuritySystem {
+    fn trigger_lockdown(&self, level: Level, data: &mut SecuritySystemData) {
+        data.state.lockdowns.insert(level);
+        data.state.fov_needs_update = true;
+        data.log.log(LogEvent::Lockdown(level));
+
+        let maps = data.tower.get_mut(&level).unwrap();
+        maps.lock_doors();
+        for (id, interactable, _, l) in (&*data.entities, &mut data.interactables,
+                                         &data.positions, &data.levels).join() {
+            if *l == level {
+                interactable.lockdown();
+                data.renderables.insert(id, interactable.get_renderable());
+                maps.set_blocking(Map::Character, &id, interactable.is_blocking());
+                maps.set_sight_blocking(Map::Character, &id, interactable.is_sight_blocking());
+            }
+        }
+        for (npc, l, awareness) in (&data.npcs, &data.levels, &mut data.awareness).join() {
+            if *l == level && npc.is_guard() {
+                awareness.alert();
+            }
+        }
+    }
+
+    fn fire_turrets(&self, targets: &[(Entity, Position, Level)], disarmed: &HashSet<Level>,
+                    data: &mut SecuritySystemData) {
+        let mut shots = vec![];
+        for (id, interactable, p, level) in (&*data.entities, &data.interactables,
+                                             &data.positions, &data.levels).join() {
+            if !interactable.has_tag("shooting") || disarmed.contains(level) {
+                continue;
+            }
+            let maps = data.tower.get(level).unwrap();
+            let p0 = (p.x as i32, p.y as i32);
+            let target = targets.iter()
+                .filter(|&&(_, t, l)| l == *level && (t - *p).length() <= TURRET_RANGE as f32)
+                .filter(|&&(_, t, _)| maps.is_in_line_of_sight(p0, (t.x as i32, t.y as i32)))
+                .min_by_key(|&&(_, t, _)| (t - *p).length() as i32)
+                .map(|&(target, _, _)| target);
+            if let Some(target) = target {
+                shots.push((id, target, p0, *level));
+            }
+        }
+
+        let bullet = ItemStats { damage: TURRET_DAMAGE, range: TURRET_RANGE, noise: TURRET_NOISE, blast: 0 };
+        for (id, target, p0, level) in shots {
+            if let Some(character_stat) = data.char_stats.get_mut(target) {
+                let damage = character_stat.apply_damage(&bullet);
+                data.log.log(LogEvent::DidDamage(id, target, damage));
+                data.noises.emit(p0, level, bullet.noise);
+            }
+        }
+    }
+}
+
+impl<'a> System<'a> for SecuritySystem {
+    type SystemData = SecuritySystemData<'a>;
+
+    fn run(&mut self, mut data: SecuritySystemData) {
+        let delta_time = data.time.delta_time.subsec_nanos() as f32 / 1.0e9;
+        let elapsed = data.stats.elapsed();
+
+        let targets = (&*data.entities, &data.players, &data.positions, &data.levels).join()
+            .map(|(id, _, p, level)| (id, *p, *level))
+            .collect::<Vec<(Entity, Position, Level)>>();
+
+        let mut disarmed = HashSet::new();
+        for (interactable, level) in (&data.interactables, &data.levels).join() {
+            if interactable.has_tag("disarmed") {
+                disarmed.insert(*level);
+            }
+        }
+
+        let mut triggered = HashSet::new();
+        for (interactable, p, level) in (&data.interactables, &data.positions, &data.levels).join() {
+            if interactable.has_tag("camera") {
+                if disarmed.contains(level) || data.state.lockdowns.contains(level) {
+                    continue;
+                }
+                let maps = data.tower.get(level).unwrap();
+                let vision = camera_vision(interactable.heading, elapsed);
+                let p0 = (p.x as i32, p.y as i32);
+                // cameras watch for whoever owns the area they hang in, like a guard would
+                let owner = maps.area_at(p0).and_then(FactionInstance::owner);
+                let spotted = targets.iter()
+                    .filter(|&&(_, _, l)| l == *level)
+                    .any(|&(player, t, _)| {
+                        let p1 = (t.x as i32, t.y as i32);
+                        let (clothing, out_of_character) = data.equipments.get(player)
+                            .map_or((None, false), |e| appearance(player, e, &data.items, &data.sneaking));
+                        let minds = owner.map_or(1.0, |f| suspicion(f, true, data.relations.attitude(f, player),
+                                                                    maps.area_at(p1), &data.state.events,
+                                                                    clothing, out_of_character));
+                        minds > 0.0 && vision.is_in_cone(p0, p1) && maps.is_in_line_of_sight(p0, p1)
+                            && maps.light_level(p1) >= CAMERA_MIN_LIGHT
+                    });
+                if spotted {
+                    triggered.insert(*level);
+                }
+            }
+        }
+        for level in triggered {
+            self.trigger_lockdown(level, &mut data);
+        }
+
+        self.reload -= delta_time;
+        if self.reload <= 0.0 {
+            self.reload = TURRET_RELOAD;
+            self.fire_turrets(&targets, &disarmed, &mut data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{ HashSet };
+    use std::time::{ Duration, Instant };
+    use specs::{ World, Entity, RunNow, SystemData };
+
+    use components::appearance::{ Renderable };
+    use components::common::{ CharacterStats };
+    use components::faction::{ FactionInstance };
+    use components::interaction::{ Interactable };
+    use components::item::{ Item, ItemInstance };
+    use components::npc::{ Npc, NpcInstance };
+    use components::player::{ Player, Equipment };
+    use components::space::{ Position, Level };
+    use components::stealth::{ Awareness, Sneaking };
+    use engine::time::{ Time };
+    use event_log::{ EventLog };
+    use factions::{ FactionRelations };
+    use floor_plan::{ FloorPlan };
+    use game_state::{ GameState };
+    use game_stats::{ GameStats };
+    use interactables::{ InteractableRegistry };
+    use noise::{ Noises };
+    use systems::security_system::{ SecuritySystem, SecuritySystemData };
+    use tile_map::{ DoorState };
+    use tower::{ Tower };
+
+    const FLOOR: Level = Level::Tower(0);
+
+    fn tower_world() -> World {
+        let mut world = World::new();
+        world.register::<Player>();
+        world.register::<Npc>();
+        world.register::<Position>();
+        world.register::<Level>();
+        world.register::<Equipment>();
+        world.register::<Item>();
+        world.register::<Sneaking>();
+        world.register::<Interactable>();
+        world.register::<Renderable>();
+        world.register::<Awareness>();
+        world.register::<CharacterStats>();
+
+        let mut tower = Tower::new();
+        tower.add_floor(FLOOR, &FloorPlan::parse(include_str!("../../data/tower0.map")).unwrap());
+        world.add_resource(tower);
+        world.add_resource(Time {
+            delta_time: Duration::from_millis(100),
+            fixed_step: Duration::from_millis(100),
+            last_fixed_update: Instant::now(),
+        });
+        world.add_resource(GameStats::default());
+        world.add_resource(GameState::default());
+        world.add_resource(EventLog::default());
+        world.add_resource(Noises::default());
+        world.add_resource(FactionRelations::default());
+        world
+    }
+
+    fn place(world: &mut World, kind: &str, x: f32, y: f32, heading: f32) -> Entity {
+        let registry = InteractableRegistry::parse(include_str!("../../data/interactables.txt")).unwrap();
+        let interactable = Interactable::new(registry.get(kind).unwrap(), 0).facing(heading.to_radians());
+        world.create_entity()
+            .with(Position { x: x, y: y })
+            .with(FLOOR)
+            .with(interactable)
+            .build()
+    }
+
+    fn player(world: &mut World, x: f32, y: f32, level: Level, clothing: Option<ItemInstance>) -> Entity {
+        let clothing = clothing.map(|instance| world.create_entity().with(Item { instance: instance }).build());
+        let mut equipment = Equipment::new();
+        equipment.clothing = clothing;
+        world.create_entity()
+            .with(Player)
+            .with(Position { x: x, y: y })
+            .with(level)
+            .with(equipment)
+            .with(CharacterStats { health: 100.0, max_health: 100.0 })
+            .build()
+    }
+
+    fn is_locked_down(world: &World) -> bool {
+        world.read_resource::<GameState>().lockdowns.contains(&FLOOR)
+    }
+
+    #[test]
+    fn cameras_spot_intruders_but_not_disguises() {
+        // the camera in the corner of the robotics labs looks to the north west
+        let mut world = tower_world();
+        place(&mut world, "camera", 33.0, 33.0, 225.0);
+        let guard = player(&mut world, 30.0, 30.0, FLOOR, Some(ItemInstance::Uniform(FactionInstance::Kayaba)));
+        SecuritySystem::new().run_now(&world.res);
+        assert!(!is_locked_down(&world));
+
+        // sneaking past in uniform gives the disguise away
+        world.write::<Sneaking>().insert(guard, Sneaking);
+        SecuritySystem::new().run_now(&world.res);
+        assert!(is_locked_down(&world));
+
+        let mut world = tower_world();
+        place(&mut world, "camera", 33.0, 33.0, 225.0);
+        player(&mut world, 31.0, 30.0, FLOOR, Some(ItemInstance::Uniform(FactionInstance::Nichirei)));
+        SecuritySystem::new().run_now(&world.res);
+        assert!(is_locked_down(&world));
+    }
+
+    #[test]
+    fn cameras_do_not_look_behind_them() {
+        let mut world = tower_world();
+        place(&mut world, "camera", 33.0, 33.0, 45.0);
+        player(&mut world, 30.0, 30.0, FLOOR, None);
+        SecuritySystem::new().run_now(&world.res);
+        assert!(!is_locked_down(&world));
+    }
+
+    #[test]
+    fn lockdowns_arm_turrets_and_alert_guards() {
+        let mut world = tower_world();
+        let turret = place(&mut world, "turret", 23.0, 33.0, 0.0);
+        let guard = world.create_entity()
+            .with(Npc { instance: NpcInstance::Guard })
+            .with(FLOOR)
+            .with(Awareness::new())
+            .build();
+        let elsewhere = world.create_entity()
+            .with(Npc { instance: NpcInstance::Guard })
+            .with(Level::Tower(1))
+            .with(Awareness::new())
+            .build();
+        // the locked door of the office was opened with a keycard
+        world.write_resource::<Tower>().get_mut(&FLOOR).unwrap().unlock_door((25, 21), 3);
+        {
+            let mut data = SecuritySystemData::fetch(&world.res, 0);
+            SecuritySystem::new().trigger_lockdown(FLOOR, &mut data);
+        }
+        assert!(is_locked_down(&world));
+        assert!(world.read::<Interactable>().get(turret).unwrap().has_tag("shooting"));
+        assert!(world.read::<Awareness>().get(guard).unwrap().is_alerted());
+        assert!(!world.read::<Awareness>().get(elsewhere).unwrap().is_alerted());
+        assert!(world.read_resource::<Tower>().get(&FLOOR).unwrap().door((25, 21)) == Some(DoorState::Locked(3)));
+    }
+
+    #[test]
+    fn turrets_shoot_the_nearest_player_in_sight() {
+        let mut world = tower_world();
+        let turret = place(&mut world, "turret", 23.0, 33.0, 0.0);
+        world.write::<Interactable>().get_mut(turret).unwrap().lockdown();
+        // the nearest one stands behind the west wall, the next one on another floor
+        let hidden = player(&mut world, 19.0, 33.0, FLOOR, None);
+        let upstairs = player(&mut world, 24.0, 33.0, Level::Tower(1), None);
+        let target = player(&mut world, 28.0, 33.0, FLOOR, None);
+        let far = player(&mut world, 30.0, 33.0, FLOOR, None);
+
+        let targets = vec![(hidden, Position { x: 19.0, y: 33.0 }, FLOOR),
+                           (upstairs, Position { x: 24.0, y: 33.0 }, Level::Tower(1)),
+                           (target, Position { x: 28.0, y: 33.0 }, FLOOR),
+                           (far, Position { x: 30.0, y: 33.0 }, FLOOR)];
+        {
+            let mut data = SecuritySystemData::fetch(&world.res, 0);
+            SecuritySystem::new().fire_turrets(&targets, &HashSet::new(), &mut data);
+        }
+        let health = |id: Entity| world.read::<CharacterStats>().get(id).unwrap().health;
+        assert!(health(target) < 100.0);
+        assert!(health(hidden) == 100.0 && health(upstairs) == 100.0 && health(far) == 100.0);
+
+        // a disarmed floor holds its fire
+        {
+            let mut data = SecuritySystemData::fetch(&world.res, 0);
+            let disarmed = [FLOOR].iter().cloned().collect();
+            SecuritySystem::new().fire_turrets(&targets, &disarmed, &mut data);
+        }
+        assert!(health(far) == 100.0 && health(target) == 85.0);
+    }
+}
@@ -37,7 +37,7 @@ impl<'a> System<'a> for StatsUpdater {
         for (id, pos, level) in graveyard {
             let p = (pos.x as i32, pos.y as i32);
             let maps = data.tower.get_mut(level).unwrap();
-            maps.remove(Map::Character, &id, p);
+            maps.remove(Map::Character, &id);
             if let Some(inventory) = data.inventories.get_mut(id) {
                 for item in inventory.items.iter() {
                     maps.push(Map::Item, item, p);
@@ -7,6 +7,7 @@ use event_log::{ EventLog, LogEvent };
 use components::player::{ Player, Equipment };
 use components::space::{ Position, Level, Viewport };
 use components::inventory::{ Inventory };
+use components::schedule::{ format_clock };
 use engine::input_handler::{ InputHandler };
 use components::common::{ Active, InTurn, InTurnState, Description, CharacterStats, ItemStats };
 
@@ -57,7 +58,7 @@ impl<'a> System<'a> for UiUpdater {
     fn run(&mut self, mut data: UiUpdaterData) {
         data.tower.clear_highlights();
 
-        data.ui.update("time_left".into(), UiData::Text{ text: data.stats.time_left().to_string() });
+        data.ui.update("time_left".into(), UiData::Text{ text: format_clock(data.stats.time_left().max(0.0)) });
 
         for (id, _, p, level, description, stats, inventory, equipment) in (&*data.entities, &data.players, &data.positions, &data.levels, &data.descriptions, &data.char_stats, &data.inventories, &data.equipments).join() {
             let active = data.actives.get(id);
@@ -84,7 +85,7 @@ impl<'a> System<'a> for UiUpdater {
                         match turn.state {
                             InTurnState::Idle => {
                                 let pos_trans = data.viewport.inv_transform(data.input.mouse_pos);
-                                if let Some(pos) = maps.screen_to_map(pos_trans) {
+                                if let Some(pos) = maps.within(pos_trans) {
                                     if !data.input.ctrl {
                                         // render movement selection highlights
                                         if data.viewport.visible(pos) {
@@ -97,7 +98,10 @@ impl<'a> System<'a> for UiUpdater {
                                     } else {
                                         if let Some(entity) = equipment.active_item {
                                             if let Some(item_stat) = data.item_stats.get(entity) {
-                                                let ray = maps.draw_ray((p.x as i32, p.y as i32), pos, item_stat.range);
+                                                let ray = maps.projectile_path((p.x as i32, p.y as i32), pos, item_stat.range)
+                                                    .into_iter()
+                                                    .map(|c| Position { x: c.0 as f32 + 0.5, y: c.1 as f32 + 0.5 })
+                                                    .collect();
                                                 highlights = Some((colors::LIGHT_RED, ray));
                                             }
                                         }
@@ -144,6 +148,14 @@ impl<'a> System<'a> for UiUpdater {
                                     damage,
                             )
                         }
+                        LogEvent::Spotted(id) => {
+                            format!("{} spotted you!", data.descriptions.get(id)
+                                    .map(|d| d.name.clone())
+                                    .unwrap_or("unknwon".into()))
+                        }
+                        LogEvent::Lockdown(_level) => {
+                            "Alarm! Lockdown!".into()
+                        }
                     }
                 })
                 .take(5)
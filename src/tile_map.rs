@@ -1,3 +1,5 @@
+use std::mem;
+use std::collections::{ HashSet, HashMap };
 use engine::tcod::{ Tcod };
 
 use tcod::colors::{ self, Color };
@@ -6,24 +8,122 @@ use tcod::chars::{ self };
 use components::space::{ Viewport };
 use geometry::{ Shape, Line, Rect };
 
-const MAP_WIDTH: i32 = 80;
-const MAP_HEIGHT: i32 = 43;
+const ROOM_LIGHT: f32 = 1.0;
+const CORRIDOR_LIGHT: f32 = 0.4;
+// even pitch black tiles in view are drawn a bit brighter than remembered ones
+const MIN_SHADE: f32 = 0.25;
+// how much blast damage it takes to break them
+const WALL_DURABILITY: f32 = 100.0;
+const DOOR_DURABILITY: f32 = 40.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DoorState {
+    Open,
+    Closed,
+    // opened by a keycard of at least this level
+    Locked(i32),
+    Broken,
+}
+
+impl DoorState {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "open" => Some(DoorState::Open),
+            "closed" => Some(DoorState::Closed),
+            "locked" => Some(DoorState::Locked(0)),
+            "broken" => Some(DoorState::Broken),
+            // locked doors name their keycard level, e.g. locked:3
+            _ if name.starts_with("locked:") => name[7..].parse().ok().map(DoorState::Locked),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match *self {
+            DoorState::Open => "open".into(),
+            DoorState::Closed => "closed".into(),
+            DoorState::Locked(0) => "locked".into(),
+            DoorState::Locked(level) => format!("locked:{}", level),
+            DoorState::Broken => "broken".into(),
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        match *self {
+            DoorState::Locked(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_blocking(&self) -> bool {
+        *self == DoorState::Closed || self.is_locked()
+    }
+}
+
+// what shots and blasts left of a wall or door
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WallDamage {
+    Intact,
+    Holed,
+    Rubble,
+}
+
+impl WallDamage {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "intact" => Some(WallDamage::Intact),
+            "holed" => Some(WallDamage::Holed),
+            "rubble" => Some(WallDamage::Rubble),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            WallDamage::Intact => "intact",
+            WallDamage::Holed => "holed",
+            WallDamage::Rubble => "rubble",
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 struct Tile {
     blocking: bool,
-    discovered: bool,
     wall: bool,
     room: Option<i32>,
+    door: Option<DoorState>,
+    // the box drawing character of walls and doors, kept up to date by the map
+    glyph: Option<char>,
+    durability: f32,
+    // shot through, it still blocks but can be seen through
+    holed: bool,
+    // what is left of a blown up wall
+    rubble: bool,
+    // light of the ceiling lamps
+    ambient: f32,
+    light: f32,
 }
 
 impl Tile {
     pub fn create(blocking: bool, wall: bool, room: Option<i32>) -> Self {
-        Tile { blocking: blocking, wall: wall, room: room, discovered: false }
+        let durability = if wall { WALL_DURABILITY } else { 0.0 };
+        Tile {
+            blocking: blocking,
+            wall: wall,
+            room: room,
+            door: None,
+            glyph: None,
+            durability: durability,
+            holed: false,
+            rubble: false,
+            ambient: ROOM_LIGHT,
+            light: ROOM_LIGHT,
+        }
     }
 
     pub fn bedrock() -> Self {
-        Tile::create(true, false, None)
+        Tile::create(true, false, None).with_light(0.0)
     }
 
     pub fn wall(room: i32) -> Self {
@@ -34,46 +134,80 @@ impl Tile {
         Tile::create(false, false, Some(room))
     }
 
-    pub fn character(&self) -> Option<char> {
-        if !self.discovered {
+    pub fn door(room: i32, state: DoorState) -> Self {
+        let mut tile = Tile::create(state.is_blocking(), false, Some(room));
+        tile.door = Some(state);
+        tile.durability = DOOR_DURABILITY;
+        tile
+    }
+
+    pub fn rubble(room: i32) -> Self {
+        let mut tile = Tile::floor(room);
+        tile.rubble = true;
+        tile
+    }
+
+    fn is_breakable(&self) -> bool {
+        self.wall || self.door.map_or(false, |d| d != DoorState::Broken)
+    }
+
+    pub fn with_light(mut self, light: f32) -> Self {
+        self.ambient = light;
+        self.light = light;
+        self
+    }
+
+    fn shade(&self) -> f32 {
+        self.light.max(MIN_SHADE).min(1.0)
+    }
+
+    pub fn character(&self, discovered: bool) -> Option<char> {
+        if !discovered {
             return None;
         }
 
-        if self.wall {
-            return Some('#');
+        if self.glyph.is_some() {
+            return self.glyph;
         } else if !self.blocking {
             return Some('.');
         }
         None
     }
 
-    pub fn fg_color(&self, visible: bool) -> Color {
-        if !self.discovered {
+    pub fn fg_color(&self, discovered: bool, visible: bool) -> Color {
+        if !discovered {
             return colors::BLACK;
         }
 
-        if visible {
-            return colors::LIGHTEST_GREY;
+        if visible && self.door.map_or(false, |d| d.is_locked()) {
+            return colors::lerp(colors::DARKEST_RED, colors::LIGHT_RED, self.shade());
+        } else if visible {
+            return colors::lerp(colors::DARKEST_GREY, colors::LIGHTEST_GREY, self.shade());
         } else {
             return colors::DARK_GREEN;
         }
     }
 
-    pub fn bg_color(&self, visible: bool) -> Color {
-        if !self.discovered || !self.blocking || !visible {
+    pub fn bg_color(&self, discovered: bool, visible: bool) -> Color {
+        if !discovered || !self.blocking || !visible {
             return colors::DARKEST_GREY;
         }
 
         if visible {
-            return colors::DARKER_GREY;
+            return colors::lerp(colors::BLACK, colors::DARKER_GREY, self.shade());
         } else {
             return colors::DARKEST_GREY;
         }
     }
+}
 
-    pub fn update(&mut self, visible: bool) {
-        self.discovered = self.discovered || visible;
-    }
+// what a tile is made of, bedrock has no spec
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TileSpec {
+    pub wall: bool,
+    pub room: i32,
+    pub light: f32,
+    pub door: Option<DoorState>,
 }
 
 pub struct TileMap {
@@ -81,37 +215,66 @@ pub struct TileMap {
     width: i32,
     height: i32,
     rooms: i32,
+    // the tiles each fov has ever seen, keyed by fov index
+    discovered: HashMap<usize, Vec<bool>>,
+    // the fovs whose memory is drawn and planned with
+    shown: Vec<usize>,
+    // the tiles changed during the loop as they were at its start
+    pristine: HashMap<(i32, i32), Tile>,
+    // counts the changes to the tiles, so derived data knows when it is stale
+    revision: u32,
 }
 
 impl TileMap {
-    pub fn new() -> Self {
-        let map = vec![vec![Tile::bedrock(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    pub fn new(width: i32, height: i32) -> Self {
+        let map = vec![vec![Tile::bedrock(); height.max(0) as usize]; width.max(0) as usize];
         TileMap {
-            width: MAP_WIDTH,
-            height: MAP_HEIGHT,
+            width: width,
+            height: height,
             map: map,
-            rooms: 0
+            rooms: 0,
+            discovered: HashMap::new(),
+            shown: vec![],
+            pristine: HashMap::new(),
+            revision: 0,
         }
     }
 
-    pub fn update(&mut self, tcod: &Tcod) {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let visible = tcod.is_in_fov((x,y));
-                self.map[x as usize][y as usize].update(visible);
-            }
+    pub fn track_fov(&mut self, index: usize) {
+        let size = (self.width * self.height) as usize;
+        self.discovered.insert(index, vec![false; size]);
+        self.shown.push(index);
+    }
+
+    // only the memory of these fovs is drawn and used for planning
+    pub fn show(&mut self, fovs: &[usize]) {
+        self.shown = fovs.iter()
+            .filter(|i| self.discovered.contains_key(i))
+            .cloned()
+            .collect();
+    }
+
+    pub fn discover(&mut self, index: usize, p: (i32, i32)) {
+        if self.get(p).is_none() {
+            return;
+        }
+        let width = self.width;
+        if let Some(memory) = self.discovered.get_mut(&index) {
+            memory[(p.1 * width + p.0) as usize] = true;
         }
     }
 
-    pub fn draw(&self, tcod: &mut Tcod, viewport: &Viewport) {
+    // a revealed map is drawn as if everything was discovered and in sight
+    pub fn draw(&self, tcod: &mut Tcod, viewport: &Viewport, reveal: bool) {
         let default = Tile::bedrock();
         for pixel in viewport.into_iter() {
             let tile = self.get(pixel).unwrap_or(&default);
-            if let Some(character) = tile.character() {
+            let discovered = reveal || self.is_discovered(pixel);
+            if let Some(character) = tile.character(discovered) {
                 let p = viewport.transform(pixel);
-                let visible = tcod.is_in_fov(pixel);
-                let fg_color = self.map[pixel.0 as usize][pixel.1 as usize].fg_color(visible);
-                let bg_color = self.map[pixel.0 as usize][pixel.1 as usize].bg_color(visible);
+                let visible = reveal || tcod.fovs.is_in_fov(pixel);
+                let fg_color = tile.fg_color(discovered, visible);
+                let bg_color = tile.bg_color(discovered, visible);
                 tcod.render(p, bg_color, fg_color, character);
             }
         }
@@ -120,7 +283,8 @@ impl TileMap {
     pub fn create_room<T>(self: &mut TileMap, room: &T) where T: Shape {
         let id = self.rooms;
         self.rooms += 1;
-        for pos in room.into_iter() {
+        let (width, height) = (self.width, self.height);
+        for pos in room.into_iter().filter(|p| p.0 >= 0 && p.0 < width && p.1 >= 0 && p.1 < height) {
             let tile = if room.is_boundary(pos) {
                 Tile::wall(id)
             } else {
@@ -128,10 +292,12 @@ impl TileMap {
             };
             self.map[pos.0 as usize][pos.1 as usize] = tile;
         }
+        self.update_glyphs(Rect::new(0, 0, width, height));
     }
 
     pub fn create_anti_room<T>(self: &mut TileMap, room: &T) where T: Shape {
-        for pos in room.into_iter() {
+        let (width, height) = (self.width, self.height);
+        for pos in room.into_iter().filter(|p| p.0 >= 0 && p.0 < width && p.1 >= 0 && p.1 < height) {
             if let Some(id) = self.map[pos.0 as usize][pos.1 as usize].room {
                 let tile = if room.is_boundary(pos) {
                     Tile::wall(id)
@@ -141,12 +307,16 @@ impl TileMap {
                 self.map[pos.0 as usize][pos.1 as usize] = tile;
             }
         }
+        self.update_glyphs(Rect::new(0, 0, width, height));
     }
 
+    // where the corridor breaks through the wall of a room it gets a door
     pub fn create_corridor(self: &mut TileMap, corridor: &Rect) {
         let id = self.rooms;
         self.rooms += 1;
-        for pos in corridor.into_iter() {
+        let (width, height) = (self.width, self.height);
+        let mut doorways = vec![];
+        for pos in corridor.into_iter().filter(|p| p.0 >= 0 && p.0 < width && p.1 >= 0 && p.1 < height) {
             let is_wall = corridor.is_boundary(pos);
 
             let tile;
@@ -154,6 +324,9 @@ impl TileMap {
                 let was_wall = self.map[pos.0 as usize][pos.1 as usize].wall;
                 tile = if was_wall && is_wall {
                     Tile::wall(old_id)
+                } else if was_wall {
+                    doorways.push(pos);
+                    Tile::door(old_id, DoorState::Closed)
                 } else {
                     Tile::floor(old_id)
                 };
@@ -161,19 +334,36 @@ impl TileMap {
                 tile = if is_wall {
                     Tile::wall(id)
                 } else {
-                    Tile::floor(id)
+                    Tile::floor(id).with_light(CORRIDOR_LIGHT)
                 };
             }
             self.map[pos.0 as usize][pos.1 as usize] = tile;
         }
+        // a doorway needs a frame on two sides and a way through on the others,
+        // where the corridor runs along a wall it is just floor
+        let framed: Vec<bool> = doorways.iter()
+            .map(|p| {
+                let (n, e, s, w) = self.frame(*p);
+                (n && s && !e && !w) || (e && w && !n && !s)
+            })
+            .collect();
+        for (pos, framed) in doorways.into_iter().zip(framed) {
+            if !framed {
+                let tile = &mut self.map[pos.0 as usize][pos.1 as usize];
+                *tile = Tile::floor(tile.room.unwrap());
+            }
+        }
+        self.update_glyphs(Rect::new(0, 0, width, height));
     }
 
     pub fn draw_line(self: &mut TileMap, line: &Line) {
-        for pos in line.into_iter() {
+        let (width, height) = (self.width, self.height);
+        for pos in line.into_iter().filter(|p| p.0 >= 0 && p.0 < width && p.1 >= 0 && p.1 < height) {
             if let Some(id) = self.map[pos.0 as usize][pos.1 as usize].room {
                 self.map[pos.0 as usize][pos.1 as usize] = Tile::wall(id);
             }
         }
+        self.update_glyphs(Rect::new(0, 0, width, height));
     }
 
     fn get(self: &TileMap, p: (i32, i32)) -> Option<&Tile> {
@@ -184,11 +374,44 @@ impl TileMap {
         }
     }
 
+    pub fn width(self: &TileMap) -> i32 {
+        self.width
+    }
+
+    pub fn height(self: &TileMap) -> i32 {
+        self.height
+    }
+
+    pub fn spec(self: &TileMap, p: (i32, i32)) -> Option<TileSpec> {
+        self.get(p).and_then(|t| t.room.map(|room| TileSpec { wall: t.wall, room: room, light: t.ambient, door: t.door }))
+    }
+
+    pub fn set_spec(self: &mut TileMap, p: (i32, i32), spec: Option<TileSpec>) {
+        if self.get(p).is_none() {
+            return;
+        }
+        let tile = match spec {
+            Some(spec) => {
+                self.rooms = self.rooms.max(spec.room + 1);
+                let tile = match spec.door {
+                    Some(state) => Tile::door(spec.room, state),
+                    None if spec.wall => Tile::wall(spec.room),
+                    None => Tile::floor(spec.room),
+                };
+                tile.with_light(spec.light)
+            },
+            None => Tile::bedrock(),
+        };
+        self.map[p.0 as usize][p.1 as usize] = tile;
+        self.update_glyphs(Rect::new(p.0 - 1, p.1 - 1, 3, 3));
+    }
+
     pub fn is_discovered(self: &TileMap, p: (i32, i32)) -> bool {
-        match self.get(p) {
-            Some(t) => t.discovered,
-            None => true,
+        if self.get(p).is_none() {
+            return true;
         }
+        let index = (p.1 * self.width + p.0) as usize;
+        self.shown.iter().any(|i| self.discovered[i][index])
     }
 
     pub fn is_blocking(self: &TileMap, p: (i32, i32)) -> bool {
@@ -200,11 +423,43 @@ impl TileMap {
 
     pub fn is_sight_blocking(self: &TileMap, p: (i32, i32)) -> bool {
         match self.get(p) {
-            Some(t) => t.blocking,
+            Some(t) => t.blocking && !t.holed,
             None => true,
         }
     }
 
+    pub fn revision(self: &TileMap) -> u32 {
+        self.revision
+    }
+
+    pub fn light_level(self: &TileMap, p: (i32, i32)) -> f32 {
+        match self.get(p) {
+            Some(t) => t.light,
+            None => 0.0,
+        }
+    }
+
+    pub fn room_at(self: &TileMap, p: (i32, i32)) -> Option<i32> {
+        self.get(p).and_then(|t| t.room)
+    }
+
+    // switches the ceiling lamps back to their ambient light, except in dark rooms
+    pub fn reset_light(self: &mut TileMap, dark_rooms: &HashSet<i32>) {
+        for column in self.map.iter_mut() {
+            for tile in column.iter_mut() {
+                let dark = tile.room.map_or(false, |r| dark_rooms.contains(&r));
+                tile.light = if dark { 0.0 } else { tile.ambient };
+            }
+        }
+    }
+
+    pub fn add_light(self: &mut TileMap, p: (i32, i32), light: f32) {
+        if self.get(p).is_some() {
+            let tile = &mut self.map[p.0 as usize][p.1 as usize];
+            tile.light = (tile.light + light).min(1.0);
+        }
+    }
+
     pub fn is_wall(self: &TileMap, p: (i32, i32)) -> bool {
         match self.get(p) {
             Some(t) => t.wall,
@@ -212,6 +467,164 @@ impl TileMap {
         }
     }
 
+    pub fn door(self: &TileMap, p: (i32, i32)) -> Option<DoorState> {
+        self.get(p).and_then(|t| t.door)
+    }
+
+    // only door tiles change their state
+    pub fn set_door(self: &mut TileMap, p: (i32, i32), state: DoorState) {
+        if self.door(p).is_some() {
+            self.remember(p);
+            let tile = &mut self.map[p.0 as usize][p.1 as usize];
+            tile.door = Some(state);
+            tile.blocking = state.is_blocking();
+        }
+        self.update_glyphs(Rect::new(p.0 - 1, p.1 - 1, 3, 3));
+    }
+
+    // the state of a door at the start of the loop
+    pub fn initial_door(self: &TileMap, p: (i32, i32)) -> Option<DoorState> {
+        match self.pristine.get(&p) {
+            Some(tile) => tile.door,
+            None => self.door(p),
+        }
+    }
+
+    fn remember(self: &mut TileMap, p: (i32, i32)) {
+        let tile = self.map[p.0 as usize][p.1 as usize].clone();
+        self.pristine.entry(p).or_insert(tile);
+    }
+
+    // blasts wear walls down to rubble and doors until they break, anything
+    // else leaves a hole to look through. True if the tile changed
+    pub fn damage(self: &mut TileMap, p: (i32, i32), amount: f32, explosive: bool) -> bool {
+        if !self.get(p).map_or(false, |t| t.is_breakable()) {
+            return false;
+        }
+        self.remember(p);
+        let changed = {
+            let tile = &mut self.map[p.0 as usize][p.1 as usize];
+            if explosive {
+                tile.durability -= amount;
+                if tile.durability <= 0.0 {
+                    let room = tile.room.unwrap();
+                    let mut broken = match tile.door {
+                        Some(_) => Tile::door(room, DoorState::Broken),
+                        None => Tile::rubble(room),
+                    };
+                    broken.ambient = tile.ambient;
+                    broken.light = tile.light;
+                    *tile = broken;
+                    true
+                } else {
+                    false
+                }
+            } else if tile.blocking && !tile.holed {
+                tile.holed = true;
+                true
+            } else {
+                false
+            }
+        };
+        if changed {
+            self.update_glyphs(Rect::new(p.0 - 1, p.1 - 1, 3, 3));
+        }
+        changed
+    }
+
+    pub fn wall_damage(self: &TileMap, p: (i32, i32)) -> WallDamage {
+        match self.get(p) {
+            Some(t) if t.rubble => WallDamage::Rubble,
+            Some(t) if t.holed => WallDamage::Holed,
+            _ => WallDamage::Intact,
+        }
+    }
+
+    // the tiles shot or blown up during the loop
+    pub fn damaged(self: &TileMap) -> Vec<((i32, i32), WallDamage)> {
+        self.pristine.keys()
+            .map(|p| (*p, self.wall_damage(*p)))
+            .filter(|&(_, damage)| damage != WallDamage::Intact)
+            .collect()
+    }
+
+    // damages a tile like the host did, intact puts back how it was at the start of the loop
+    pub fn set_wall_damage(self: &mut TileMap, p: (i32, i32), damage: WallDamage) {
+        if self.get(p).is_none() {
+            return;
+        }
+        match damage {
+            WallDamage::Intact => match self.pristine.remove(&p) {
+                Some(tile) => self.map[p.0 as usize][p.1 as usize] = tile,
+                None => return,
+            },
+            WallDamage::Holed => {
+                self.remember(p);
+                self.map[p.0 as usize][p.1 as usize].holed = true;
+            },
+            WallDamage::Rubble => {
+                self.remember(p);
+                let tile = &mut self.map[p.0 as usize][p.1 as usize];
+                if let Some(room) = tile.room {
+                    let mut rubble = Tile::rubble(room);
+                    rubble.ambient = tile.ambient;
+                    rubble.light = tile.light;
+                    *tile = rubble;
+                }
+            },
+        }
+        self.update_glyphs(Rect::new(p.0 - 1, p.1 - 1, 3, 3));
+    }
+
+    // puts back what was opened, shot and blown up during the loop
+    pub fn restore(self: &mut TileMap) {
+        let pristine = mem::replace(&mut self.pristine, HashMap::new());
+        for (p, tile) in pristine {
+            self.map[p.0 as usize][p.1 as usize] = tile;
+            self.update_glyphs(Rect::new(p.0 - 1, p.1 - 1, 3, 3));
+        }
+    }
+
+    // which of the neighbours are walls or doors
+    fn frame(self: &TileMap, p: (i32, i32)) -> (bool, bool, bool, bool) {
+        let framing = |p: (i32, i32)| self.is_wall(p) || self.door(p).is_some();
+        (framing((p.0, p.1 - 1)), framing((p.0 + 1, p.1)), framing((p.0, p.1 + 1)), framing((p.0 - 1, p.1)))
+    }
+
+    // walls on the outside of the floor get double lines, those between rooms single ones.
+    // A shut door runs along its wall, an open one swings out across it
+    fn glyph(self: &TileMap, p: (i32, i32)) -> Option<char> {
+        let (n, e, s, w) = self.frame(p);
+        match self.get(p) {
+            Some(&Tile { door: Some(state), .. }) => Some(match state {
+                DoorState::Closed | DoorState::Locked(_) => self.create_box_character(n, e, s, w),
+                DoorState::Open => self.create_box_character(e, n, w, s),
+                DoorState::Broken => chars::BLOCK1,
+            }),
+            Some(&Tile { rubble: true, .. }) => Some(chars::BLOCK1),
+            Some(&Tile { wall: true, .. }) => {
+                let outer = Rect::new(p.0 - 1, p.1 - 1, 3, 3).into_iter()
+                    .any(|q| self.get(q).map_or(true, |t| t.room.is_none()));
+                if outer {
+                    Some(self.create_dbox_character(n, e, s, w))
+                } else {
+                    Some(self.create_box_character(n, e, s, w))
+                }
+            },
+            _ => None,
+        }
+    }
+
+    // recomputes the glyphs of the tiles in the area, a changed tile changes its neighbours
+    fn update_glyphs(self: &mut TileMap, area: Rect) {
+        self.revision += 1;
+        let (width, height) = (self.width, self.height);
+        for p in area.into_iter().filter(|p| p.0 >= 0 && p.0 < width && p.1 >= 0 && p.1 < height) {
+            let glyph = self.glyph(p);
+            self.map[p.0 as usize][p.1 as usize].glyph = glyph;
+        }
+    }
+
     fn create_dbox_character(self: &TileMap, n: bool, e: bool, s: bool, w: bool) -> char {
         match (n, e, s, w) {
             (true, true, false, false) => chars::DSW,
@@ -263,4 +676,53 @@ impl TileMap {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use tcod::chars::{ self };
+    use tile_map::{ TileMap, TileSpec, DoorState };
+    use geometry::{ Rect, Line };
+
+    #[test]
+    fn walls_connect_with_double_lines_outside_and_single_lines_inside() {
+        let mut tiles = TileMap::new(10, 10);
+        tiles.create_room(&Rect::new(0, 0, 6, 5));
+        tiles.draw_line(&Line::new(3, 1, 3, 3));
+        let glyph = |tiles: &TileMap, p: (i32, i32)| tiles.get(p).and_then(|t| t.character(true));
+        assert_eq!(glyph(&tiles, (0, 0)), Some(chars::DNW));
+        assert_eq!(glyph(&tiles, (3, 0)), Some(chars::DTEES));
+        assert_eq!(glyph(&tiles, (3, 2)), Some(chars::VLINE));
+        assert_eq!(glyph(&tiles, (1, 1)), Some('.'));
+
+        // a door in the inner wall opens across it
+        tiles.set_spec((3, 2), Some(TileSpec { wall: false, room: 0, light: 1.0, door: Some(DoorState::Closed) }));
+        assert_eq!(glyph(&tiles, (3, 2)), Some(chars::VLINE));
+        assert_eq!(glyph(&tiles, (3, 1)), Some(chars::VLINE));
+        tiles.set_door((3, 2), DoorState::Open);
+        assert_eq!(glyph(&tiles, (3, 2)), Some(chars::HLINE));
+
+        // taking a wall away updates its neighbours
+        tiles.set_spec((3, 1), Some(TileSpec { wall: false, room: 0, light: 1.0, door: None }));
+        assert_eq!(glyph(&tiles, (3, 0)), Some(chars::DHLINE));
+    }
 
+    #[test]
+    fn corridors_break_through_walls_with_doors() {
+        let mut tiles = TileMap::new(20, 20);
+        tiles.create_room(&Rect::new(0, 0, 10, 10));
+        tiles.create_corridor(&Rect::new(4, 8, 3, 8));
+        assert_eq!(tiles.door((5, 9)), Some(DoorState::Closed));
+        assert_eq!(tiles.room_at((5, 9)), tiles.room_at((5, 5)));
+        assert!(tiles.is_blocking((5, 9)) && tiles.is_sight_blocking((5, 9)));
+        assert!(tiles.is_wall((4, 9)) && tiles.is_wall((6, 9)));
+
+        tiles.set_door((5, 9), DoorState::Open);
+        assert!(!tiles.is_blocking((5, 9)));
+        tiles.set_door((5, 5), DoorState::Open);
+        assert_eq!(tiles.door((5, 5)), None);
+
+        // running along the wall leaves no row of doors
+        tiles.create_corridor(&Rect::new(0, 8, 10, 3));
+        assert_eq!(tiles.door((2, 9)), None);
+        assert!(!tiles.is_blocking((2, 9)));
+    }
+}
@@ -1,17 +1,24 @@
-use specs::{ World, Entity };
+use specs::{ World, Entity, Join };
 use std::collections::VecDeque;
 use engine::tcod::{ Tcod };
+use engine::fov::{ Fovs };
 use tcod::colors::{ self, Color };
 use std::collections::{ HashMap };
+use std::sync::{ Arc };
 use maps::{ Maps };
+use floor_plan::{ FloorPlan, FloorPlanError, PlayerSpawn, InteractableSpawn, NpcSpawn, ItemSpawn };
 
 use components::appearance::{ Renderable, Layer0, Layer1 };
-use components::player::{ Player, Fov, Equipment };
-use components::space::{ Viewport, Spawn, Position, Level };
-use components::npc::{ Npc, NpcInstance };
+use components::player::{ Player, Fov, Equipment, Orders };
+use components::space::{ Viewport, Spawn, Position, Level, Vector };
+use components::npc::{ Npc, NpcInstance, Dialogue };
 use components::item::{ Item, ItemInstance };
 use components::common::{ Active, CharacterStats, Description };
-use components::interaction::{ Interactable, InteractableInstance };
+use components::interaction::{ Interactable };
+use interactables::{ InteractableRegistry, InteractableKind };
+use components::stealth::{ Awareness, Vision };
+use components::schedule::{ Schedule, format_clock };
+use components::faction::{ Faction, FactionInstance };
 use components::inventory::{ Inventory };
 
 
@@ -22,57 +29,57 @@ pub struct Tower {
 }
 
 impl Tower {
-    pub fn new(levels: &[Level]) -> Self {
-        let mut maps = HashMap::new();
-        for level in levels {
-            maps.insert(*level, Maps::new());
-        }
+    pub fn new() -> Self {
         Tower {
-            maps: maps,
+            maps: HashMap::new(),
             highlights: vec![],
             highlight_color: colors::LIGHT_GREEN,
         }
     }
 
-    fn create_player(&mut self, x: f32, y: f32, active: bool, name: String,
-                     tcod: &mut Tcod, world: &mut World) {
-        let fov_map = self.create_fov(tcod);
+    fn create_player(&mut self, x: f32, y: f32, level: Level, active: bool, name: String,
+                     world: &mut World) -> Entity {
         let mut builder = world.create_entity()
             .with(Player)
-            .with(Spawn::for_location(x, y, Level::Tower(0)))
+            .with(Spawn::for_location(x, y, level))
             .with(Renderable { character: '@', color: colors::WHITE })
             .with(CharacterStats { health: 100.0, max_health: 100.0 } )
             .with(Description { name: name, description: "".into() })
-            .with(Fov { fov_map: fov_map})
             .with(Inventory::new())
             .with(Equipment::new())
+            .with(Orders::new())
             .with(Layer1);
         if active {
             builder = builder.with(Active);
         }
-        builder.build();
+        builder.build()
     }
 
-    fn create_npc(&mut self, x: f32, y: f32, level: Level,
-                  instance: NpcInstance, world: &mut World) -> Entity {
+    fn create_npc(&mut self, x: f32, y: f32, level: Level, facing: Vector,
+                  instance: NpcInstance, faction: FactionInstance, world: &mut World) -> Entity {
         let n = Npc { instance: instance };
         let builder = world.create_entity()
             .with(Spawn::for_location(x, y, level))
             .with(n.get_renderable())
             .with(n.get_description())
             .with(n.get_stats())
+            .with(n.get_vision(facing))
+            .with(n.get_hearing())
+            .with(Awareness::new())
             .with(Inventory::new())
+            .with(Faction::new(faction))
             .with(n)
             .with(Layer1);
         builder.build()
     }
 
-    fn create_interactable(&mut self, x: f32, y: f32, level: Level,
-                           instance: InteractableInstance, world: &mut World) -> Entity {
-        let interactable = Interactable::new(instance);
+    fn create_interactable(&mut self, x: f32, y: f32, level: Level, kind: Arc<InteractableKind>,
+                           security_level: i32, heading: f32, world: &mut World) -> Entity {
+        let interactable = Interactable::new(kind, security_level).facing(heading);
         let builder = world.create_entity()
             .with(Spawn::for_location(x, y, level))
             .with(interactable.get_renderable())
+            .with(interactable.get_description())
             .with(interactable)
             .with(Layer0);
         builder.build()
@@ -95,6 +102,14 @@ impl Tower {
         world.write().insert(owner, inventory);
     }
 
+    fn create_dialogue(&mut self, owner: Entity, id: &str, world: &mut World) {
+        world.write().insert(owner, Dialogue::new(id));
+    }
+
+    fn create_schedule(&mut self, owner: Entity, schedule: Schedule, world: &mut World) {
+        world.write().insert(owner, schedule);
+    }
+
     fn create_item(&mut self, x: f32, y: f32, level: Level,
                    instance: ItemInstance, world: &mut World) {
         let i = Item { instance: instance };
@@ -111,38 +126,150 @@ impl Tower {
             .build();
     }
 
-    pub fn build(&mut self, tcod: &mut Tcod, world: &mut World) {
+    // loads the floor plans and spawns everything on them
+    pub fn build(&mut self, floors: &[(Level, FloorPlan)], tcod: &mut Tcod,
+                 world: &mut World) -> Result<(), FloorPlanError> {
+        for player in self.spawn(floors, world)? {
+            let fov_map = self.create_fov(&mut tcod.fovs);
+            world.write().insert(player, Fov { fov_map: fov_map });
+        }
+        Ok(())
+    }
+
+    // the floors and everything on them, the players it returns still need their fov
+    pub fn spawn(&mut self, floors: &[(Level, FloorPlan)],
+                 world: &mut World) -> Result<Vec<Entity>, FloorPlanError> {
+        // a plan put together in code may name kinds nobody defined, then nothing is spawned
+        let mut kinds = vec![];
         {
-            let lvl0 = self.get_mut(&Level::Tower(0)).unwrap();
-            lvl0.build();
+            let registry = world.read_resource::<InteractableRegistry>();
+            for plan in floors.iter().map(|f| &f.1) {
+                for i in plan.interactables.iter() {
+                    match registry.get(&i.kind) {
+                        Some(kind) => kinds.push(kind),
+                        None => return Err(FloorPlanError {
+                            line: 0,
+                            column: 0,
+                            message: format!("unknown interactable kind '{}' at {}, {}", i.kind, i.position.0, i.position.1),
+                        }),
+                    }
+                }
+            }
         }
+        let mut kinds = kinds.into_iter();
 
-        self.create_player(15.0, 15.0, true, "Colton".into(), tcod, world);
-        self.create_player(16.0, 16.0, false, "Gage".into(), tcod, world);
+        let mut players = vec![];
+        for &(level, ref plan) in floors {
+            self.add_floor(level, plan);
 
-        self.create_interactable(25.0, 21.0, Level::Tower(0),
-                                 InteractableInstance::KeyDoor(3, false), world);
+            for player in plan.players.iter() {
+                let (x, y) = player.position;
+                players.push(self.create_player(x as f32, y as f32, level, player.active, player.name.clone(), world));
+            }
+            for (i, kind) in plan.interactables.iter().zip(kinds.by_ref()) {
+                let (x, y) = i.position;
+                self.create_interactable(x as f32, y as f32, level, kind, i.security_level,
+                                         i.heading.to_radians(), world);
+            }
+            for npc in plan.npcs.iter() {
+                let (x, y) = npc.position;
+                let facing = Vector { x: npc.facing.0, y: npc.facing.1 };
+                let id = self.create_npc(x as f32, y as f32, level, facing, npc.instance, npc.faction, world);
+                if !npc.inventory.is_empty() {
+                    self.create_inventory(id, npc.inventory.clone(), world);
+                }
+                if let Some(ref dialogue) = npc.dialogue {
+                    self.create_dialogue(id, dialogue, world);
+                }
+                if !npc.errands.is_empty() {
+                    let schedule = npc.errands.iter()
+                        .fold(Schedule::new(), |s, &(ref clock, target)| s.at(clock, level, target));
+                    self.create_schedule(id, schedule, world);
+                }
+            }
+            for item in plan.items.iter() {
+                let (x, y) = item.position;
+                self.create_item(x as f32, y as f32, level, item.instance, world);
+            }
+        }
+        Ok(players)
+    }
 
-        {
-            let guard = self.create_npc(31.0, 24.0, Level::Tower(0),
-                                        NpcInstance::Guard, world);
-            self.create_inventory(guard, vec![ItemInstance::FlickKnife,
-                                              ItemInstance::Watch,
-                                              ItemInstance::KeyCard(3)], world);
+    // the floor plan of a floor with everything spawned on it at the start of the loop
+    pub fn export(&self, level: &Level, world: &World) -> Option<FloorPlan> {
+        let mut plan = match self.maps.get(level) {
+            Some(maps) => maps.export(),
+            None => return None,
+        };
+        let entities = world.entities();
+        let spawns = world.read::<Spawn>();
+        let items = world.read::<Item>();
+        let actives = world.read::<Active>();
+        let dialogues = world.read::<Dialogue>();
+        let schedules = world.read::<Schedule>();
+        let on_floor = |id: Entity| match spawns.get(id).and_then(|s| s.location) {
+            Some((x, y, l)) if l == *level => Some((x as i32, y as i32)),
+            _ => None,
+        };
+
+        for (id, _, description) in (&*entities, &world.read::<Player>(), &world.read::<Description>()).join() {
+            if let Some(position) = on_floor(id) {
+                plan.players.push(PlayerSpawn {
+                    name: description.name.clone(),
+                    position: position,
+                    active: actives.get(id).is_some(),
+                });
+            }
+        }
+        for (id, interactable) in (&*entities, &world.read::<Interactable>()).join() {
+            if let Some(position) = on_floor(id) {
+                plan.interactables.push(InteractableSpawn {
+                    kind: interactable.kind().to_string(),
+                    position: position,
+                    security_level: interactable.initial_level(),
+                    heading: interactable.heading.to_degrees().round(),
+                });
+            }
         }
-        self.create_npc(29.0, 24.0, Level::Tower(0), NpcInstance::Technician, world);
-        self.create_npc(31.0, 29.0, Level::Tower(0), NpcInstance::Accountant, world);
+        for (id, npc, vision, faction) in (&*entities, &world.read::<Npc>(), &world.read::<Vision>(),
+                                           &world.read::<Faction>()).join() {
+            if let Some(position) = on_floor(id) {
+                let inventory = (&spawns, &items).join()
+                    .filter(|&(spawn, _)| spawn.owner == Some(id))
+                    .map(|(_, item)| item.instance)
+                    .collect();
+                let errands = schedules.get(id).map_or(vec![], |schedule| schedule.errands().iter()
+                                                       .map(|e| (format_clock(e.at), e.target))
+                                                       .collect());
+                plan.npcs.push(NpcSpawn {
+                    instance: npc.instance,
+                    position: position,
+                    facing: (vision.initial_direction.x, vision.initial_direction.y),
+                    faction: faction.instance,
+                    inventory: inventory,
+                    dialogue: dialogues.get(id).map(|d| d.id.clone()),
+                    errands: errands,
+                });
+            }
+        }
+        for (id, item) in (&*entities, &items).join() {
+            if let Some(position) = on_floor(id) {
+                plan.items.push(ItemSpawn { instance: item.instance, position: position });
+            }
+        }
+        Some(plan)
+    }
 
-        self.create_item(14.0, 15.0, Level::Tower(0), ItemInstance::FlickKnife, world);
-        self.create_item(13.0, 15.0, Level::Tower(0), ItemInstance::DartGun, world);
-        self.create_item(33.0, 25.0, Level::Tower(0), ItemInstance::Simstim, world);
-        self.create_item(23.0, 25.0, Level::Tower(0), ItemInstance::HitachiRam, world);
-        self.create_item(28.0, 21.0, Level::Tower(0), ItemInstance::Shuriken, world);
+    // the tiles and areas of a floor without spawning anything on it
+    pub fn add_floor(&mut self, level: Level, plan: &FloorPlan) {
+        self.maps.insert(level, Maps::from_plan(plan));
     }
 
+    // back to the start of the loop, without anybody on the floors
     pub fn clear(&mut self) {
         for (_, maps) in &mut self.maps {
             maps.clear_all();
+            maps.restore();
         }
     }
 
@@ -154,15 +281,16 @@ impl Tower {
         self.maps.get(level)
     }
 
-    pub fn update(&mut self, tcod: &mut Tcod) {
-        for (_, maps) in &mut self.maps {
-            maps.update(tcod);
-        }
+    // from the lowest floor up
+    pub fn levels(&self) -> Vec<Level> {
+        let mut levels: Vec<Level> = self.maps.keys().cloned().collect();
+        levels.sort_by_key(|&Level::Tower(floor)| floor);
+        levels
     }
 
-    pub fn draw(&self, level: &Level, tcod: &mut Tcod, viewport: &Viewport) {
+    pub fn draw(&self, level: &Level, tcod: &mut Tcod, viewport: &Viewport, reveal: bool) {
         if let Some(map) = self.maps.get(level) {
-            map.draw(tcod, viewport);
+            map.draw(tcod, viewport, reveal);
         }
 
         for pos in self.highlights.iter() {
@@ -174,14 +302,22 @@ impl Tower {
         }
     }
 
-    pub fn create_fov(&self, tcod: &mut Tcod) -> HashMap<Level, usize> {
+    pub fn create_fov(&mut self, fovs: &mut Fovs) -> HashMap<Level, usize> {
         let mut result = HashMap::new();
-        for (level, _) in &self.maps {
-            result.insert(*level, tcod.create_fov());
+        for (level, maps) in &mut self.maps {
+            let index = fovs.create_fov(maps.width(), maps.height());
+            maps.track_fov(index);
+            result.insert(*level, index);
         }
         result
     }
 
+    pub fn show(&mut self, fovs: &[usize]) {
+        for (_, maps) in &mut self.maps {
+            maps.show(fovs);
+        }
+    }
+
     pub fn clear_highlights(&mut self) {
         self.highlights.clear();
     }
@@ -199,3 +335,83 @@ impl Tower {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use specs::{ World, Join };
+    use tower::{ Tower };
+    use floor_plan::{ FloorPlan };
+    use interactables::{ InteractableRegistry };
+    use components::appearance::{ Renderable, Layer0, Layer1 };
+    use components::player::{ Player, Equipment, Orders };
+    use components::space::{ Spawn, Level };
+    use components::npc::{ Npc, Dialogue };
+    use components::item::{ Item };
+    use components::common::{ Active, CharacterStats, Description, ItemStats };
+    use components::interaction::{ Interactable };
+    use components::stealth::{ Awareness, Vision, Hearing };
+    use components::schedule::{ Schedule };
+    use components::faction::{ Faction };
+    use components::inventory::{ Inventory };
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Player>();
+        world.register::<Spawn>();
+        world.register::<Renderable>();
+        world.register::<Layer0>();
+        world.register::<Layer1>();
+        world.register::<CharacterStats>();
+        world.register::<Description>();
+        world.register::<Inventory>();
+        world.register::<Equipment>();
+        world.register::<Orders>();
+        world.register::<Active>();
+        world.register::<Npc>();
+        world.register::<Vision>();
+        world.register::<Hearing>();
+        world.register::<Awareness>();
+        world.register::<Faction>();
+        world.register::<Dialogue>();
+        world.register::<Schedule>();
+        world.register::<Interactable>();
+        world.register::<Item>();
+        world.register::<ItemStats>();
+        world.add_resource(InteractableRegistry::parse(include_str!("../data/interactables.txt")).unwrap());
+        world
+    }
+
+    #[test]
+    fn export_matches_the_spawned_plan() {
+        let mut world = world();
+        let plan = FloorPlan::parse(include_str!("../data/tower0.map")).unwrap();
+        let mut tower = Tower::new();
+        tower.spawn(&[(Level::Tower(0), plan.clone())], &mut world).unwrap();
+        let exported = tower.export(&Level::Tower(0), &world).unwrap();
+
+        assert!(exported.size == plan.size);
+        let (width, height) = plan.size;
+        for y in 0..height {
+            for x in 0..width {
+                assert!(exported.tile((x, y)) == plan.tile((x, y)));
+            }
+        }
+        assert!(exported.areas == plan.areas);
+        assert!(exported.players == plan.players);
+        assert!(exported.interactables == plan.interactables);
+        assert!(exported.npcs == plan.npcs);
+        assert!(exported.items == plan.items);
+        assert!(tower.export(&Level::Tower(1), &world).is_none());
+    }
+
+    #[test]
+    fn unknown_interactables_spawn_nothing() {
+        let mut world = world();
+        let mut plan = FloorPlan::parse(include_str!("../data/tower0.map")).unwrap();
+        plan.interactables[0].kind = "vending_machine".into();
+        let mut tower = Tower::new();
+        assert!(tower.spawn(&[(Level::Tower(0), plan)], &mut world).is_err());
+        assert!(tower.levels().is_empty());
+        assert!(world.read::<Spawn>().join().next().is_none());
+    }
+}